@@ -135,6 +135,9 @@ namespace RemoteC.Core.Interop
         [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
         public static extern int remotec_capture_get_frame(IntPtr handle, ref FrameData frameData);
         
+        [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
+        public static extern void remotec_frame_release(ref FrameData frameData);
+        
         [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
         public static extern int remotec_capture_stop(IntPtr handle);
         
@@ -148,6 +151,15 @@ namespace RemoteC.Core.Interop
         [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
         public static extern IntPtr remotec_input_create();
         
+        [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
+        public static extern int remotec_input_set_capture(IntPtr handle, IntPtr captureHandle);
+        
+        [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
+        public static extern int remotec_input_set_viewer_size(IntPtr handle, uint width, uint height);
+        
+        [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
+        public static extern int remotec_input_set_stream_size(IntPtr handle, uint width, uint height);
+        
         [DllImport(LibraryName, CallingConvention = CallingConvention.Cdecl)]
         public static extern int remotec_input_mouse_move(IntPtr handle, int x, int y);
         
@@ -213,8 +225,15 @@ namespace RemoteC.Core.Interop
             public IntPtr data;
             public UIntPtr data_len;
             public ulong timestamp;
+            public IntPtr buffer;
+            public uint format;
         }
         
+        /// <summary>
+        /// FrameData.format value of 32-bit BGRA frames
+        /// </summary>
+        public const uint FrameFormatBgra8888 = 0;
+        
         /// <summary>
         /// FFI-safe monitor info structure
         /// </summary>
@@ -25,6 +25,8 @@ namespace RemoteC.Core.Interop
             public IntPtr InputHandle { get; set; }
             public IntPtr TransportHandle { get; set; }
             public IntPtr EncoderHandle { get; set; }
+            public (uint Width, uint Height) FrameSize { get; set; }
+            public ScreenFrame? LastFrame { get; set; }
         }
 
         public string Name => "RemoteC Rust Engine";
@@ -95,6 +97,10 @@ namespace RemoteC.Core.Interop
                 if (result != 0)
                     throw new Exception("Failed to start capture");
                 
+                // Map viewer input onto the captured area
+                if (RemoteCCore.remotec_input_set_capture(context.InputHandle, context.CaptureHandle) != 0)
+                    throw new Exception("Failed to map input onto the capture");
+                
                 _sessions[session.Id] = context;
                 return await Task.FromResult(session);
             }
@@ -131,12 +137,36 @@ namespace RemoteC.Core.Interop
             var frameData = new RemoteCCore.FrameData();
             var result = RemoteCCore.remotec_capture_get_frame(context.CaptureHandle, ref frameData);
             
+            // 1 means no new frame yet or an unchanged screen
+            if (result == 1 && context.LastFrame != null)
+                return await Task.FromResult(context.LastFrame);
+            
             if (result == 0 && frameData.data != IntPtr.Zero)
             {
-                // Copy frame data
+                // Copy frame data, then hand the native buffer back
                 var dataSize = (int)frameData.data_len.ToUInt32();
                 var data = new byte[dataSize];
-                Marshal.Copy(frameData.data, data, 0, dataSize);
+                try
+                {
+                    Marshal.Copy(frameData.data, data, 0, dataSize);
+                }
+                finally
+                {
+                    RemoteCCore.remotec_frame_release(ref frameData);
+                }
+                
+                // ScreenFrame carries no pixel format; viewers expect BGRA
+                if (frameData.format != RemoteCCore.FrameFormatBgra8888)
+                    throw new InvalidOperationException($"Unsupported frame format {frameData.format}");
+                
+                // Viewers report input positions in frame pixels
+                var frameSize = (frameData.width, frameData.height);
+                if (context.FrameSize != frameSize)
+                {
+                    RemoteCCore.remotec_input_set_stream_size(context.InputHandle, frameData.width, frameData.height);
+                    RemoteCCore.remotec_input_set_viewer_size(context.InputHandle, frameData.width, frameData.height);
+                    context.FrameSize = frameSize;
+                }
                 
                 var frame = new ScreenFrame
                 {
@@ -147,6 +177,7 @@ namespace RemoteC.Core.Interop
                     IsKeyFrame = true
                 };
                 
+                context.LastFrame = frame;
                 return await Task.FromResult(frame);
             }
             
@@ -292,6 +323,13 @@ namespace RemoteC.Core.Interop
 
                 // Start new capture
                 var result = RemoteCCore.remotec_capture_start(context.CaptureHandle);
+                if (result == 0)
+                {
+                    // Map viewer input onto the newly captured area
+                    result = RemoteCCore.remotec_input_set_capture(context.InputHandle, context.CaptureHandle);
+                    context.FrameSize = default;
+                    context.LastFrame = null;
+                }
                 return await Task.FromResult(result == 0);
             }
             catch (Exception ex)
@@ -354,6 +392,13 @@ namespace RemoteC.Core.Interop
 
                 // Start new capture
                 var result = RemoteCCore.remotec_capture_start(context.CaptureHandle);
+                if (result == 0)
+                {
+                    // Map viewer input onto the newly captured area
+                    result = RemoteCCore.remotec_input_set_capture(context.InputHandle, context.CaptureHandle);
+                    context.FrameSize = default;
+                    context.LastFrame = null;
+                }
                 return await Task.FromResult(result == 0);
             }
             catch (Exception ex)
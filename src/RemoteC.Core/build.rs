@@ -12,6 +12,10 @@ fn main() {
         "linux" => {
             println!("cargo:rustc-link-lib=X11");
             println!("cargo:rustc-link-lib=Xext");
+            println!("cargo:rustc-link-lib=Xrandr");
+            println!("cargo:rustc-link-lib=Xfixes");
+            println!("cargo:rustc-link-lib=Xdamage");
+            println!("cargo:rustc-link-lib=Xcomposite");
             println!("cargo:rustc-link-lib=Xtst");
         }
         "macos" => {
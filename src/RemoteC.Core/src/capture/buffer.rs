@@ -0,0 +1,259 @@
+//! Shared, pooled frame buffers
+//!
+//! [`FrameBuffer`] is an immutable, reference-counted pixel buffer: cloning
+//! a frame or handing it to the encoder, transport or FFI shares one
+//! allocation instead of copying it. Buffers filled from a [`BufferPool`]
+//! go back to the pool when the last reference is dropped, so steady-state
+//! capture reuses the same few allocations.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Released buffers a pool keeps by default
+///
+/// Covers a frame being captured, one queued by the capture driver and
+/// one in the encoder.
+pub const DEFAULT_POOL_CAPACITY: usize = 4;
+
+/// Buffer storage, returned to its pool (if any) on drop
+struct Storage {
+    data: Vec<u8>,
+    pool: Weak<PoolShared>,
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            pool.recycle(std::mem::take(&mut self.data));
+        }
+    }
+}
+
+/// Immutable, cheaply cloneable pixel buffer
+#[derive(Clone)]
+pub struct FrameBuffer {
+    storage: Arc<Storage>,
+}
+
+impl FrameBuffer {
+    /// Number of live references to this buffer
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.storage)
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.storage.data
+    }
+}
+
+impl AsRef<[u8]> for FrameBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<Vec<u8>> for FrameBuffer {
+    /// Wrap an unpooled buffer
+    fn from(data: Vec<u8>) -> Self {
+        Self {
+            storage: Arc::new(Storage { data, pool: Weak::new() }),
+        }
+    }
+}
+
+impl From<FrameBuffer> for bytes::Bytes {
+    /// Share the buffer with transport without copying
+    fn from(buffer: FrameBuffer) -> Self {
+        bytes::Bytes::from_owner(buffer)
+    }
+}
+
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for FrameBuffer {}
+
+impl fmt::Debug for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameBuffer").field("len", &self.len()).finish()
+    }
+}
+
+/// Buffer taken from a [`BufferPool`] and being filled
+///
+/// Dereferences to an empty `Vec` with the requested capacity. Call
+/// [`freeze`](Self::freeze) once filled; dropping it returns the
+/// allocation to the pool.
+pub struct PooledBuffer {
+    storage: Storage,
+}
+
+impl PooledBuffer {
+    /// Make the buffer immutable and shareable
+    pub fn freeze(self) -> FrameBuffer {
+        FrameBuffer {
+            storage: Arc::new(self.storage),
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.storage.data
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.storage.data
+    }
+}
+
+struct PoolShared {
+    free: Mutex<Vec<Vec<u8>>>,
+    capacity: usize,
+}
+
+impl PoolShared {
+    fn recycle(&self, mut data: Vec<u8>) {
+        data.clear();
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        if free.len() < self.capacity {
+            free.push(data);
+        }
+    }
+}
+
+/// Recycles frame allocations between captures
+///
+/// Clones share the same pool. Buffers outliving the pool are simply freed.
+#[derive(Clone)]
+pub struct BufferPool {
+    shared: Arc<PoolShared>,
+}
+
+impl BufferPool {
+    /// Create a pool keeping up to `capacity` released buffers
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(PoolShared {
+                free: Mutex::new(Vec::new()),
+                capacity,
+            }),
+        }
+    }
+
+    /// Take an empty buffer able to hold `capacity` bytes, reusing a
+    /// released allocation when one is free
+    pub fn take(&self, capacity: usize) -> PooledBuffer {
+        let recycled = {
+            let mut free = self.shared.free.lock().unwrap_or_else(|e| e.into_inner());
+            // Prefer a buffer that is already large enough; otherwise grow one
+            match free.iter().position(|data| data.capacity() >= capacity) {
+                Some(index) => free.swap_remove(index),
+                None => free.pop().unwrap_or_default(),
+            }
+        };
+
+        let mut data = recycled;
+        data.reserve_exact(capacity);
+        PooledBuffer {
+            storage: Storage {
+                data,
+                pool: Arc::downgrade(&self.shared),
+            },
+        }
+    }
+
+    /// Fill a pooled buffer with a copy of `data`
+    pub fn copy_from(&self, data: &[u8]) -> FrameBuffer {
+        let mut buffer = self.take(data.len());
+        buffer.extend_from_slice(data);
+        buffer.freeze()
+    }
+
+    /// Number of released buffers waiting for reuse
+    pub fn available(&self) -> usize {
+        self.shared.free.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOL_CAPACITY)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("available", &self.available())
+            .field("capacity", &self.shared.capacity)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_return_to_pool_when_released() {
+        let pool = BufferPool::new(2);
+        let mut buffer = pool.take(16);
+        buffer.extend_from_slice(&[7; 16]);
+        let ptr = buffer.as_ptr();
+
+        let frame = buffer.freeze();
+        let shared = frame.clone();
+        assert_eq!(frame.ref_count(), 2);
+        drop(frame);
+        assert_eq!(pool.available(), 0);
+        drop(shared);
+        assert_eq!(pool.available(), 1);
+
+        // The allocation is reused, emptied
+        let reused = pool.take(8);
+        assert_eq!(reused.as_ptr(), ptr);
+        assert!(reused.is_empty());
+        drop(reused);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_pool_keeps_at_most_capacity() {
+        let pool = BufferPool::new(1);
+        let first = pool.copy_from(&[1, 2, 3]);
+        let second = pool.copy_from(&[4, 5, 6]);
+        drop((first, second));
+        assert_eq!(pool.available(), 1);
+
+        // Buffers outliving their pool are freed normally
+        let orphan = pool.copy_from(&[1]);
+        drop(pool);
+        assert_eq!(&orphan[..], [1]);
+    }
+
+    #[test]
+    fn test_bytes_share_frame_buffer() {
+        let pool = BufferPool::new(1);
+        let frame = pool.copy_from(&[1, 2, 3, 4]);
+        let bytes = bytes::Bytes::from(frame.clone());
+        assert_eq!(bytes.as_ptr(), frame.as_ptr());
+        assert_eq!(frame.ref_count(), 2);
+
+        drop((frame, bytes));
+        assert_eq!(pool.available(), 1);
+        assert_eq!(FrameBuffer::from(vec![1, 2]), pool.copy_from(&[1, 2]));
+    }
+}
@@ -0,0 +1,500 @@
+//! Multi-monitor compositing
+//!
+//! Stitches per-monitor frames into one canvas covering the selected part
+//! of the [`VirtualDesktop`]. Each monitor lands at its bounds; canvas
+//! areas no selected monitor covers (gaps in staggered or L-shaped layouts)
+//! get a solid fill instead of whatever the bounding box holds.
+//!
+//! X11 root window and GDI grabs return one pixel per desktop unit, already
+//! upright, so by default the canvas keeps that scale and frames are drawn
+//! as they arrive. Frames of any other size are resampled to their monitor's
+//! canvas area; [`CompositorConfig::native_resolution`] and
+//! [`CompositorConfig::rotate`] serve sources that deliver `scale_factor`
+//! pixels per unit or the panel's scan-out orientation.
+
+use super::monitor::{Monitor, MonitorOrientation, VirtualDesktop};
+use super::{BufferPool, CaptureRegion, FrameFormat, ScreenFrame};
+use crate::{RemoteCError, Result};
+use rayon::prelude::*;
+use std::time::Instant;
+
+/// Compositor settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompositorConfig {
+    /// Canvas pixels per desktop unit, 1 by default
+    pub scale: f32,
+    /// BGRA color of canvas areas no monitor covers
+    pub fill: [u8; 4],
+    /// Whether frames arrive in the panel's scan-out orientation and must
+    /// be rotated by the monitor's [`MonitorOrientation`]; off by default
+    pub rotate: bool,
+}
+
+impl Default for CompositorConfig {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            fill: [0, 0, 0, 255],
+            rotate: false,
+        }
+    }
+}
+
+impl CompositorConfig {
+    /// Settings keeping the full detail of the densest composed monitor
+    pub fn native_resolution(desktop: &VirtualDesktop, indices: &[usize]) -> Self {
+        let scale = indices
+            .iter()
+            .filter_map(|&index| desktop.get_monitor(index))
+            .map(|monitor| monitor.scale_factor)
+            .fold(1.0, f32::max);
+        Self { scale, ..Self::default() }
+    }
+}
+
+/// Canvas rectangle in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Where one monitor is drawn
+#[derive(Debug, Clone)]
+struct Placement {
+    index: usize,
+    orientation: MonitorOrientation,
+    dest: Rect,
+}
+
+/// Stitches per-monitor frames into a virtual-desktop canvas
+pub struct Compositor {
+    config: CompositorConfig,
+    /// Drawing order; the primary monitor goes last to win overlaps
+    placements: Vec<Placement>,
+    origin: (i32, i32),
+    width: usize,
+    height: usize,
+    canvas: Vec<u8>,
+    /// Whether the next canvas must be reported as entirely changed
+    full_frame_pending: bool,
+    pool: BufferPool,
+}
+
+impl Compositor {
+    /// Compose the monitors at `indices` of `desktop`
+    pub fn new(desktop: &VirtualDesktop, indices: &[usize], config: CompositorConfig) -> Result<Self> {
+        if !(config.scale.is_finite() && config.scale > 0.0) {
+            return Err(RemoteCError::CaptureError(format!("Invalid canvas scale {}", config.scale)));
+        }
+
+        let mut monitors: Vec<&Monitor> = Vec::new();
+        for &index in indices {
+            let monitor = desktop.get_monitor(index).ok_or_else(|| {
+                RemoteCError::CaptureError(format!("Invalid monitor index: {}", index))
+            })?;
+            if !monitors.iter().any(|m| m.index == index) {
+                monitors.push(monitor);
+            }
+        }
+        monitors.sort_by_key(|monitor| monitor.is_primary);
+
+        let total = monitors
+            .iter()
+            .map(|monitor| monitor.bounds)
+            .reduce(|total, bounds| total.union(&bounds))
+            .ok_or_else(|| RemoteCError::CaptureError("No monitors selected".to_string()))?;
+
+        // Edges are scaled rather than sizes so adjacent monitors share an
+        // edge on the canvas without seams
+        let scale = f64::from(config.scale);
+        let edge = |offset: i64| (offset as f64 * scale).round() as usize;
+        let placements: Vec<Placement> = monitors
+            .iter()
+            .map(|monitor| {
+                let b = monitor.bounds;
+                let (left, top) = (i64::from(b.x - total.x), i64::from(b.y - total.y));
+                let (x, y) = (edge(left), edge(top));
+                Placement {
+                    index: monitor.index,
+                    orientation: monitor.orientation,
+                    dest: Rect {
+                        x,
+                        y,
+                        width: edge(left + i64::from(b.width)) - x,
+                        height: edge(top + i64::from(b.height)) - y,
+                    },
+                }
+            })
+            .collect();
+
+        let (width, height) = (edge(i64::from(total.width)), edge(i64::from(total.height)));
+        if width == 0 || height == 0 {
+            return Err(RemoteCError::CaptureError("Composed area is empty".to_string()));
+        }
+
+        let mut canvas = vec![0u8; width * height * 4];
+        for px in canvas.chunks_exact_mut(4) {
+            px.copy_from_slice(&config.fill);
+        }
+
+        Ok(Self {
+            config,
+            placements,
+            origin: (total.x, total.y),
+            width,
+            height,
+            canvas,
+            full_frame_pending: true,
+            pool: BufferPool::default(),
+        })
+    }
+
+    /// Canvas size in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+
+    /// Desktop coordinates of the canvas's top-left corner
+    pub fn origin(&self) -> (i32, i32) {
+        self.origin
+    }
+
+    /// Canvas area showing monitor `index`
+    pub fn monitor_region(&self, index: usize) -> Option<CaptureRegion> {
+        self.placements.iter().find(|p| p.index == index).map(|p| CaptureRegion {
+            x: p.dest.x as i32,
+            y: p.dest.y as i32,
+            width: p.dest.width as u32,
+            height: p.dest.height as u32,
+        })
+    }
+
+    /// Draw new monitor frames and return the updated canvas
+    ///
+    /// `frames` pairs monitor indices with their latest frame; monitors
+    /// without a new frame keep their previous content. Dirty rectangles
+    /// of the result cover what the given frames changed.
+    pub fn compose(&mut self, frames: &[(usize, &ScreenFrame)]) -> Result<ScreenFrame> {
+        if let Some(&(index, _)) = frames.iter().find(|(index, _)| self.monitor_region(*index).is_none()) {
+            return Err(RemoteCError::CaptureError(format!("Monitor {} is not composed", index)));
+        }
+
+        let mut dirty = Vec::new();
+        let mut timestamp = None;
+
+        // Draw in placement order so overlaps resolve the same every time
+        for placement in &self.placements {
+            let Some(&(_, frame)) = frames.iter().find(|(index, _)| *index == placement.index) else {
+                continue;
+            };
+            if frame.width == 0 || frame.height == 0 || frame.data.len() != frame.width as usize * frame.height as usize * 4 {
+                return Err(RemoteCError::CaptureError(format!(
+                    "Frame for monitor {} has invalid size {}x{}",
+                    placement.index, frame.width, frame.height
+                )));
+            }
+
+            let source = Source::new(frame, self.orientation(placement));
+            draw(&mut self.canvas, self.width, &placement.dest, &source);
+            dirty.extend(source.dirty_regions(frame, &placement.dest));
+            timestamp = timestamp.max(Some(frame.timestamp));
+        }
+
+        if std::mem::take(&mut self.full_frame_pending) {
+            dirty = vec![CaptureRegion { x: 0, y: 0, width: self.width as u32, height: self.height as u32 }];
+        }
+
+        Ok(ScreenFrame {
+            width: self.width as u32,
+            height: self.height as u32,
+            data: self.pool.copy_from(&self.canvas),
+            timestamp: timestamp.unwrap_or_else(Instant::now),
+            dirty_rects: Some(dirty),
+            keyframe: frames.iter().any(|(_, frame)| frame.keyframe),
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
+        })
+    }
+
+    fn orientation(&self, placement: &Placement) -> MonitorOrientation {
+        if self.config.rotate {
+            placement.orientation
+        } else {
+            MonitorOrientation::Landscape
+        }
+    }
+}
+
+/// A monitor frame viewed upright
+///
+/// Follows RandR and `wl_output` transforms: a `Portrait` panel shows the
+/// desktop rotated 90° counter-clockwise, so its frames are turned 90°
+/// clockwise to stand upright.
+struct Source<'a> {
+    data: &'a [u8],
+    /// Frame size as stored
+    native: (usize, usize),
+    /// Frame size once upright
+    upright: (usize, usize),
+    orientation: MonitorOrientation,
+}
+
+impl<'a> Source<'a> {
+    fn new(frame: &'a ScreenFrame, orientation: MonitorOrientation) -> Self {
+        let native = (frame.width as usize, frame.height as usize);
+        let upright = match orientation {
+            MonitorOrientation::Portrait | MonitorOrientation::PortraitFlipped => (native.1, native.0),
+            MonitorOrientation::Landscape | MonitorOrientation::LandscapeFlipped => native,
+        };
+        Self { data: &frame.data, native, upright, orientation }
+    }
+
+    /// Offset of upright pixel `(x, y)` in the frame data
+    fn offset(&self, x: usize, y: usize) -> usize {
+        let (width, height) = self.native;
+        let (nx, ny) = match self.orientation {
+            MonitorOrientation::Landscape => (x, y),
+            MonitorOrientation::Portrait => (y, height - 1 - x),
+            MonitorOrientation::LandscapeFlipped => (width - 1 - x, height - 1 - y),
+            MonitorOrientation::PortraitFlipped => (width - 1 - y, x),
+        };
+        (ny * width + nx) * 4
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let offset = self.offset(x, y);
+        &self.data[offset..offset + 4]
+    }
+
+    /// Bilinear sample at upright position `(u, v)`, in pixel units
+    fn sample(&self, u: f32, v: f32, out: &mut [u8]) {
+        let (max_x, max_y) = (self.upright.0 - 1, self.upright.1 - 1);
+        let (u, v) = (u.clamp(0.0, max_x as f32), v.clamp(0.0, max_y as f32));
+        let (x0, y0) = (u as usize, v as usize);
+        let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+        let (fx, fy) = (u - x0 as f32, v - y0 as f32);
+
+        let (a, b) = (self.pixel(x0, y0), self.pixel(x1, y0));
+        let (c, d) = (self.pixel(x0, y1), self.pixel(x1, y1));
+        for channel in 0..4 {
+            let top = f32::from(a[channel]) * (1.0 - fx) + f32::from(b[channel]) * fx;
+            let bottom = f32::from(c[channel]) * (1.0 - fx) + f32::from(d[channel]) * fx;
+            out[channel] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+    }
+
+    /// Map the frame's dirty rectangles onto the canvas at `dest`
+    fn dirty_regions(&self, frame: &ScreenFrame, dest: &Rect) -> Vec<CaptureRegion> {
+        let whole = CaptureRegion { x: 0, y: 0, width: frame.width, height: frame.height };
+        let rects = frame.dirty_rects.as_deref().unwrap_or(std::slice::from_ref(&whole));
+        let (width, height) = (self.native.0 as i64, self.native.1 as i64);
+        let resampled = self.upright != (dest.width, dest.height);
+
+        rects
+            .iter()
+            .filter_map(|rect| {
+                let (x, y, w, h) = (i64::from(rect.x), i64::from(rect.y), i64::from(rect.width), i64::from(rect.height));
+                let (ux, uy, uw, uh) = match self.orientation {
+                    MonitorOrientation::Landscape => (x, y, w, h),
+                    MonitorOrientation::Portrait => (height - y - h, x, h, w),
+                    MonitorOrientation::LandscapeFlipped => (width - x - w, height - y - h, w, h),
+                    MonitorOrientation::PortraitFlipped => (y, width - x - w, h, w),
+                };
+
+                // Round outwards, plus one pixel of filter reach when resampled
+                let margin = i64::from(resampled);
+                let span = |start: i64, len: i64, from: usize, to: usize| {
+                    let (from, to) = (from as i64, to as i64);
+                    let lo = (start * to).div_euclid(from) - margin;
+                    let hi = ((start + len) * to + from - 1).div_euclid(from) + margin;
+                    (lo.max(0), hi.min(to))
+                };
+                let (x0, x1) = span(ux, uw, self.upright.0, dest.width);
+                let (y0, y1) = span(uy, uh, self.upright.1, dest.height);
+                (x1 > x0 && y1 > y0).then(|| CaptureRegion {
+                    x: (dest.x as i64 + x0) as i32,
+                    y: (dest.y as i64 + y0) as i32,
+                    width: (x1 - x0) as u32,
+                    height: (y1 - y0) as u32,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Draw `source` stretched over `dest` on the canvas
+fn draw(canvas: &mut [u8], canvas_width: usize, dest: &Rect, source: &Source) {
+    let stride = canvas_width * 4;
+    let rows = &mut canvas[dest.y * stride..(dest.y + dest.height) * stride];
+    let same_size = source.upright == (dest.width, dest.height);
+    let (sx, sy) = (
+        source.upright.0 as f32 / dest.width as f32,
+        source.upright.1 as f32 / dest.height as f32,
+    );
+
+    rows.par_chunks_mut(stride).enumerate().for_each(|(y, row)| {
+        let row = &mut row[dest.x * 4..(dest.x + dest.width) * 4];
+        if same_size && source.orientation == MonitorOrientation::Landscape {
+            let start = source.offset(0, y);
+            row.copy_from_slice(&source.data[start..start + dest.width * 4]);
+            return;
+        }
+
+        let v = (y as f32 + 0.5) * sy - 0.5;
+        for (x, px) in row.chunks_exact_mut(4).enumerate() {
+            if same_size {
+                px.copy_from_slice(source.pixel(x, y));
+            } else {
+                source.sample((x as f32 + 0.5) * sx - 0.5, v, px);
+            }
+        }
+    });
+}
+
+impl std::fmt::Debug for Compositor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Compositor")
+            .field("config", &self.config)
+            .field("placements", &self.placements)
+            .field("origin", &self.origin)
+            .field("size", &(self.width, self.height))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::monitor::MonitorBounds;
+
+    /// Frame whose pixels hold `value(x, y)` in every color channel
+    fn frame(width: u32, height: u32, value: impl Fn(u32, u32) -> u8) -> ScreenFrame {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let v = value(x, y);
+                data.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        ScreenFrame {
+            width,
+            height,
+            data: data.into(),
+            timestamp: Instant::now(),
+            dirty_rects: None,
+            keyframe: false,
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
+        }
+    }
+
+    fn pixel(frame: &ScreenFrame, x: u32, y: u32) -> u8 {
+        frame.data[((y * frame.width + x) * 4) as usize]
+    }
+
+    #[test]
+    fn test_compose_fills_layout_gaps() {
+        // A 4x2 monitor with a 2x2 monitor to its right, one unit lower
+        let desktop = VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 4, 2)),
+            Monitor::test(1, MonitorBounds::new(4, 1, 2, 2)),
+        ])
+        .unwrap();
+        let config = CompositorConfig { fill: [9, 9, 9, 255], ..Default::default() };
+        let mut compositor = Compositor::new(&desktop, &[0, 1], config).unwrap();
+        assert_eq!(compositor.size(), (6, 3));
+
+        let (left, right) = (frame(4, 2, |_, _| 100), frame(2, 2, |x, _| 200 + x as u8));
+        let canvas = compositor.compose(&[(0, &left), (1, &right)]).unwrap();
+        assert_eq!(pixel(&canvas, 3, 1), 100);
+        assert_eq!((pixel(&canvas, 4, 1), pixel(&canvas, 5, 2)), (200, 201));
+        // Gaps above the right monitor and below the left one
+        assert_eq!((pixel(&canvas, 5, 0), pixel(&canvas, 0, 2)), (9, 9));
+        assert_eq!(canvas.dirty_rects.as_ref().unwrap().len(), 1);
+
+        // Later frames only report what changed
+        let mut update = frame(2, 2, |_, _| 50);
+        update.dirty_rects = Some(vec![CaptureRegion { x: 1, y: 0, width: 1, height: 1 }]);
+        let canvas = compositor.compose(&[(1, &update)]).unwrap();
+        assert_eq!(pixel(&canvas, 3, 1), 100);
+        assert_eq!(pixel(&canvas, 4, 1), 50);
+        let dirty = canvas.dirty_rects.unwrap();
+        assert_eq!((dirty[0].x, dirty[0].y, dirty[0].width, dirty[0].height), (5, 1, 1, 1));
+    }
+
+    #[test]
+    fn test_compose_normalizes_mixed_scale() {
+        // The right monitor has twice the pixel density
+        let desktop = VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 2, 2)),
+            Monitor { scale_factor: 2.0, ..Monitor::test(1, MonitorBounds::new(2, 0, 2, 2)) },
+        ])
+        .unwrap();
+        let low = frame(2, 2, |_, _| 10);
+        let high = frame(4, 4, |x, _| if x % 2 == 0 { 100 } else { 200 });
+
+        let mut compositor = Compositor::new(&desktop, &[0, 1], CompositorConfig::default()).unwrap();
+        let canvas = compositor.compose(&[(0, &low), (1, &high)]).unwrap();
+        assert_eq!(canvas.width, 4);
+        // Each canvas pixel averages two HiDPI columns
+        assert_eq!((pixel(&canvas, 1, 0), pixel(&canvas, 2, 0), pixel(&canvas, 3, 1)), (10, 150, 150));
+
+        let config = CompositorConfig::native_resolution(&desktop, &[0, 1]);
+        assert_eq!(config.scale, 2.0);
+        let mut compositor = Compositor::new(&desktop, &[0, 1], config).unwrap();
+        let canvas = compositor.compose(&[(0, &low), (1, &high)]).unwrap();
+        assert_eq!((canvas.width, canvas.height), (8, 4));
+        assert_eq!((pixel(&canvas, 3, 3), pixel(&canvas, 4, 0), pixel(&canvas, 5, 0)), (10, 100, 200));
+        let region = compositor.monitor_region(1).unwrap();
+        assert_eq!((region.x, region.width), (4, 4));
+    }
+
+    #[test]
+    fn test_compose_rotates_portrait_monitor() {
+        // Desktop-oriented bounds are 2 wide and 3 tall; the panel scans out 3x2
+        let desktop = VirtualDesktop::new(vec![
+            Monitor { orientation: MonitorOrientation::Portrait, ..Monitor::test(0, MonitorBounds::new(0, 0, 2, 3)) },
+        ])
+        .unwrap();
+        let mut native = frame(3, 2, |x, y| (y * 3 + x) as u8);
+        native.dirty_rects = Some(vec![CaptureRegion { x: 0, y: 0, width: 1, height: 1 }]);
+
+        let config = CompositorConfig { rotate: true, ..Default::default() };
+        let mut compositor = Compositor::new(&desktop, &[0], config).unwrap();
+        compositor.compose(&[(0, &native)]).unwrap();
+        let canvas = compositor.compose(&[(0, &native)]).unwrap();
+        assert_eq!((canvas.width, canvas.height), (2, 3));
+        // Turned 90° clockwise: the native bottom-left pixel ends up top-left
+        let rows: Vec<Vec<u8>> = (0..3).map(|y| (0..2).map(|x| pixel(&canvas, x, y)).collect()).collect();
+        assert_eq!(rows, vec![vec![3, 0], vec![4, 1], vec![5, 2]]);
+        let dirty = canvas.dirty_rects.unwrap();
+        assert_eq!((dirty[0].x, dirty[0].y), (1, 0));
+
+        // Upright sources are drawn as-is
+        let upright = frame(2, 3, |x, y| (y * 2 + x) as u8);
+        let mut compositor = Compositor::new(&desktop, &[0], CompositorConfig::default()).unwrap();
+        let canvas = compositor.compose(&[(0, &upright)]).unwrap();
+        assert_eq!(pixel(&canvas, 1, 2), 5);
+    }
+
+    #[test]
+    fn test_compose_rejects_bad_input() {
+        let desktop = VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 2, 2)),
+            Monitor::test(1, MonitorBounds::new(2, 0, 2, 2)),
+        ])
+        .unwrap();
+        assert!(Compositor::new(&desktop, &[], CompositorConfig::default()).is_err());
+        assert!(Compositor::new(&desktop, &[5], CompositorConfig::default()).is_err());
+        assert!(Compositor::new(&desktop, &[0], CompositorConfig { scale: 0.0, ..Default::default() }).is_err());
+
+        let mut compositor = Compositor::new(&desktop, &[0], CompositorConfig::default()).unwrap();
+        assert!(compositor.compose(&[(1, &frame(2, 2, |_, _| 0))]).is_err());
+        let mut truncated = frame(2, 2, |_, _| 0);
+        truncated.height = 3;
+        assert!(compositor.compose(&[(0, &truncated)]).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Coordinate mapping between viewer, stream and desktop
+//!
+//! Input arrives in viewer coordinates: pixels of the remote window showing
+//! the stream, whatever its size. Frames are in stream coordinates, which
+//! may be scaled from the captured area (e.g. by the compositor or DPI
+//! scaling), and that area may be one monitor, a window, or a region
+//! cropped from them. Input injection needs absolute desktop coordinates.
+//! [`CoordinateTransform`] converts between the three spaces.
+//!
+//! Desktop coordinates are in the units of [`Monitor`](super::Monitor)
+//! bounds. Like the [`Compositor`](super::Compositor) canvas, streams
+//! default to one pixel per unit, which is what X11 and GDI grabs return.
+
+use super::monitor::{MonitorBounds, VirtualDesktop};
+use super::window::{enumerate_windows, find_window};
+use super::{CaptureConfig, CaptureMode};
+use crate::{RemoteCError, Result};
+
+/// Maps points between viewer, stream and desktop coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordinateTransform {
+    /// Desktop rectangle shown by the stream
+    area: MonitorBounds,
+    /// Stream size in pixels
+    stream: (u32, u32),
+    /// Viewer surface size in pixels
+    viewer: (u32, u32),
+    /// Monitors desktop points are kept on
+    monitors: Vec<MonitorBounds>,
+}
+
+impl CoordinateTransform {
+    /// Transform for frames captured with `config` on `desktop`
+    ///
+    /// The stream and viewer sizes start at the captured area's size;
+    /// update them with [`set_stream_size`](Self::set_stream_size) and
+    /// [`set_viewer_size`](Self::set_viewer_size).
+    pub fn new(config: &CaptureConfig, desktop: &VirtualDesktop) -> Result<Self> {
+        let area = match &config.mode {
+            CaptureMode::Window(query) => {
+                let windows = enumerate_windows()?;
+                find_window(&windows, query)
+                    .map(|window| window.bounds)
+                    .ok_or_else(|| RemoteCError::CaptureError(format!("Window not found: {}", query)))?
+            }
+            _ => config.capture_area(desktop)?,
+        };
+        Ok(Self::from_area(area, desktop))
+    }
+
+    /// Transform for a stream showing the desktop rectangle `area`
+    pub fn from_area(area: MonitorBounds, desktop: &VirtualDesktop) -> Self {
+        Self {
+            area,
+            stream: (area.width, area.height),
+            viewer: (area.width, area.height),
+            monitors: desktop.monitors.iter().map(|monitor| monitor.bounds).collect(),
+        }
+    }
+
+    /// Desktop rectangle shown by the stream
+    pub fn area(&self) -> MonitorBounds {
+        self.area
+    }
+
+    /// Set the size of the frames, e.g. from the latest
+    /// [`ScreenFrame`](super::ScreenFrame)
+    pub fn set_stream_size(&mut self, width: u32, height: u32) {
+        self.stream = (width, height);
+    }
+
+    /// Set the size of the surface the viewer shows the stream on
+    pub fn set_viewer_size(&mut self, width: u32, height: u32) {
+        self.viewer = (width, height);
+    }
+
+    /// Desktop point shown at viewer point `(x, y)`
+    ///
+    /// Points outside the viewer are clamped to its edges, and points
+    /// falling between monitors move to the nearest monitor.
+    pub fn viewer_to_desktop(&self, x: i32, y: i32) -> (i32, i32) {
+        self.to_desktop(x, y, self.viewer)
+    }
+
+    /// Viewer point showing desktop point `(x, y)`, clamped to the viewer
+    pub fn desktop_to_viewer(&self, x: i32, y: i32) -> (i32, i32) {
+        self.desktop_to(x, y, self.viewer)
+    }
+
+    /// Desktop point shown at stream pixel `(x, y)`, clamped like
+    /// [`viewer_to_desktop`](Self::viewer_to_desktop)
+    pub fn stream_to_desktop(&self, x: i32, y: i32) -> (i32, i32) {
+        self.to_desktop(x, y, self.stream)
+    }
+
+    /// Stream pixel showing desktop point `(x, y)`, clamped to the stream
+    pub fn desktop_to_stream(&self, x: i32, y: i32) -> (i32, i32) {
+        self.desktop_to(x, y, self.stream)
+    }
+
+    /// Desktop distance covered by a viewer-space movement of `(dx, dy)`
+    pub fn viewer_delta_to_desktop(&self, dx: i32, dy: i32) -> (i32, i32) {
+        let scale = |delta: i32, to: u32, from: u32| {
+            (f64::from(delta) * f64::from(to) / f64::from(from.max(1))).round() as i32
+        };
+        (scale(dx, self.area.width, self.viewer.0), scale(dy, self.area.height, self.viewer.1))
+    }
+
+    /// Whether desktop point `(x, y)` is inside the captured area
+    pub fn contains_desktop_point(&self, x: i32, y: i32) -> bool {
+        contains(&self.area, x, y)
+    }
+
+    /// Nearest point to `(x, y)` on a monitor
+    pub fn clamp_to_monitors(&self, x: i32, y: i32) -> (i32, i32) {
+        self.monitors
+            .iter()
+            .map(|bounds| clamp(bounds, x, y))
+            .min_by_key(|&(cx, cy)| {
+                let (dx, dy) = (i64::from(cx - x), i64::from(cy - y));
+                dx * dx + dy * dy
+            })
+            .unwrap_or((x, y))
+    }
+
+    fn to_desktop(&self, x: i32, y: i32, (width, height): (u32, u32)) -> (i32, i32) {
+        let desktop_x = self.area.x + map_axis(x, width, self.area.width);
+        let desktop_y = self.area.y + map_axis(y, height, self.area.height);
+        let (x, y) = clamp(&self.area, desktop_x, desktop_y);
+        self.clamp_to_monitors(x, y)
+    }
+
+    fn desktop_to(&self, x: i32, y: i32, (width, height): (u32, u32)) -> (i32, i32) {
+        let (x, y) = clamp(&self.area, x, y);
+        (
+            map_axis(x - self.area.x, self.area.width, width),
+            map_axis(y - self.area.y, self.area.height, height),
+        )
+    }
+}
+
+/// Map pixel `value` of an axis `from` pixels long onto one `to` pixels
+/// long, by pixel centers and clamped to the target axis
+fn map_axis(value: i32, from: u32, to: u32) -> i32 {
+    if from == 0 || to == 0 {
+        return 0;
+    }
+    let mapped = ((f64::from(value) + 0.5) * f64::from(to) / f64::from(from)).floor() as i64;
+    mapped.clamp(0, i64::from(to) - 1) as i32
+}
+
+fn contains(bounds: &MonitorBounds, x: i32, y: i32) -> bool {
+    x >= bounds.x && y >= bounds.y && x < bounds.x + bounds.width as i32 && y < bounds.y + bounds.height as i32
+}
+
+fn clamp(bounds: &MonitorBounds, x: i32, y: i32) -> (i32, i32) {
+    let right = bounds.x + bounds.width.max(1) as i32 - 1;
+    let bottom = bounds.y + bounds.height.max(1) as i32 - 1;
+    (x.clamp(bounds.x, right), y.clamp(bounds.y, bottom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::monitor::Monitor;
+    use crate::capture::CaptureRegion;
+
+    /// 1920x1080 primary with a 1280x1024 monitor to its right, top-aligned
+    fn desktop() -> VirtualDesktop {
+        VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)),
+            Monitor::test(1, MonitorBounds::new(1920, 0, 1280, 1024)),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_single_monitor_scaled_viewer() {
+        let config = CaptureConfig { mode: CaptureMode::SingleMonitor(1), ..Default::default() };
+        let mut transform = CoordinateTransform::new(&config, &desktop()).unwrap();
+        assert_eq!(transform.area(), MonitorBounds::new(1920, 0, 1280, 1024));
+
+        transform.set_viewer_size(640, 512);
+        // Viewer pixels map to the center of the desktop pixels they cover
+        assert_eq!(transform.viewer_to_desktop(0, 0), (1921, 1));
+        assert_eq!(transform.viewer_to_desktop(320, 256), (2561, 513));
+        assert_eq!(transform.desktop_to_viewer(2561, 513), (320, 256));
+        assert_eq!(transform.viewer_delta_to_desktop(10, -5), (20, -10));
+
+        // Outside the viewer clamps to the monitor edge
+        assert_eq!(transform.viewer_to_desktop(-50, 9000), (1920, 1023));
+        assert_eq!(transform.desktop_to_viewer(0, 0), (0, 0));
+        assert!(!transform.contains_desktop_point(1919, 0));
+    }
+
+    #[test]
+    fn test_region_and_stream_scaling() {
+        let config = CaptureConfig {
+            mode: CaptureMode::PrimaryMonitor,
+            region: Some(CaptureRegion { x: 100, y: 200, width: 800, height: 600 }),
+            ..Default::default()
+        };
+        let mut transform = CoordinateTransform::new(&config, &desktop()).unwrap();
+        transform.set_stream_size(1600, 1200);
+
+        assert_eq!(transform.stream_to_desktop(0, 0), (100, 200));
+        assert_eq!(transform.stream_to_desktop(1599, 1199), (899, 799));
+        assert_eq!(transform.desktop_to_stream(500, 500), (801, 601));
+        // The viewer defaults to the area size, independent of the stream
+        assert_eq!(transform.viewer_to_desktop(400, 300), (500, 500));
+    }
+
+    #[test]
+    fn test_points_between_monitors_move_to_nearest() {
+        let config = CaptureConfig { mode: CaptureMode::AllMonitors, ..Default::default() };
+        let transform = CoordinateTransform::new(&config, &desktop()).unwrap();
+        assert_eq!(transform.area(), MonitorBounds::new(0, 0, 3200, 1080));
+
+        // Below the shorter right monitor
+        assert_eq!(transform.viewer_to_desktop(2500, 1050), (2500, 1023));
+        assert_eq!(transform.viewer_to_desktop(1000, 1050), (1000, 1050));
+        assert_eq!(transform.clamp_to_monitors(5000, -10), (3199, 0));
+    }
+
+    #[test]
+    fn test_hidpi_stream_size() {
+        let mut hidpi = Monitor::test(1, MonitorBounds::new(1920, 0, 1280, 720));
+        hidpi.scale_factor = 2.0;
+        let desktop = VirtualDesktop::new(vec![Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)), hidpi]).unwrap();
+
+        // One pixel per desktop unit by default, like the compositor canvas
+        let config = CaptureConfig { mode: CaptureMode::SingleMonitor(1), ..Default::default() };
+        let mut transform = CoordinateTransform::new(&config, &desktop).unwrap();
+        assert_eq!(transform.stream_to_desktop(640, 360), (2560, 360));
+
+        // A source delivering 2560x1440 frames shown 1:1 by the viewer
+        transform.set_stream_size(2560, 1440);
+        transform.set_viewer_size(2560, 1440);
+        assert_eq!(transform.viewer_to_desktop(2559, 1439), (3199, 719));
+        assert_eq!(transform.stream_to_desktop(1280, 720), (2560, 360));
+        assert_eq!(transform.desktop_to_viewer(2560, 360), (1281, 721));
+        assert_eq!(transform.viewer_delta_to_desktop(10, -4), (5, -2));
+    }
+}
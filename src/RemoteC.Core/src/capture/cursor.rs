@@ -0,0 +1,401 @@
+//! Cursor shape and position channel
+//!
+//! Instead of drawing the cursor into every frame, the capture side reports
+//! the cursor shape and position as [`CursorEvent`]s so viewers can render
+//! it locally. Shapes are identified by a content hash and each one is sent
+//! in full only once; afterwards a [`CursorEvent::CachedShape`] selects it
+//! from the viewer's [`CursorShapeCache`].
+
+use crate::{RemoteCError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Number of shapes kept by default on both sides of the channel
+pub const DEFAULT_SHAPE_CACHE_CAPACITY: usize = 32;
+
+/// Cursor image
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorShape {
+    /// Content hash used as cache key
+    pub id: u64,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Hotspot X offset from the left edge
+    pub hotspot_x: u32,
+    /// Hotspot Y offset from the top edge
+    pub hotspot_y: u32,
+    /// Pixel data in BGRA format, straight (non-premultiplied) alpha
+    pub data: Vec<u8>,
+}
+
+impl CursorShape {
+    /// Create a shape, validating the data size and computing its id
+    pub fn new(width: u32, height: u32, hotspot_x: u32, hotspot_y: u32, data: Vec<u8>) -> Result<Self> {
+        let expected = width as usize * height as usize * 4;
+        if data.len() != expected {
+            return Err(RemoteCError::CaptureError(format!(
+                "Invalid cursor size: expected {}, got {}",
+                expected,
+                data.len()
+            )));
+        }
+
+        let mut hash = Fnv1a::new();
+        for value in [width, height, hotspot_x, hotspot_y] {
+            hash.write(&value.to_le_bytes());
+        }
+        hash.write(&data);
+
+        Ok(Self {
+            id: hash.finish(),
+            width,
+            height,
+            hotspot_x: hotspot_x.min(width.saturating_sub(1)),
+            hotspot_y: hotspot_y.min(height.saturating_sub(1)),
+            data,
+        })
+    }
+}
+
+/// 64-bit FNV-1a, stable across processes and platforms unlike `DefaultHasher`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Cursor hotspot position relative to the captured area
+///
+/// The position may lie outside the frame when the cursor is on another
+/// monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorPosition {
+    /// X coordinate in frame pixels
+    pub x: i32,
+    /// Y coordinate in frame pixels
+    pub y: i32,
+    /// Whether the cursor is shown
+    pub visible: bool,
+}
+
+/// Cursor update delivered alongside frames
+#[derive(Debug, Clone)]
+pub enum CursorEvent {
+    /// New shape, to be cached and used as the current shape
+    Shape(Arc<CursorShape>),
+    /// Switch to a shape sent earlier
+    CachedShape(u64),
+    /// Cursor moved or changed visibility
+    Position(CursorPosition),
+}
+
+/// Raw cursor state read from the platform
+#[derive(Debug, Clone)]
+pub struct CursorSample {
+    /// Hotspot X in desktop coordinates
+    pub x: i32,
+    /// Hotspot Y in desktop coordinates
+    pub y: i32,
+    /// Whether the cursor is shown
+    pub visible: bool,
+    /// Current shape, only present when it changed since the previous sample
+    pub shape: Option<CursorShape>,
+}
+
+/// Platform cursor reader
+pub trait CursorSource: Send + Sync {
+    /// Read the current cursor state
+    fn sample(&mut self) -> Result<CursorSample>;
+}
+
+/// Bounded least-recently-used cache of cursor shapes
+///
+/// The sender and the receiver must use the same capacity so that both
+/// evict the same shapes.
+#[derive(Debug)]
+pub struct CursorShapeCache {
+    capacity: usize,
+    shapes: HashMap<u64, Arc<CursorShape>>,
+    order: VecDeque<u64>,
+}
+
+impl Default for CursorShapeCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHAPE_CACHE_CAPACITY)
+    }
+}
+
+impl CursorShapeCache {
+    /// Create a cache holding up to `capacity` shapes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            shapes: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Insert a shape, evicting the least recently used one if full
+    pub fn insert(&mut self, shape: Arc<CursorShape>) {
+        let id = shape.id;
+        if self.shapes.insert(id, shape).is_some() {
+            self.touch(id);
+            return;
+        }
+
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.shapes.remove(&evicted);
+            }
+        }
+    }
+
+    /// Look up a shape and mark it as recently used
+    pub fn get(&mut self, id: u64) -> Option<Arc<CursorShape>> {
+        let shape = self.shapes.get(&id).cloned()?;
+        self.touch(id);
+        Some(shape)
+    }
+
+    /// Whether the shape is cached
+    pub fn contains(&self, id: u64) -> bool {
+        self.shapes.contains_key(&id)
+    }
+
+    /// Number of cached shapes
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// Remove all shapes
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+        self.order.clear();
+    }
+
+    fn touch(&mut self, id: u64) {
+        if let Some(pos) = self.order.iter().position(|&cached| cached == id) {
+            self.order.remove(pos);
+            self.order.push_back(id);
+        }
+    }
+}
+
+/// Sender side: turns cursor samples into events
+#[derive(Debug)]
+pub struct CursorTracker {
+    origin: (i32, i32),
+    position: Option<CursorPosition>,
+    shape: Option<u64>,
+    sent: CursorShapeCache,
+}
+
+impl CursorTracker {
+    /// Track the cursor relative to a captured area starting at `origin`
+    pub fn new(origin: (i32, i32), cache_capacity: usize) -> Self {
+        Self {
+            origin,
+            position: None,
+            shape: None,
+            sent: CursorShapeCache::new(cache_capacity),
+        }
+    }
+
+    /// Events describing what changed since the previous sample
+    pub fn update(&mut self, sample: CursorSample) -> Vec<CursorEvent> {
+        let mut events = Vec::new();
+
+        if let Some(shape) = sample.shape {
+            if self.shape != Some(shape.id) {
+                self.shape = Some(shape.id);
+                if self.sent.get(shape.id).is_some() {
+                    events.push(CursorEvent::CachedShape(shape.id));
+                } else {
+                    let shape = Arc::new(shape);
+                    self.sent.insert(shape.clone());
+                    events.push(CursorEvent::Shape(shape));
+                }
+            }
+        }
+
+        let position = CursorPosition {
+            x: sample.x - self.origin.0,
+            y: sample.y - self.origin.1,
+            visible: sample.visible,
+        };
+        if self.position != Some(position) {
+            self.position = Some(position);
+            events.push(CursorEvent::Position(position));
+        }
+
+        events
+    }
+
+    /// Move the captured area, e.g. when following a window
+    pub fn set_origin(&mut self, origin: (i32, i32)) {
+        self.origin = origin;
+    }
+
+    /// Forget what was sent, e.g. when a new viewer joins
+    ///
+    /// The source must report the current shape again for it to be resent.
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.shape = None;
+        self.sent.clear();
+    }
+}
+
+/// Cursor source combined with a tracker
+pub struct CursorChannel {
+    source: Box<dyn CursorSource>,
+    tracker: CursorTracker,
+}
+
+impl CursorChannel {
+    /// Report the cursor of `source` relative to `origin`
+    pub fn new(source: Box<dyn CursorSource>, origin: (i32, i32)) -> Self {
+        Self {
+            source,
+            tracker: CursorTracker::new(origin, DEFAULT_SHAPE_CACHE_CAPACITY),
+        }
+    }
+
+    /// Move the captured area, e.g. when following a window
+    pub fn set_origin(&mut self, origin: (i32, i32)) {
+        self.tracker.set_origin(origin);
+    }
+
+    /// Sample the cursor and return the resulting events
+    pub fn poll(&mut self) -> Result<Vec<CursorEvent>> {
+        let sample = self.source.sample()?;
+        Ok(self.tracker.update(sample))
+    }
+}
+
+/// Receiver side: current cursor as seen by a viewer
+#[derive(Debug, Default)]
+pub struct CursorState {
+    /// Shape to draw, if any has been received
+    pub shape: Option<Arc<CursorShape>>,
+    /// Last known position
+    pub position: Option<CursorPosition>,
+    cache: CursorShapeCache,
+}
+
+impl CursorState {
+    /// Create a viewer state whose cache matches a sender using `cache_capacity`
+    pub fn new(cache_capacity: usize) -> Self {
+        Self {
+            shape: None,
+            position: None,
+            cache: CursorShapeCache::new(cache_capacity),
+        }
+    }
+
+    /// Apply an event received from the capture side
+    pub fn apply(&mut self, event: CursorEvent) -> Result<()> {
+        match event {
+            CursorEvent::Shape(shape) => {
+                self.cache.insert(shape.clone());
+                self.shape = Some(shape);
+            }
+            CursorEvent::CachedShape(id) => {
+                let shape = self.cache.get(id).ok_or_else(|| {
+                    RemoteCError::Other(format!("Unknown cursor shape {:016x}", id))
+                })?;
+                self.shape = Some(shape);
+            }
+            CursorEvent::Position(position) => self.position = Some(position),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(color: u8) -> CursorShape {
+        CursorShape::new(2, 2, 0, 0, vec![color; 16]).unwrap()
+    }
+
+    fn sample(x: i32, shape: Option<CursorShape>) -> CursorSample {
+        CursorSample { x, y: 10, visible: true, shape }
+    }
+
+    #[test]
+    fn test_shape_id_is_content_hash() {
+        assert_eq!(shape(1).id, shape(1).id);
+        assert_ne!(shape(1).id, shape(2).id);
+        assert_ne!(CursorShape::new(2, 2, 1, 1, vec![1; 16]).unwrap().id, shape(1).id);
+        assert!(CursorShape::new(2, 2, 0, 0, vec![0; 15]).is_err());
+    }
+
+    #[test]
+    fn test_tracker_sends_each_shape_once() {
+        let mut tracker = CursorTracker::new((100, 0), 4);
+        let mut viewer = CursorState::new(4);
+
+        let events = tracker.update(sample(150, Some(shape(1))));
+        assert!(matches!(events[0], CursorEvent::Shape(_)));
+        assert!(matches!(events[1], CursorEvent::Position(CursorPosition { x: 50, y: 10, visible: true })));
+        for event in events {
+            viewer.apply(event).unwrap();
+        }
+
+        // Unchanged sample produces nothing
+        assert!(tracker.update(sample(150, None)).is_empty());
+
+        let events = [
+            tracker.update(sample(150, Some(shape(2)))),
+            tracker.update(sample(151, Some(shape(1)))),
+        ]
+        .concat();
+        assert!(matches!(events[0], CursorEvent::Shape(_)));
+        assert!(matches!(events[1], CursorEvent::CachedShape(id) if id == shape(1).id));
+        assert!(matches!(events[2], CursorEvent::Position(CursorPosition { x: 51, .. })));
+
+        for event in events {
+            viewer.apply(event).unwrap();
+        }
+        assert_eq!(viewer.shape.as_ref().unwrap().id, shape(1).id);
+        assert_eq!(viewer.position.unwrap().x, 51);
+    }
+
+    #[test]
+    fn test_cache_eviction_stays_in_sync() {
+        let mut tracker = CursorTracker::new((0, 0), 2);
+        let mut viewer = CursorState::new(2);
+
+        for color in [1, 2, 1, 3, 2] {
+            for event in tracker.update(sample(0, Some(shape(color)))) {
+                viewer.apply(event).unwrap();
+            }
+        }
+        // Shape 2 was evicted before being used again, so it was resent
+        assert_eq!(viewer.shape.as_ref().unwrap().id, shape(2).id);
+        assert!(viewer.apply(CursorEvent::CachedShape(0xdead)).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Paced capture on a dedicated thread
+//!
+//! [`CaptureDriver`] polls a [`ScreenCapture`] backend at the configured
+//! `target_fps` and hands frames to the consumer through a small bounded
+//! queue, either as a [`futures::Stream`] or a blocking iterator. When the
+//! backend has nothing new (e.g. an unchanged screen with damage tracking),
+//! polling backs off so an idle desktop costs little CPU. Frames the
+//! consumer is too slow to take are dropped rather than queued.
+
+use super::{ScreenCapture, ScreenFrame};
+use crate::{RemoteCError, Result};
+use futures::channel::mpsc;
+use futures::{SinkExt, Stream};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Frames waiting for the consumer before new ones are dropped
+const QUEUE_DEPTH: usize = 2;
+
+/// Longest wait between polls while the backend has nothing new
+const MAX_IDLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Period over which fps and latency are averaged
+const STATS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Capture driver statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CaptureStats {
+    /// Frames captured since the driver started
+    pub frames_captured: u64,
+    /// Frames per second achieved over the last second
+    pub fps: f64,
+    /// Average time spent in the backend per captured frame over the last second
+    pub capture_latency: Duration,
+    /// Frames lost because capture fell behind `target_fps` or the consumer
+    /// did not take them in time
+    pub skipped_frames: u64,
+}
+
+/// Statistics shared between the capture thread and the driver
+#[derive(Default)]
+struct StatsRecorder {
+    frames_captured: u64,
+    skipped_frames: u64,
+    /// Capture start time and latency of recent frames
+    recent: VecDeque<(Instant, Duration)>,
+}
+
+impl StatsRecorder {
+    fn record_frame(&mut self, started: Instant, latency: Duration) {
+        self.frames_captured += 1;
+        self.recent.push_back((started, latency));
+        while self.recent.front().is_some_and(|&(at, _)| started - at > STATS_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+
+    fn snapshot(&self) -> CaptureStats {
+        let fps = match (self.recent.front(), self.recent.back()) {
+            (Some(&(first, _)), Some(&(last, _))) if last > first => {
+                (self.recent.len() - 1) as f64 / (last - first).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        let capture_latency = if self.recent.is_empty() {
+            Duration::ZERO
+        } else {
+            self.recent.iter().map(|&(_, latency)| latency).sum::<Duration>() / self.recent.len() as u32
+        };
+
+        CaptureStats {
+            frames_captured: self.frames_captured,
+            fps,
+            capture_latency,
+            skipped_frames: self.skipped_frames,
+        }
+    }
+}
+
+/// Runs a capture backend on its own thread at `target_fps`
+pub struct CaptureDriver {
+    frames: Option<mpsc::Receiver<Result<ScreenFrame>>>,
+    stats: Arc<Mutex<StatsRecorder>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Box<dyn ScreenCapture>>>,
+}
+
+impl CaptureDriver {
+    /// Start `capture` if needed and begin polling it on a new thread
+    pub fn start(mut capture: Box<dyn ScreenCapture>) -> Result<Self> {
+        if !capture.is_active() {
+            capture.start()?;
+        }
+
+        let (sender, receiver) = mpsc::channel(QUEUE_DEPTH);
+        let stats = Arc::new(Mutex::new(StatsRecorder::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (stats, stop) = (stats.clone(), stop.clone());
+            thread::Builder::new()
+                .name("remotec-capture".to_string())
+                .spawn(move || run(capture, sender, &stats, &stop))?
+        };
+
+        Ok(Self {
+            frames: Some(receiver),
+            stats,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Current capture statistics
+    pub fn stats(&self) -> CaptureStats {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).snapshot()
+    }
+
+    /// Blocking iterator over captured frames
+    ///
+    /// Ends when the backend fails (after yielding the error) or the
+    /// driver is stopped.
+    pub fn iter(&mut self) -> impl Iterator<Item = Result<ScreenFrame>> + '_ {
+        futures::executor::block_on_stream(self)
+    }
+
+    /// Stop the capture thread and return the stopped backend
+    pub fn stop(mut self) -> Result<Box<dyn ScreenCapture>> {
+        let mut capture = self.shutdown()?;
+        if capture.is_active() {
+            capture.stop()?;
+        }
+        Ok(capture)
+    }
+
+    fn shutdown(&mut self) -> Result<Box<dyn ScreenCapture>> {
+        self.stop.store(true, Ordering::Release);
+        // Unblocks a thread waiting to deliver an error
+        self.frames = None;
+
+        let thread = self
+            .thread
+            .take()
+            .ok_or_else(|| RemoteCError::CaptureError("Capture driver already stopped".to_string()))?;
+        thread.thread().unpark();
+        thread
+            .join()
+            .map_err(|_| RemoteCError::CaptureError("Capture thread panicked".to_string()))
+    }
+}
+
+impl Stream for CaptureDriver {
+    type Item = Result<ScreenFrame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.frames.as_mut() {
+            Some(frames) => Pin::new(frames).poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Drop for CaptureDriver {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            if let Ok(mut capture) = self.shutdown() {
+                let _ = capture.stop();
+            }
+        }
+    }
+}
+
+/// Wait before polling again after `idle_polls` polls without a frame
+///
+/// The first retries come early because backends pacing themselves may
+/// have been polled just before their frame was due.
+fn idle_wait(interval: Duration, idle_polls: u32) -> Duration {
+    let wait = interval / 16 * 2u32.pow(idle_polls.clamp(1, 10) - 1);
+    wait.min(MAX_IDLE_WAIT.max(interval))
+}
+
+/// Capture thread body
+fn run(
+    mut capture: Box<dyn ScreenCapture>,
+    mut sender: mpsc::Sender<Result<ScreenFrame>>,
+    stats: &Mutex<StatsRecorder>,
+    stop: &AtomicBool,
+) -> Box<dyn ScreenCapture> {
+    let interval = Duration::from_secs(1) / capture.config().target_fps.max(1);
+    let mut idle_polls = 0;
+    let mut next_poll = Instant::now();
+
+    while !stop.load(Ordering::Acquire) {
+        let started = Instant::now();
+        match capture.get_frame() {
+            Ok(Some(frame)) => {
+                let latency = started.elapsed();
+                idle_polls = 0;
+
+                // Recorded before delivery so stats cover every frame the
+                // consumer has seen
+                stats.lock().unwrap_or_else(|e| e.into_inner()).record_frame(started, latency);
+                let delivered = match sender.try_send(Ok(frame)) {
+                    Ok(()) => true,
+                    Err(e) if e.is_disconnected() => break,
+                    Err(_) => false,
+                };
+
+                // Schedule from the previous deadline to hold the rate
+                // regardless of how long the capture took
+                next_poll += interval;
+                let now = Instant::now();
+                let missed = if next_poll < now {
+                    let behind = now - next_poll;
+                    next_poll = now;
+                    (behind.as_nanos() / interval.as_nanos().max(1)) as u64
+                } else {
+                    0
+                };
+
+                stats.lock().unwrap_or_else(|e| e.into_inner()).skipped_frames += missed + u64::from(!delivered);
+            }
+            Ok(None) => {
+                idle_polls += 1;
+                next_poll = Instant::now() + idle_wait(interval, idle_polls);
+            }
+            Err(e) => {
+                let _ = futures::executor::block_on(sender.send(Err(e)));
+                break;
+            }
+        }
+
+        // Woken early by `CaptureDriver::shutdown`
+        let now = Instant::now();
+        if next_poll > now {
+            thread::park_timeout(next_poll - now);
+        }
+    }
+
+    capture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CaptureConfig, CaptureSource, SyntheticCapture, SyntheticConfig};
+    use futures::StreamExt;
+
+    fn synthetic(target_fps: u32) -> Box<dyn ScreenCapture> {
+        let config = CaptureConfig {
+            target_fps,
+            source: CaptureSource::Synthetic(SyntheticConfig { width: 16, height: 8, ..Default::default() }),
+            ..Default::default()
+        };
+        Box::new(SyntheticCapture::new(config).unwrap())
+    }
+
+    /// Backend that never has a frame, then fails
+    struct IdleCapture {
+        config: CaptureConfig,
+        polls: u32,
+        fail_after: u32,
+    }
+
+    impl ScreenCapture for IdleCapture {
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+            self.polls += 1;
+            if self.polls > self.fail_after {
+                return Err(RemoteCError::CaptureError("display lost".to_string()));
+            }
+            Ok(None)
+        }
+
+        fn is_active(&self) -> bool {
+            true
+        }
+
+        fn config(&self) -> &CaptureConfig {
+            &self.config
+        }
+    }
+
+    #[test]
+    fn test_idle_wait_backs_off() {
+        let interval = Duration::from_millis(20);
+        assert_eq!(idle_wait(interval, 1), Duration::from_micros(1250));
+        assert_eq!(idle_wait(interval, 2), Duration::from_micros(2500));
+        assert_eq!(idle_wait(interval, 8), MAX_IDLE_WAIT);
+        assert_eq!(idle_wait(Duration::from_millis(500), 12), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_driver_iterates_paced_frames() {
+        let mut driver = CaptureDriver::start(synthetic(100)).unwrap();
+
+        let started = Instant::now();
+        let frames: Vec<ScreenFrame> = driver.iter().take(5).map(|f| f.unwrap()).collect();
+        assert_eq!(frames.len(), 5);
+        assert!(frames.iter().all(|f| (f.width, f.height) == (16, 8)));
+        // Five frames at 100 fps span at least four intervals
+        assert!(started.elapsed() >= Duration::from_millis(35));
+
+        let stats = driver.stats();
+        assert!(stats.frames_captured >= 5);
+        assert!(stats.fps > 0.0);
+
+        let capture = driver.stop().unwrap();
+        assert!(!capture.is_active());
+    }
+
+    #[test]
+    fn test_driver_stream_ends_with_backend_error() {
+        let capture = IdleCapture {
+            config: CaptureConfig { target_fps: 1000, ..Default::default() },
+            polls: 0,
+            fail_after: 3,
+        };
+        let mut driver = CaptureDriver::start(Box::new(capture)).unwrap();
+
+        futures::executor::block_on(async {
+            assert!(driver.next().await.unwrap().is_err());
+            assert!(driver.next().await.is_none());
+        });
+        assert_eq!(driver.stats().frames_captured, 0);
+    }
+
+    #[test]
+    fn test_slow_consumer_skips_frames() {
+        let mut driver = CaptureDriver::start(synthetic(1000)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        assert!(driver.stats().skipped_frames > 0);
+        assert!(driver.iter().next().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,280 @@
+//! Display configuration change detection
+//!
+//! [`DisplayWatcher`] keeps the latest [`VirtualDesktop`] and reports how
+//! it changed as monitors are plugged in, removed, resized, rotated or
+//! rescaled. On X11 it re-enumerates when RandR announces a change;
+//! elsewhere it polls.
+
+use super::monitor::{get_virtual_desktop, Monitor, VirtualDesktop};
+use crate::Result;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How often monitors are re-enumerated without change notifications
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A change between two desktop snapshots
+#[derive(Debug, Clone)]
+pub enum DisplayChange {
+    /// A monitor was connected or enabled
+    Added(Monitor),
+    /// A monitor was disconnected or disabled
+    Removed(Monitor),
+    /// A monitor moved or changed resolution
+    BoundsChanged {
+        /// Monitor before the change
+        previous: Monitor,
+        /// Monitor after the change
+        current: Monitor,
+    },
+    /// A monitor was rotated
+    Rotated {
+        /// Monitor before the change
+        previous: Monitor,
+        /// Monitor after the change
+        current: Monitor,
+    },
+    /// A monitor's DPI scaling changed
+    ScaleChanged {
+        /// Monitor before the change
+        previous: Monitor,
+        /// Monitor after the change
+        current: Monitor,
+    },
+    /// Another monitor became primary
+    PrimaryChanged {
+        /// Id of the former primary monitor
+        previous: String,
+        /// Id of the new primary monitor
+        current: String,
+    },
+}
+
+impl fmt::Display for DisplayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayChange::Added(monitor) => write!(f, "Monitor added: {}", monitor),
+            DisplayChange::Removed(monitor) => write!(f, "Monitor removed: {}", monitor),
+            DisplayChange::BoundsChanged { current, .. } => write!(f, "Monitor reconfigured: {}", current),
+            DisplayChange::Rotated { current, .. } => {
+                write!(f, "Monitor rotated to {}: {}", current.orientation, current.name)
+            }
+            DisplayChange::ScaleChanged { current, .. } => {
+                write!(f, "Monitor scale changed to {}: {}", current.scale_factor, current.name)
+            }
+            DisplayChange::PrimaryChanged { current, .. } => write!(f, "Primary monitor is now {}", current),
+        }
+    }
+}
+
+/// Changes from `previous` to `current`, matching monitors by id
+pub fn diff_desktops(previous: &VirtualDesktop, current: &VirtualDesktop) -> Vec<DisplayChange> {
+    let mut changes = Vec::new();
+
+    for old in &previous.monitors {
+        let Some(new) = current.monitors.iter().find(|m| m.id == old.id) else {
+            changes.push(DisplayChange::Removed(old.clone()));
+            continue;
+        };
+        let pair = || (old.clone(), new.clone());
+        if new.bounds != old.bounds {
+            let (previous, current) = pair();
+            changes.push(DisplayChange::BoundsChanged { previous, current });
+        }
+        if new.orientation != old.orientation {
+            let (previous, current) = pair();
+            changes.push(DisplayChange::Rotated { previous, current });
+        }
+        if new.scale_factor != old.scale_factor {
+            let (previous, current) = pair();
+            changes.push(DisplayChange::ScaleChanged { previous, current });
+        }
+    }
+
+    for new in &current.monitors {
+        if !previous.monitors.iter().any(|m| m.id == new.id) {
+            changes.push(DisplayChange::Added(new.clone()));
+        }
+    }
+
+    let (old_primary, new_primary) = (&previous.primary_monitor().id, &current.primary_monitor().id);
+    if old_primary != new_primary {
+        changes.push(DisplayChange::PrimaryChanged {
+            previous: old_primary.clone(),
+            current: new_primary.clone(),
+        });
+    }
+
+    changes
+}
+
+type Enumerate = Box<dyn FnMut() -> Result<VirtualDesktop> + Send + Sync>;
+
+/// When to re-enumerate monitors
+enum Trigger {
+    Poll { interval: Duration, last: Instant },
+    #[cfg(all(target_os = "linux", feature = "x11"))]
+    RandR(super::linux::RandrNotifier),
+}
+
+/// Watches the display configuration for changes
+pub struct DisplayWatcher {
+    desktop: VirtualDesktop,
+    enumerate: Enumerate,
+    trigger: Trigger,
+}
+
+impl DisplayWatcher {
+    /// Watch the platform's monitors
+    ///
+    /// Uses RandR notifications on X11 and polls every
+    /// [`DEFAULT_POLL_INTERVAL`] elsewhere.
+    pub fn new() -> Result<Self> {
+        let desktop = get_virtual_desktop()?;
+
+        #[cfg(all(target_os = "linux", feature = "x11"))]
+        if !is_wayland_session() {
+            match super::linux::RandrNotifier::new() {
+                Ok(notifier) => {
+                    return Ok(Self {
+                        desktop,
+                        enumerate: Box::new(get_virtual_desktop),
+                        trigger: Trigger::RandR(notifier),
+                    });
+                }
+                Err(e) => log::debug!("RandR notifications unavailable, polling monitors: {}", e),
+            }
+        }
+
+        Ok(Self::with_desktop(desktop, DEFAULT_POLL_INTERVAL, Box::new(get_virtual_desktop)))
+    }
+
+    /// Watch by calling `enumerate` every `interval`
+    pub fn polling(
+        interval: Duration,
+        mut enumerate: impl FnMut() -> Result<VirtualDesktop> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let desktop = enumerate()?;
+        Ok(Self::with_desktop(desktop, interval, Box::new(enumerate)))
+    }
+
+    fn with_desktop(desktop: VirtualDesktop, interval: Duration, enumerate: Enumerate) -> Self {
+        Self {
+            desktop,
+            enumerate,
+            trigger: Trigger::Poll { interval, last: Instant::now() },
+        }
+    }
+
+    /// Latest desktop snapshot
+    pub fn desktop(&self) -> &VirtualDesktop {
+        &self.desktop
+    }
+
+    /// Changes since the last call (non-blocking)
+    ///
+    /// Returns an empty list until a notification arrives or the poll
+    /// interval elapses.
+    pub fn poll(&mut self) -> Result<Vec<DisplayChange>> {
+        let due = match &mut self.trigger {
+            Trigger::Poll { interval, last } => {
+                let due = last.elapsed() >= *interval;
+                if due {
+                    *last = Instant::now();
+                }
+                due
+            }
+            #[cfg(all(target_os = "linux", feature = "x11"))]
+            Trigger::RandR(notifier) => notifier.changed(),
+        };
+        if !due {
+            return Ok(Vec::new());
+        }
+
+        let desktop = (self.enumerate)()?;
+        let changes = diff_desktops(&self.desktop, &desktop);
+        self.desktop = desktop;
+        Ok(changes)
+    }
+}
+
+/// Watcher for a platform capture backend, or `None` if monitors cannot be
+/// enumerated
+pub(crate) fn watch_displays() -> Option<DisplayWatcher> {
+    DisplayWatcher::new()
+        .map_err(|e| log::warn!("Display changes will not be detected: {}", e))
+        .ok()
+}
+
+#[cfg(all(target_os = "linux", feature = "x11"))]
+fn is_wayland_session() -> bool {
+    #[cfg(feature = "wayland")]
+    return super::linux::wayland::is_wayland_session();
+
+    #[cfg(not(feature = "wayland"))]
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::monitor::{MonitorBounds, MonitorOrientation};
+    use std::sync::{Arc, Mutex};
+
+    /// Monitor identified by its output name, as diffs match on ids
+    fn output(id: &str, x: i32, is_primary: bool) -> Monitor {
+        Monitor {
+            id: id.to_string(),
+            name: id.to_string(),
+            is_primary,
+            ..Monitor::test(0, MonitorBounds::new(x, 0, 1920, 1080))
+        }
+    }
+
+    #[test]
+    fn test_diff_desktops() {
+        let previous = VirtualDesktop::new(vec![output("DP-1", 0, true), output("HDMI-1", 1920, false)]).unwrap();
+
+        let mut rotated = output("DP-1", 0, false);
+        rotated.bounds = MonitorBounds::new(0, 0, 1080, 1920);
+        rotated.orientation = MonitorOrientation::Portrait;
+        let current = VirtualDesktop::new(vec![rotated, output("DP-2", 1080, true)]).unwrap();
+
+        let changes = diff_desktops(&previous, &current);
+        let kinds: Vec<&str> = changes
+            .iter()
+            .map(|change| match change {
+                DisplayChange::BoundsChanged { .. } => "bounds",
+                DisplayChange::Rotated { .. } => "rotated",
+                DisplayChange::Removed(m) if m.id == "HDMI-1" => "removed",
+                DisplayChange::Added(m) if m.id == "DP-2" => "added",
+                DisplayChange::PrimaryChanged { previous, current } if previous == "DP-1" && current == "DP-2" => {
+                    "primary"
+                }
+                other => panic!("unexpected change {:?}", other),
+            })
+            .collect();
+        assert_eq!(kinds, vec!["bounds", "rotated", "removed", "added", "primary"]);
+
+        assert!(diff_desktops(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_polling_watcher_reports_changes_once() {
+        let monitors = Arc::new(Mutex::new(vec![output("DP-1", 0, true)]));
+        let source = monitors.clone();
+        let mut watcher = DisplayWatcher::polling(Duration::ZERO, move || {
+            VirtualDesktop::new(source.lock().unwrap().clone())
+        })
+        .unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        let mut scaled = output("DP-1", 0, true);
+        scaled.scale_factor = 2.0;
+        *monitors.lock().unwrap() = vec![scaled];
+        let changes = watcher.poll().unwrap();
+        assert!(matches!(changes.as_slice(), [DisplayChange::ScaleChanged { .. }]));
+        assert_eq!(watcher.desktop().monitors[0].scale_factor, 2.0);
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+}
@@ -1,113 +0,0 @@
-//! Linux-specific screen capture implementation
-
-use super::{CaptureConfig, ScreenCapture, ScreenFrame};
-use super::monitor::{Monitor, MonitorBounds, MonitorOrientation};
-use crate::{Result, RemoteCError};
-
-/// Linux screen capture implementation
-pub struct LinuxCapture {
-    config: CaptureConfig,
-    active: bool,
-}
-
-impl LinuxCapture {
-    /// Create a new Linux screen capture instance
-    pub fn new(config: CaptureConfig) -> Result<Self> {
-        Ok(Self {
-            config,
-            active: false,
-        })
-    }
-}
-
-impl ScreenCapture for LinuxCapture {
-    fn start(&mut self) -> Result<()> {
-        if self.active {
-            return Err(RemoteCError::CaptureError("Already capturing".to_string()));
-        }
-        
-        // TODO: Implement X11/Wayland capture
-        self.active = true;
-        Ok(())
-    }
-    
-    fn stop(&mut self) -> Result<()> {
-        if !self.active {
-            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
-        }
-        
-        self.active = false;
-        Ok(())
-    }
-    
-    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
-        if !self.active {
-            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
-        }
-        
-        // TODO: Implement frame capture
-        Ok(None)
-    }
-    
-    fn is_active(&self) -> bool {
-        self.active
-    }
-    
-    fn config(&self) -> &CaptureConfig {
-        &self.config
-    }
-}
-
-/// Monitor enumeration for Linux
-pub fn enumerate_monitors_linux() -> Result<Vec<Monitor>> {
-    // TODO: Implement using X11 RandR or Wayland protocols
-    // This is a stub implementation that returns a default monitor configuration
-    
-    // In a real implementation, we would:
-    // 1. Check if running under X11 or Wayland
-    // 2. For X11: Use XRandR extension to query monitors
-    // 3. For Wayland: Use wl_output protocol
-    
-    // For now, return a single default monitor
-    Ok(vec![Monitor {
-        id: "default".to_string(),
-        index: 0,
-        name: "Built-in Display".to_string(),
-        is_primary: true,
-        bounds: MonitorBounds::new(0, 0, 1920, 1080),
-        work_area: MonitorBounds::new(0, 0, 1920, 1040), // Assuming 40px taskbar
-        scale_factor: 1.0,
-        refresh_rate: 60,
-        bit_depth: 32,
-        orientation: MonitorOrientation::Landscape,
-    }])
-}
-
-// X11 implementation placeholder
-#[cfg(feature = "x11")]
-mod x11 {
-    use super::*;
-    
-    pub fn enumerate_monitors_x11() -> Result<Vec<Monitor>> {
-        // TODO: Implement using XRandR
-        // - XOpenDisplay
-        // - XRRGetScreenResourcesCurrent
-        // - XRRGetOutputInfo for each output
-        // - XRRGetCrtcInfo for position and size
-        Err(RemoteCError::NotImplemented("X11 monitor enumeration not yet implemented".to_string()))
-    }
-}
-
-// Wayland implementation placeholder
-#[cfg(feature = "wayland")]
-mod wayland {
-    use super::*;
-    
-    pub fn enumerate_monitors_wayland() -> Result<Vec<Monitor>> {
-        // TODO: Implement using Wayland protocols
-        // - Connect to Wayland compositor
-        // - Bind to wl_output global
-        // - Listen for geometry and mode events
-        Err(RemoteCError::NotImplemented("Wayland monitor enumeration not yet implemented".to_string()))
-    }
-}
\ No newline at end of file
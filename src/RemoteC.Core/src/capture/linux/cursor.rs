@@ -0,0 +1,177 @@
+//! X11 cursor source using the XFIXES extension
+//!
+//! The core protocol cannot read the cursor image, so shapes come from
+//! `XFixesGetCursorImage`. The image is only fetched after a cursor change
+//! notification, while the position is polled with `XQueryPointer`.
+
+use super::x11::X11Display;
+use crate::capture::cursor::{CursorSample, CursorShape, CursorSource};
+use crate::{RemoteCError, Result};
+use std::os::raw::{c_int, c_uint, c_ulong};
+use x11::{xfixes, xlib};
+
+/// `XFixesCursorNotify` offset from the extension event base
+const X_FIXES_CURSOR_NOTIFY: c_int = 1;
+
+/// `XFixesDisplayCursorNotifyMask`
+const X_FIXES_DISPLAY_CURSOR_NOTIFY_MASK: c_ulong = 1;
+
+// The x11 crate declares this with the display passed by value
+extern "C" {
+    fn XFixesSelectCursorInput(dpy: *mut xlib::Display, win: xlib::Window, event_mask: c_ulong);
+}
+
+/// Cursor shape and position read through XFIXES
+pub struct X11CursorSource {
+    display: X11Display,
+    cursor_notify: c_int,
+    shape_changed: bool,
+}
+
+impl X11CursorSource {
+    /// Watch the cursor on a new connection to the default display
+    ///
+    /// A dedicated connection keeps cursor events out of the capture
+    /// connection's queue.
+    pub fn new() -> Result<Self> {
+        let display = X11Display::open(None)?;
+        let dpy = display.raw();
+        unsafe {
+            let (mut event_base, mut error_base) = (0, 0);
+            if xfixes::XFixesQueryExtension(dpy, &mut event_base, &mut error_base) == xlib::False {
+                return Err(RemoteCError::CaptureError("XFIXES extension not available".to_string()));
+            }
+
+            XFixesSelectCursorInput(dpy, display.root(), X_FIXES_DISPLAY_CURSOR_NOTIFY_MASK);
+            xlib::XFlush(dpy);
+
+            Ok(Self {
+                display,
+                cursor_notify: event_base + X_FIXES_CURSOR_NOTIFY,
+                // Report the initial shape on the first sample
+                shape_changed: true,
+            })
+        }
+    }
+
+    fn drain_events(&mut self) {
+        let dpy = self.display.raw();
+        unsafe {
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            while xlib::XPending(dpy) > 0 {
+                xlib::XNextEvent(dpy, &mut event);
+                if event.get_type() == self.cursor_notify {
+                    self.shape_changed = true;
+                }
+            }
+        }
+    }
+
+    fn fetch_shape(&self) -> Result<CursorShape> {
+        unsafe {
+            let image = xfixes::XFixesGetCursorImage(self.display.raw());
+            if image.is_null() {
+                return Err(RemoteCError::CaptureError("Failed to read cursor image".to_string()));
+            }
+
+            let width = u32::from((*image).width);
+            let height = u32::from((*image).height);
+            let pixels = std::slice::from_raw_parts((*image).pixels, (width * height) as usize);
+            let data = xfixes_pixels_to_bgra(pixels);
+            let (hotspot_x, hotspot_y) = (u32::from((*image).xhot), u32::from((*image).yhot));
+            xlib::XFree(image.cast());
+
+            CursorShape::new(width, height, hotspot_x, hotspot_y, data)
+        }
+    }
+
+    fn query_pointer(&self) -> (i32, i32, bool) {
+        unsafe {
+            let (mut root, mut child): (xlib::Window, xlib::Window) = (0, 0);
+            let (mut root_x, mut root_y, mut win_x, mut win_y): (c_int, c_int, c_int, c_int) = (0, 0, 0, 0);
+            let mut mask: c_uint = 0;
+            let same_screen = xlib::XQueryPointer(
+                self.display.raw(),
+                self.display.root(),
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut win_x,
+                &mut win_y,
+                &mut mask,
+            );
+            (root_x, root_y, same_screen != xlib::False)
+        }
+    }
+}
+
+impl CursorSource for X11CursorSource {
+    fn sample(&mut self) -> Result<CursorSample> {
+        self.drain_events();
+
+        let shape = if self.shape_changed {
+            let shape = self.fetch_shape()?;
+            self.shape_changed = false;
+            Some(shape)
+        } else {
+            None
+        };
+
+        // The pointer is on another X screen when `same_screen` is false
+        let (x, y, visible) = self.query_pointer();
+        Ok(CursorSample { x, y, visible, shape })
+    }
+}
+
+// SAFETY: X11CursorSource exclusively owns its display connection, which is
+// only used from `sample(&mut self)`, so one thread uses it at a time.
+unsafe impl Send for X11CursorSource {}
+unsafe impl Sync for X11CursorSource {}
+
+/// Convert XFIXES premultiplied ARGB pixels to straight-alpha BGRA
+///
+/// Each pixel is stored in an `unsigned long`, so only the low 32 bits are
+/// meaningful on 64-bit platforms.
+fn xfixes_pixels_to_bgra(pixels: &[c_ulong]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() * 4);
+    for &pixel in pixels {
+        let [b, g, r, a] = (pixel as u32).to_le_bytes();
+        let unpremultiply = |c: u8| match a {
+            0 => 0,
+            255 => c,
+            _ => ((u32::from(c) * 255 + u32::from(a) / 2) / u32::from(a)).min(255) as u8,
+        };
+        out.extend_from_slice(&[unpremultiply(b), unpremultiply(g), unpremultiply(r), a]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xfixes_pixels_are_unpremultiplied() {
+        let pixels: [c_ulong; 3] = [0xff10_2030, 0x8040_4040, 0x0000_0000];
+        assert_eq!(
+            xfixes_pixels_to_bgra(&pixels),
+            vec![0x30, 0x20, 0x10, 0xff, 0x80, 0x80, 0x80, 0x80, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_sample_reports_initial_shape() {
+        if std::env::var_os("DISPLAY").is_none() {
+            return;
+        }
+        let Ok(mut source) = X11CursorSource::new() else {
+            return;
+        };
+
+        let first = source.sample().unwrap();
+        let shape = first.shape.expect("first sample carries the shape");
+        assert_eq!(shape.data.len(), (shape.width * shape.height * 4) as usize);
+        assert!(source.sample().unwrap().shape.is_none());
+    }
+}
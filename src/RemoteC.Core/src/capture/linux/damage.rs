@@ -0,0 +1,153 @@
+//! Damage tracking through the X DAMAGE extension
+//!
+//! The damage object accumulates every region of the root window redrawn
+//! since the last fetch, so a capture can report which parts of the frame
+//! changed, or skip the frame entirely when nothing did.
+
+use super::x11::X11Display;
+use crate::capture::monitor::MonitorBounds;
+use crate::capture::CaptureRegion;
+use crate::{RemoteCError, Result};
+use std::os::raw::{c_int, c_ulong};
+use std::ptr;
+use x11::{xfixes, xlib};
+
+/// Above this many rectangles a single bounding box is reported instead
+const MAX_DIRTY_RECTS: usize = 64;
+
+type Damage = c_ulong;
+
+/// Only report the transition from empty to non-empty damage
+const X_DAMAGE_REPORT_NON_EMPTY: c_int = 3;
+
+// The x11 crate has no bindings for libXdamage
+extern "C" {
+    fn XDamageQueryExtension(dpy: *mut xlib::Display, event_base: *mut c_int, error_base: *mut c_int) -> xlib::Bool;
+    fn XDamageCreate(dpy: *mut xlib::Display, drawable: xlib::Drawable, level: c_int) -> Damage;
+    fn XDamageDestroy(dpy: *mut xlib::Display, damage: Damage);
+    fn XDamageSubtract(
+        dpy: *mut xlib::Display,
+        damage: Damage,
+        repair: xfixes::XserverRegion,
+        parts: xfixes::XserverRegion,
+    );
+}
+
+/// Damage accumulated on the root window
+pub struct DamageTracker {
+    display: *mut xlib::Display,
+    damage: Damage,
+    /// Scratch region the accumulated damage is moved into
+    parts: xfixes::XserverRegion,
+}
+
+impl DamageTracker {
+    /// Start tracking damage on the root window of `display`
+    pub fn new(display: &X11Display) -> Result<Self> {
+        let dpy = display.raw();
+        unsafe {
+            let (mut event_base, mut error_base) = (0, 0);
+            if XDamageQueryExtension(dpy, &mut event_base, &mut error_base) == xlib::False
+                || xfixes::XFixesQueryExtension(dpy, &mut event_base, &mut error_base) == xlib::False
+            {
+                return Err(RemoteCError::CaptureError("DAMAGE extension not available".to_string()));
+            }
+
+            let root = display.root();
+            let damage = display.trap_errors(|| XDamageCreate(dpy, root, X_DAMAGE_REPORT_NON_EMPTY))?;
+            let parts = xfixes::XFixesCreateRegion(dpy, ptr::null_mut(), 0);
+
+            Ok(Self { display: dpy, damage, parts })
+        }
+    }
+
+    /// Take the damage accumulated since the last call, in root coordinates
+    pub fn take(&mut self) -> Vec<MonitorBounds> {
+        unsafe {
+            // Notify events only signal that damage exists; drop them so the
+            // queue does not grow
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            while xlib::XPending(self.display) > 0 {
+                xlib::XNextEvent(self.display, &mut event);
+            }
+
+            XDamageSubtract(self.display, self.damage, 0, self.parts);
+
+            let mut count = 0;
+            let rects = xfixes::XFixesFetchRegion(self.display, self.parts, &mut count);
+            if rects.is_null() {
+                return Vec::new();
+            }
+
+            let damage = std::slice::from_raw_parts(rects, count.max(0) as usize)
+                .iter()
+                .map(|r| MonitorBounds::new(i32::from(r.x), i32::from(r.y), u32::from(r.width), u32::from(r.height)))
+                .collect();
+            xlib::XFree(rects.cast());
+            damage
+        }
+    }
+}
+
+impl Drop for DamageTracker {
+    fn drop(&mut self) {
+        unsafe {
+            xfixes::XFixesDestroyRegion(self.display, self.parts);
+            XDamageDestroy(self.display, self.damage);
+        }
+    }
+}
+
+/// Clip root-window damage to `bounds` and make it relative to its origin
+///
+/// Too many rectangles are merged into their bounding box, which is cheaper
+/// for consumers than walking hundreds of tiny regions.
+pub fn dirty_regions(damage: &[MonitorBounds], bounds: &MonitorBounds) -> Vec<CaptureRegion> {
+    let clipped: Vec<MonitorBounds> = damage.iter().filter_map(|rect| rect.intersection(bounds)).collect();
+
+    let merged = if clipped.len() > MAX_DIRTY_RECTS {
+        vec![clipped[1..].iter().fold(clipped[0], |acc, rect| acc.union(rect))]
+    } else {
+        clipped
+    };
+
+    merged
+        .into_iter()
+        .map(|rect| CaptureRegion {
+            x: rect.x - bounds.x,
+            y: rect.y - bounds.y,
+            width: rect.width,
+            height: rect.height,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_regions_are_clipped_and_relative() {
+        let bounds = MonitorBounds::new(1920, 0, 1920, 1080);
+        let damage = [
+            MonitorBounds::new(1900, 10, 100, 20), // straddles the left edge
+            MonitorBounds::new(0, 0, 50, 50),      // other monitor
+            MonitorBounds::new(2000, 500, 10, 10),
+        ];
+
+        let regions = dirty_regions(&damage, &bounds);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].x, regions[0].y, regions[0].width, regions[0].height), (0, 10, 80, 20));
+        assert_eq!((regions[1].x, regions[1].y, regions[1].width, regions[1].height), (80, 500, 10, 10));
+    }
+
+    #[test]
+    fn test_many_dirty_regions_are_merged() {
+        let bounds = MonitorBounds::new(0, 0, 1000, 1000);
+        let damage: Vec<MonitorBounds> = (0..100).map(|i| MonitorBounds::new(i * 5, i * 2, 2, 2)).collect();
+
+        let regions = dirty_regions(&damage, &bounds);
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].x, regions[0].y, regions[0].width, regions[0].height), (0, 0, 497, 200));
+    }
+}
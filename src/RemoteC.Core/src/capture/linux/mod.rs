@@ -0,0 +1,438 @@
+//! Linux-specific screen capture implementation
+
+#[cfg(feature = "x11")]
+use super::compositor::{Compositor, CompositorConfig};
+use super::cursor::{CursorChannel, CursorEvent};
+use super::hotplug::{self, DisplayWatcher};
+use super::{CaptureConfig, CaptureMode, ScreenCapture, ScreenFrame};
+use super::monitor::{Monitor, MonitorBounds, VirtualDesktop};
+use crate::{Result, RemoteCError};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "x11")]
+pub mod cursor;
+#[cfg(feature = "x11")]
+mod damage;
+#[cfg(feature = "x11")]
+mod randr;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "x11")]
+pub mod window;
+#[cfg(feature = "x11")]
+pub mod x11;
+
+#[cfg(feature = "x11")]
+pub(crate) use randr::RandrNotifier;
+
+/// Linux screen capture implementation
+pub struct LinuxCapture {
+    config: CaptureConfig,
+    active: bool,
+    last_frame: Option<Instant>,
+    cursor: Option<CursorChannel>,
+    watcher: Option<DisplayWatcher>,
+    /// Mark the next delivered frame as a keyframe
+    keyframe_pending: bool,
+    #[cfg(feature = "x11")]
+    x11: Option<x11::X11Capture>,
+    /// Selected monitors grabbed one by one and stitched together
+    #[cfg(feature = "x11")]
+    x11_composed: Vec<(usize, x11::X11Capture)>,
+    #[cfg(feature = "x11")]
+    compositor: Option<Compositor>,
+    #[cfg(feature = "x11")]
+    x11_window: Option<window::X11WindowCapture>,
+    #[cfg(feature = "wayland")]
+    wayland: Option<wayland::WaylandCapture>,
+}
+
+impl LinuxCapture {
+    /// Create a new Linux screen capture instance
+    pub fn new(config: CaptureConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            active: false,
+            last_frame: None,
+            cursor: None,
+            watcher: None,
+            keyframe_pending: false,
+            #[cfg(feature = "x11")]
+            x11: None,
+            #[cfg(feature = "x11")]
+            x11_composed: Vec::new(),
+            #[cfg(feature = "x11")]
+            compositor: None,
+            #[cfg(feature = "x11")]
+            x11_window: None,
+            #[cfg(feature = "wayland")]
+            wayland: None,
+        })
+    }
+
+    /// Minimum time between two frames
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.config.target_fps.max(1)
+    }
+
+    fn start_backend(&mut self) -> Result<()> {
+        // Under Wayland, X11 capture through XWayland only sees X clients
+        #[cfg(feature = "wayland")]
+        if wayland::is_wayland_session() {
+            if let CaptureMode::Window(_) = self.config.mode {
+                return Err(RemoteCError::NotImplemented(
+                    "Window capture is not available on Wayland".to_string()
+                ));
+            }
+            self.wayland = Some(wayland::WaylandCapture::new(&self.config)?);
+            return Ok(());
+        }
+
+        #[cfg(feature = "x11")]
+        {
+            let display = x11::X11Display::open(None)?;
+            let bounds = if let CaptureMode::Window(query) = &self.config.mode {
+                let windows = window::enumerate_windows_x11(&display)?;
+                let target = super::window::find_window(&windows, query).ok_or_else(|| {
+                    RemoteCError::CaptureError(format!("Window not found: {}", query))
+                })?;
+                let capture = window::X11WindowCapture::new(display, target.id as ::x11::xlib::Window)?;
+                let bounds = capture.bounds();
+                self.x11_window = Some(capture);
+                bounds
+            } else {
+                let desktop = VirtualDesktop::new(x11::enumerate_monitors_x11(&display)?)?;
+                let bounds = self.config.capture_area(&desktop)?;
+                match composed_monitors(&self.config, &desktop, bounds)? {
+                    Some(indices) => {
+                        self.compositor = Some(Compositor::new(&desktop, &indices, CompositorConfig::default())?);
+                        let mut display = Some(display);
+                        for index in indices {
+                            let Some(monitor) = desktop.get_monitor(index) else { continue };
+                            let display = match display.take() {
+                                Some(display) => display,
+                                None => x11::X11Display::open(None)?,
+                            };
+                            self.x11_composed.push((index, x11::X11Capture::new(display, monitor.bounds)?));
+                        }
+                    }
+                    None => self.x11 = Some(x11::X11Capture::new(display, bounds)?),
+                }
+                bounds
+            };
+
+            if self.config.capture_cursor && self.config.cursor_mode == super::CursorMode::Separate {
+                let source = cursor::X11CursorSource::new()?;
+                self.cursor = Some(CursorChannel::new(Box::new(source), (bounds.x, bounds.y)));
+            }
+            Ok(())
+        }
+
+        #[cfg(not(feature = "x11"))]
+        Err(RemoteCError::UnsupportedPlatform(
+            "Screen capture requires the x11 or wayland feature on Linux".to_string()
+        ))
+    }
+
+    fn capture_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        #[cfg(feature = "wayland")]
+        if let Some(wayland) = self.wayland.as_mut() {
+            return wayland.capture_frame();
+        }
+
+        #[cfg(feature = "x11")]
+        if let Some(x11) = self.x11.as_mut() {
+            return x11.capture_frame();
+        }
+
+        #[cfg(feature = "x11")]
+        if let Some(compositor) = self.compositor.as_mut() {
+            // Grabbing monitors one by one keeps unselected monitors and
+            // the gaps between them out of the canvas
+            let mut frames = Vec::new();
+            let mut error = None;
+            for (index, capture) in &mut self.x11_composed {
+                match capture.capture_frame() {
+                    Ok(Some(frame)) => frames.push((*index, frame)),
+                    Ok(None) => {}
+                    Err(e) => error = error.or(Some(e)),
+                }
+            }
+            if frames.is_empty() && error.is_none() {
+                return Ok(None);
+            }
+
+            let frames: Vec<(usize, &ScreenFrame)> = frames.iter().map(|(index, frame)| (*index, frame)).collect();
+            let composed = compositor.compose(&frames)?;
+            if let Some(e) = error {
+                // The monitors grabbed so far are on the canvas, but their
+                // changes would go unreported without a keyframe
+                self.keyframe_pending = true;
+                return Err(e);
+            }
+            return Ok(Some(composed));
+        }
+
+        #[cfg(feature = "x11")]
+        if let Some(window) = self.x11_window.as_mut() {
+            let frame = window.capture_frame()?;
+            // Keep cursor positions relative to the window as it moves
+            if let Some(cursor) = self.cursor.as_mut() {
+                let bounds = window.bounds();
+                cursor.set_origin((bounds.x, bounds.y));
+            }
+            return Ok(frame);
+        }
+
+        Err(RemoteCError::CaptureError("No capture backend".to_string()))
+    }
+
+    fn stop_backend(&mut self) {
+        self.cursor = None;
+        #[cfg(feature = "x11")]
+        {
+            self.x11 = None;
+            self.x11_composed.clear();
+            self.compositor = None;
+            self.x11_window = None;
+        }
+        #[cfg(feature = "wayland")]
+        {
+            self.wayland = None;
+        }
+    }
+
+    /// Restart the backend if the display layout changed since the last call
+    fn handle_display_changes(&mut self) -> Result<()> {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return Ok(());
+        };
+        let changes = match watcher.poll() {
+            Ok(changes) => changes,
+            Err(e) => {
+                log::warn!("Failed to check the display configuration: {}", e);
+                return Ok(());
+            }
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        for change in &changes {
+            log::info!("{}", change);
+        }
+        // Monitor indices, sizes and outputs may all be stale
+        self.stop_backend();
+        self.start_backend()?;
+        self.keyframe_pending = true;
+        Ok(())
+    }
+}
+
+impl ScreenCapture for LinuxCapture {
+    fn start(&mut self) -> Result<()> {
+        if self.active {
+            return Err(RemoteCError::CaptureError("Already capturing".to_string()));
+        }
+
+        self.start_backend()?;
+        self.watcher = hotplug::watch_displays();
+        self.keyframe_pending = false;
+        self.last_frame = None;
+        self.active = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if !self.active {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+
+        self.stop_backend();
+        self.watcher = None;
+        self.active = false;
+        Ok(())
+    }
+
+    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        if !self.active {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+
+        // Pace to target_fps without blocking the caller
+        if let Some(last) = self.last_frame {
+            if last.elapsed() < self.frame_interval() {
+                return Ok(None);
+            }
+        }
+
+        self.handle_display_changes()?;
+
+        // Unchanged screens yield no frame but still count against the pacing
+        let mut frame = self.capture_frame()?;
+        if let Some(frame) = frame.as_mut() {
+            frame.keyframe |= std::mem::take(&mut self.keyframe_pending);
+        }
+        self.last_frame = Some(frame.as_ref().map_or_else(Instant::now, |f| f.timestamp));
+        Ok(frame)
+    }
+
+    fn cursor_events(&mut self) -> Result<Vec<CursorEvent>> {
+        if !self.active {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+
+        // Wayland has no protocol for reading the cursor of other clients
+        match self.cursor.as_mut() {
+            Some(cursor) => cursor.poll(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+}
+
+/// Compute the desktop rectangle to capture for `config`
+///
+/// See [`CaptureConfig::capture_area`].
+pub fn capture_bounds(config: &CaptureConfig, monitors: Vec<Monitor>) -> Result<MonitorBounds> {
+    config.capture_area(&VirtualDesktop::new(monitors)?)
+}
+
+/// Selected monitors to grab one by one and compose for `config`
+///
+/// `area` is the [`CaptureConfig::capture_area`] on `desktop`. Returns
+/// `None` when it lies on a single selected monitor and can be grabbed
+/// directly; the bounding box of several selected monitors may cover
+/// unselected ones, so they are composed instead. Regions spanning several
+/// selected monitors are rejected rather than grabbed whole.
+pub fn composed_monitors(config: &CaptureConfig, desktop: &VirtualDesktop, area: MonitorBounds) -> Result<Option<Vec<usize>>> {
+    let CaptureMode::SelectedMonitors(indices) = &config.mode else {
+        return Ok(None);
+    };
+    let on_one_monitor = indices
+        .iter()
+        .filter_map(|&index| desktop.get_monitor(index))
+        .any(|monitor| monitor.bounds.intersection(&area) == Some(area));
+    if on_one_monitor {
+        return Ok(None);
+    }
+    if config.region.is_some() {
+        return Err(RemoteCError::CaptureError(
+            "Capture regions spanning several selected monitors are not supported".to_string(),
+        ));
+    }
+    Ok(Some(indices.clone()))
+}
+
+/// Monitor enumeration for Linux
+pub fn enumerate_monitors_linux() -> Result<Vec<Monitor>> {
+    #[cfg(feature = "wayland")]
+    if wayland::is_wayland_session() {
+        match wayland::enumerate_monitors_wayland() {
+            Ok(monitors) => return Ok(monitors),
+            Err(e) => log::debug!("Wayland monitor enumeration unavailable: {}", e),
+        }
+    }
+
+    #[cfg(feature = "x11")]
+    {
+        match x11::X11Display::open(None).and_then(|display| x11::enumerate_monitors_x11(&display)) {
+            Ok(monitors) => return Ok(monitors),
+            Err(e) => log::debug!("X11 monitor enumeration unavailable: {}", e),
+        }
+    }
+
+    Err(RemoteCError::CaptureError(if cfg!(any(feature = "x11", feature = "wayland")) {
+        "No display server reachable for monitor enumeration".to_string()
+    } else {
+        "Monitor enumeration requires the linux or wayland feature".to_string()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CaptureQuality, CaptureRegion, CaptureSource, CursorMode};
+
+    fn config(mode: CaptureMode, region: Option<CaptureRegion>) -> CaptureConfig {
+        CaptureConfig {
+            mode,
+            target_fps: 30,
+            capture_cursor: false,
+            cursor_mode: CursorMode::Embedded,
+            region,
+            quality: CaptureQuality::default(),
+            source: CaptureSource::Platform,
+            redaction: None,
+        }
+    }
+
+    #[test]
+    fn test_capture_bounds_modes() {
+        let monitors = vec![
+            Monitor { is_primary: false, ..Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)) },
+            Monitor { is_primary: true, ..Monitor::test(1, MonitorBounds::new(1920, 0, 1920, 1080)) },
+        ];
+
+        let primary = capture_bounds(&config(CaptureMode::PrimaryMonitor, None), monitors.clone()).unwrap();
+        assert_eq!(primary, MonitorBounds::new(1920, 0, 1920, 1080));
+
+        let all = capture_bounds(&config(CaptureMode::AllMonitors, None), monitors.clone()).unwrap();
+        assert_eq!(all, MonitorBounds::new(0, 0, 3840, 1080));
+
+        assert!(capture_bounds(&config(CaptureMode::SingleMonitor(5), None), monitors).is_err());
+    }
+
+    #[test]
+    fn test_capture_bounds_region_is_clipped() {
+        let monitors = vec![
+            Monitor { is_primary: false, ..Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)) },
+            Monitor { is_primary: true, ..Monitor::test(1, MonitorBounds::new(1920, 0, 1920, 1080)) },
+        ];
+        let region = CaptureRegion { x: 1800, y: 100, width: 400, height: 200 };
+
+        let bounds = capture_bounds(&config(CaptureMode::PrimaryMonitor, Some(region)), monitors.clone()).unwrap();
+        assert_eq!(bounds, MonitorBounds::new(3720, 100, 120, 200));
+
+        let outside = CaptureRegion { x: 2000, y: 0, width: 10, height: 10 };
+        assert!(capture_bounds(&config(CaptureMode::PrimaryMonitor, Some(outside)), monitors).is_err());
+    }
+
+    #[test]
+    fn test_selected_monitors_are_composed() {
+        // The unselected monitor 1 lies between the selected ones
+        let desktop = VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)),
+            Monitor::test(1, MonitorBounds::new(1920, 0, 1920, 1080)),
+            Monitor::test(2, MonitorBounds::new(3840, 0, 1920, 1080)),
+        ]).unwrap();
+        let selected = config(CaptureMode::SelectedMonitors(vec![0, 2]), None);
+        let area = selected.capture_area(&desktop).unwrap();
+        assert_eq!(composed_monitors(&selected, &desktop, area).unwrap(), Some(vec![0, 2]));
+
+        // A region on one selected monitor is grabbed directly
+        let region = CaptureRegion { x: 3900, y: 0, width: 100, height: 100 };
+        let inside = config(CaptureMode::SelectedMonitors(vec![0, 2]), Some(region));
+        let area = inside.capture_area(&desktop).unwrap();
+        assert_eq!(composed_monitors(&inside, &desktop, area).unwrap(), None);
+
+        let region = CaptureRegion { x: 1800, y: 0, width: 2200, height: 100 };
+        let spanning = config(CaptureMode::SelectedMonitors(vec![0, 2]), Some(region));
+        let area = spanning.capture_area(&desktop).unwrap();
+        assert!(composed_monitors(&spanning, &desktop, area).is_err());
+
+        let single = config(CaptureMode::SelectedMonitors(vec![1]), None);
+        let area = single.capture_area(&desktop).unwrap();
+        assert_eq!(composed_monitors(&single, &desktop, area).unwrap(), None);
+        let all = config(CaptureMode::AllMonitors, None);
+        let area = all.capture_area(&desktop).unwrap();
+        assert_eq!(composed_monitors(&all, &desktop, area).unwrap(), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! Monitor enumeration through the X RandR extension
+
+use super::x11::X11Display;
+use crate::capture::monitor::{Monitor, MonitorBounds, MonitorOrientation};
+use crate::{RemoteCError, Result};
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_uint, c_ulong};
+use x11::{xlib, xrandr};
+
+/// Reference DPI corresponding to a scale factor of 1.0
+const BASE_DPI: f64 = 96.0;
+
+/// Enumerate active RandR outputs
+///
+/// Returns `None` when RandR is unavailable or reports no active output, in
+/// which case the caller should fall back to the core screen size.
+pub fn enumerate_monitors_randr(display: &X11Display) -> Option<Vec<Monitor>> {
+    let dpy = display.raw();
+    let root = display.root();
+
+    unsafe {
+        let (mut event_base, mut error_base) = (0, 0);
+        if xrandr::XRRQueryExtension(dpy, &mut event_base, &mut error_base) == xlib::False {
+            return None;
+        }
+
+        let resources = xrandr::XRRGetScreenResourcesCurrent(dpy, root);
+        if resources.is_null() {
+            return None;
+        }
+
+        let primary = xrandr::XRRGetOutputPrimary(dpy, root);
+        let scale_factor = xft_scale_factor(display);
+        let bit_depth = match xlib::XDefaultDepth(dpy, xlib::XDefaultScreen(dpy)) {
+            // 24-bit visuals are stored as 32-bit pixels
+            24 => 32,
+            depth => depth as u32,
+        };
+
+        let modes = slice((*resources).modes, (*resources).nmode);
+        let mut seen_crtcs = HashSet::new();
+        let mut monitors = Vec::new();
+
+        for &output in slice((*resources).outputs, (*resources).noutput) {
+            let info = xrandr::XRRGetOutputInfo(dpy, resources, output);
+            if info.is_null() {
+                continue;
+            }
+
+            // Mirrored outputs share a CRTC; report each CRTC once
+            let crtc = (*info).crtc;
+            let active = (*info).connection == xrandr::RR_Connected as u16 && crtc != 0;
+            if !active || !seen_crtcs.insert(crtc) {
+                xrandr::XRRFreeOutputInfo(info);
+                continue;
+            }
+
+            let crtc_info = xrandr::XRRGetCrtcInfo(dpy, resources, crtc);
+            if crtc_info.is_null() {
+                xrandr::XRRFreeOutputInfo(info);
+                continue;
+            }
+
+            let name = output_name(&*info);
+            let bounds = MonitorBounds::new((*crtc_info).x, (*crtc_info).y, (*crtc_info).width, (*crtc_info).height);
+            let refresh_rate = modes
+                .iter()
+                .find(|mode| mode.id == (*crtc_info).mode)
+                .map_or(60, |mode| mode_refresh_rate(mode.dotClock, mode.hTotal, mode.vTotal, mode.modeFlags));
+
+            monitors.push(Monitor {
+                id: name.clone(),
+                index: monitors.len(),
+                name,
+                is_primary: output == primary,
+                bounds,
+                work_area: bounds,
+                scale_factor,
+                refresh_rate,
+                bit_depth,
+                orientation: orientation_from_rotation((*crtc_info).rotation),
+            });
+
+            xrandr::XRRFreeCrtcInfo(crtc_info);
+            xrandr::XRRFreeOutputInfo(info);
+        }
+
+        xrandr::XRRFreeScreenResources(resources);
+
+        if monitors.is_empty() {
+            return None;
+        }
+        if !monitors.iter().any(|m| m.is_primary) {
+            monitors[0].is_primary = true;
+        }
+        Some(monitors)
+    }
+}
+
+/// RandR screen, CRTC and output change notifications
+pub struct RandrNotifier {
+    display: X11Display,
+    event_base: c_int,
+}
+
+impl RandrNotifier {
+    /// Listen for configuration changes on a new connection to the default
+    /// display, keeping the events out of capture connections' queues
+    pub fn new() -> Result<Self> {
+        let display = X11Display::open(None)?;
+        let dpy = display.raw();
+        unsafe {
+            let (mut event_base, mut error_base) = (0, 0);
+            if xrandr::XRRQueryExtension(dpy, &mut event_base, &mut error_base) == xlib::False {
+                return Err(RemoteCError::CaptureError("RandR extension not available".to_string()));
+            }
+
+            let mask = xrandr::RRScreenChangeNotifyMask | xrandr::RRCrtcChangeNotifyMask | xrandr::RROutputChangeNotifyMask;
+            xrandr::XRRSelectInput(dpy, display.root(), mask);
+            xlib::XFlush(dpy);
+
+            Ok(Self { display, event_base })
+        }
+    }
+
+    /// Whether the display configuration changed since the last call
+    pub fn changed(&mut self) -> bool {
+        let dpy = self.display.raw();
+        let mut changed = false;
+        unsafe {
+            let mut event: xlib::XEvent = std::mem::zeroed();
+            while xlib::XPending(dpy) > 0 {
+                xlib::XNextEvent(dpy, &mut event);
+                let kind = event.get_type() - self.event_base;
+                if kind == xrandr::RRScreenChangeNotify || kind == xrandr::RRNotify {
+                    // Keeps Xlib's cached screen size current
+                    xrandr::XRRUpdateConfiguration(&mut event);
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+// SAFETY: RandrNotifier exclusively owns its display connection, which is
+// only used from `changed(&mut self)`, so one thread uses it at a time.
+unsafe impl Send for RandrNotifier {}
+unsafe impl Sync for RandrNotifier {}
+
+/// View an Xlib-owned array as a slice
+unsafe fn slice<'a, T>(data: *mut T, len: c_int) -> &'a [T] {
+    if data.is_null() || len <= 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, len as usize)
+    }
+}
+
+unsafe fn output_name(info: &xrandr::XRROutputInfo) -> String {
+    let bytes = slice(info.name as *mut u8, info.nameLen);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Scale factor from the `Xft.dpi` resource, the setting desktops use for HiDPI
+fn xft_scale_factor(display: &X11Display) -> f32 {
+    let resources = unsafe { xlib::XResourceManagerString(display.raw()) };
+    if resources.is_null() {
+        return 1.0;
+    }
+
+    let resources = unsafe { CStr::from_ptr(resources) }.to_string_lossy();
+    parse_xft_dpi(&resources).map_or(1.0, |dpi| (dpi / BASE_DPI) as f32)
+}
+
+/// Find the `Xft.dpi` value in an X resource database string
+pub(crate) fn parse_xft_dpi(resources: &str) -> Option<f64> {
+    resources.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim() != "Xft.dpi" {
+            return None;
+        }
+        value.trim().parse::<f64>().ok().filter(|dpi| *dpi > 0.0)
+    })
+}
+
+/// Map a RandR rotation bitmask to an orientation; reflection bits are ignored
+pub(crate) fn orientation_from_rotation(rotation: u16) -> MonitorOrientation {
+    let rotation = c_int::from(rotation);
+    if rotation & xrandr::RR_Rotate_90 != 0 {
+        MonitorOrientation::Portrait
+    } else if rotation & xrandr::RR_Rotate_180 != 0 {
+        MonitorOrientation::LandscapeFlipped
+    } else if rotation & xrandr::RR_Rotate_270 != 0 {
+        MonitorOrientation::PortraitFlipped
+    } else {
+        MonitorOrientation::Landscape
+    }
+}
+
+/// Refresh rate in Hz of a mode line, rounded to the nearest integer
+pub(crate) fn mode_refresh_rate(dot_clock: c_ulong, h_total: c_uint, v_total: c_uint, flags: c_ulong) -> u32 {
+    let mut v_total = f64::from(v_total);
+    if flags & xrandr::RR_DoubleScan as c_ulong != 0 {
+        v_total *= 2.0;
+    }
+    if flags & xrandr::RR_Interlace as c_ulong != 0 {
+        v_total /= 2.0;
+    }
+
+    let pixels_per_frame = f64::from(h_total) * v_total;
+    if pixels_per_frame == 0.0 {
+        return 60;
+    }
+    (dot_clock as f64 / pixels_per_frame).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientation_from_rotation() {
+        assert_eq!(orientation_from_rotation(1), MonitorOrientation::Landscape);
+        assert_eq!(orientation_from_rotation(2), MonitorOrientation::Portrait);
+        assert_eq!(orientation_from_rotation(4), MonitorOrientation::LandscapeFlipped);
+        // Rotate 270 combined with Reflect X
+        assert_eq!(orientation_from_rotation(8 | 16), MonitorOrientation::PortraitFlipped);
+    }
+
+    #[test]
+    fn test_mode_refresh_rate() {
+        // 1920x1080@60 CEA mode
+        assert_eq!(mode_refresh_rate(148_500_000, 2200, 1125, 0), 60);
+        // 2560x1440@144
+        assert_eq!(mode_refresh_rate(586_586_000, 2720, 1497, 0), 144);
+        // Interlaced 1080i doubles the field rate
+        assert_eq!(mode_refresh_rate(74_250_000, 2200, 1125, xrandr::RR_Interlace as c_ulong), 60);
+        assert_eq!(mode_refresh_rate(0, 0, 0, 0), 60);
+    }
+
+    #[test]
+    fn test_parse_xft_dpi() {
+        assert_eq!(parse_xft_dpi("Xft.antialias:\t1\nXft.dpi:\t144\n"), Some(144.0));
+        assert_eq!(parse_xft_dpi("Xcursor.size: 24\n"), None);
+        assert_eq!(parse_xft_dpi("Xft.dpi: bogus\n"), None);
+    }
+}
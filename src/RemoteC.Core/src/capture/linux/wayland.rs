@@ -0,0 +1,1174 @@
+//! Wayland screen capture
+//!
+//! Uses ext-image-copy-capture when the compositor offers it and falls back
+//! to wlr-screencopy (sway, Hyprland, older wlroots). Frames are copied into
+//! a shared memory buffer. Both protocols only complete a frame once the
+//! output has new content, so capture requests are kept in flight and polled
+//! without blocking the caller.
+
+use crate::capture::monitor::{Monitor, MonitorBounds, MonitorOrientation, VirtualDesktop};
+use crate::capture::{BufferPool, CaptureConfig, CaptureRegion, CursorMode, FrameFormat, ScreenFrame};
+use crate::{RemoteCError, Result};
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::ptr;
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, GlobalList, GlobalListContents};
+use wayland_client::protocol::{wl_buffer, wl_output, wl_registry, wl_shm, wl_shm_pool};
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols::ext::image_capture_source::v1::client::{
+    ext_image_capture_source_v1, ext_output_image_capture_source_manager_v1,
+};
+use wayland_protocols::ext::image_copy_capture::v1::client::{
+    ext_image_copy_capture_frame_v1, ext_image_copy_capture_manager_v1, ext_image_copy_capture_session_v1,
+};
+use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
+use wayland_protocols_wlr::screencopy::v1::client::{zwlr_screencopy_frame_v1, zwlr_screencopy_manager_v1};
+
+/// Whether the process runs inside a Wayland session
+pub fn is_wayland_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+/// Output state reported through `wl_output` events
+#[derive(Debug, Clone)]
+pub struct OutputInfo {
+    /// Registry name of the output global
+    pub global_name: u32,
+    /// Connector name, e.g. `DP-1` (wl_output v4)
+    pub name: Option<String>,
+    /// Human-readable description (wl_output v4)
+    pub description: Option<String>,
+    /// Manufacturer
+    pub make: String,
+    /// Model
+    pub model: String,
+    /// Position in the compositor space (wl_output geometry)
+    pub x: i32,
+    /// Position in the compositor space (wl_output geometry)
+    pub y: i32,
+    /// Position in the compositor space reported by xdg-output
+    pub logical_position: Option<(i32, i32)>,
+    /// Size in the compositor space reported by xdg-output
+    pub logical_size: Option<(i32, i32)>,
+    /// Current mode width in pixels, before the transform
+    pub mode_width: i32,
+    /// Current mode height in pixels, before the transform
+    pub mode_height: i32,
+    /// Current mode refresh rate in mHz
+    pub refresh_mhz: i32,
+    /// Integer buffer scale; fractionally scaled outputs round it up
+    pub scale: i32,
+    /// Output transform
+    pub transform: wl_output::Transform,
+}
+
+impl OutputInfo {
+    fn new(global_name: u32) -> Self {
+        Self {
+            global_name,
+            name: None,
+            description: None,
+            make: String::new(),
+            model: String::new(),
+            x: 0,
+            y: 0,
+            logical_position: None,
+            logical_size: None,
+            mode_width: 0,
+            mode_height: 0,
+            refresh_mhz: 0,
+            scale: 1,
+            transform: wl_output::Transform::Normal,
+        }
+    }
+
+    /// Mode size rotated by the transform, in physical pixels
+    pub fn physical_size(&self) -> (u32, u32) {
+        upright_size(self.transform, self.mode_width.max(0) as u32, self.mode_height.max(0) as u32)
+    }
+
+    /// Describe the output as a monitor; the first output is reported as primary
+    ///
+    /// Bounds are the xdg-output logical geometry, which carries fractional
+    /// scales. Without xdg-output they are the physical size divided by the
+    /// integer scale. The scale factor is the physical over the logical width.
+    pub fn to_monitor(&self, index: usize) -> Monitor {
+        use wl_output::Transform;
+
+        let orientation = match self.transform {
+            Transform::_90 | Transform::Flipped90 => MonitorOrientation::Portrait,
+            Transform::_180 | Transform::Flipped180 => MonitorOrientation::LandscapeFlipped,
+            Transform::_270 | Transform::Flipped270 => MonitorOrientation::PortraitFlipped,
+            _ => MonitorOrientation::Landscape,
+        };
+        let (width, height) = self.physical_size();
+        let (x, y) = self.logical_position.unwrap_or((self.x, self.y));
+        let bounds = match self.logical_size {
+            Some((logical_width, logical_height)) if logical_width > 0 && logical_height > 0 => {
+                MonitorBounds::new(x, y, logical_width as u32, logical_height as u32)
+            }
+            _ => {
+                let scale = self.scale.max(1) as u32;
+                MonitorBounds::new(x, y, width / scale, height / scale)
+            }
+        };
+        let scale_factor = if width > 0 && bounds.width > 0 {
+            width as f32 / bounds.width as f32
+        } else {
+            self.scale.max(1) as f32
+        };
+
+        let name = self.description.clone().unwrap_or_else(|| {
+            format!("{} {}", self.make, self.model).trim().to_string()
+        });
+
+        Monitor {
+            id: self.name.clone().unwrap_or_else(|| format!("wl-output-{}", self.global_name)),
+            index,
+            name,
+            is_primary: index == 0,
+            bounds,
+            work_area: bounds,
+            scale_factor,
+            refresh_rate: match self.refresh_mhz {
+                mhz if mhz > 0 => ((mhz + 500) / 1000) as u32,
+                _ => 60,
+            },
+            bit_depth: 32,
+            orientation,
+        }
+    }
+}
+
+/// State of the frame currently in flight
+#[derive(Debug, Default)]
+struct FrameState {
+    /// Buffer layouts offered by wlr-screencopy: format, width, height, stride
+    buffers: Vec<(wl_shm::Format, u32, u32, u32)>,
+    buffer_done: bool,
+    y_invert: bool,
+    /// Transform of the buffer contents (ext-image-copy-capture)
+    transform: Option<wl_output::Transform>,
+    damage: Vec<MonitorBounds>,
+    ready: bool,
+    failed: bool,
+}
+
+/// Buffer constraints of an ext-image-copy-capture session
+#[derive(Debug, Default)]
+struct SessionState {
+    size: Option<(u32, u32)>,
+    formats: Vec<wl_shm::Format>,
+    done: bool,
+    stopped: bool,
+}
+
+/// Event dispatch state
+struct WaylandState {
+    outputs: Vec<(wl_output::WlOutput, OutputInfo)>,
+    frame: FrameState,
+    session: SessionState,
+}
+
+/// Connection with the globals needed for capture
+struct WaylandConnection {
+    conn: Connection,
+    queue: EventQueue<WaylandState>,
+    state: WaylandState,
+    globals: GlobalList,
+}
+
+impl WaylandConnection {
+    /// Connect to `$WAYLAND_DISPLAY` and collect the current outputs
+    fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env()
+            .map_err(|e| RemoteCError::CaptureError(format!("Failed to connect to Wayland compositor: {}", e)))?;
+        let (globals, mut queue) = registry_queue_init::<WaylandState>(&conn)
+            .map_err(|e| RemoteCError::CaptureError(format!("Failed to read Wayland globals: {}", e)))?;
+        let qh = queue.handle();
+
+        let mut state = WaylandState {
+            outputs: Vec::new(),
+            frame: FrameState::default(),
+            session: SessionState::default(),
+        };
+        for global in globals.contents().clone_list() {
+            if global.interface == wl_output::WlOutput::interface().name {
+                let output = globals.registry().bind::<wl_output::WlOutput, _, _>(
+                    global.name,
+                    global.version.min(4),
+                    &qh,
+                    global.name,
+                );
+                state.outputs.push((output, OutputInfo::new(global.name)));
+            }
+        }
+
+        // Logical geometry of fractionally scaled outputs
+        let xdg_manager = globals.bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1, _, _>(&qh, 1..=3, ()).ok();
+        let xdg_outputs: Vec<zxdg_output_v1::ZxdgOutputV1> = match &xdg_manager {
+            Some(manager) => state.outputs.iter()
+                .map(|(output, info)| manager.get_xdg_output(output, &qh, info.global_name))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // Receive the initial geometry, mode and scale of every output
+        queue.roundtrip(&mut state).map_err(dispatch_error)?;
+        for xdg_output in xdg_outputs {
+            xdg_output.destroy();
+        }
+        if let Some(manager) = xdg_manager {
+            manager.destroy();
+        }
+
+        Ok(Self { conn, queue, state, globals })
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        self.state.outputs.iter().enumerate().map(|(i, (_, info))| info.to_monitor(i)).collect()
+    }
+
+    fn bind<I>(&self, version: u32) -> Option<I>
+    where
+        I: Proxy + 'static,
+        WaylandState: Dispatch<I, ()>,
+    {
+        self.globals.bind(&self.queue.handle(), 1..=version, ()).ok()
+    }
+
+    /// Read and dispatch available events without blocking
+    fn poll(&mut self) -> Result<()> {
+        match self.conn.flush() {
+            Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            result => result.map_err(|e| RemoteCError::CaptureError(format!("Wayland flush failed: {}", e)))?,
+        }
+
+        self.queue.dispatch_pending(&mut self.state).map_err(dispatch_error)?;
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(RemoteCError::CaptureError(format!("Wayland read failed: {}", e))),
+            }
+        }
+        self.queue.dispatch_pending(&mut self.state).map_err(dispatch_error)?;
+        Ok(())
+    }
+}
+
+fn dispatch_error(e: wayland_client::DispatchError) -> RemoteCError {
+    RemoteCError::CaptureError(format!("Wayland dispatch failed: {}", e))
+}
+
+/// Enumerate outputs through `wl_output`
+pub fn enumerate_monitors_wayland() -> Result<Vec<Monitor>> {
+    let connection = WaylandConnection::connect()?;
+    let monitors = connection.monitors();
+    if monitors.is_empty() {
+        return Err(RemoteCError::CaptureError("Compositor reported no outputs".to_string()));
+    }
+    Ok(monitors)
+}
+
+/// Shared memory buffer the compositor copies frames into
+struct ShmBuffer {
+    pool: wl_shm_pool::WlShmPool,
+    buffer: wl_buffer::WlBuffer,
+    // Kept open for the lifetime of the pool
+    _fd: OwnedFd,
+    map: *mut u8,
+    len: usize,
+    format: wl_shm::Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+impl ShmBuffer {
+    fn new(
+        shm: &wl_shm::WlShm,
+        qh: &QueueHandle<WaylandState>,
+        format: wl_shm::Format,
+        width: u32,
+        height: u32,
+        stride: u32,
+    ) -> Result<Self> {
+        let len = stride as usize * height as usize;
+        if len == 0 {
+            return Err(RemoteCError::CaptureError("Empty capture buffer".to_string()));
+        }
+
+        unsafe {
+            let fd = libc::memfd_create(c"remotec-capture".as_ptr(), libc::MFD_CLOEXEC);
+            if fd < 0 {
+                return Err(RemoteCError::IoError(std::io::Error::last_os_error()));
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+            if libc::ftruncate(std::os::fd::AsRawFd::as_raw_fd(&fd), len as libc::off_t) < 0 {
+                return Err(RemoteCError::IoError(std::io::Error::last_os_error()));
+            }
+
+            let map = libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                std::os::fd::AsRawFd::as_raw_fd(&fd),
+                0,
+            );
+            if map == libc::MAP_FAILED {
+                return Err(RemoteCError::IoError(std::io::Error::last_os_error()));
+            }
+
+            let pool = shm.create_pool(fd.as_fd(), len as i32, qh, ());
+            let buffer = pool.create_buffer(0, width as i32, height as i32, stride as i32, format, qh, ());
+
+            Ok(Self {
+                pool,
+                buffer,
+                _fd: fd,
+                map: map.cast(),
+                len,
+                format,
+                width,
+                height,
+                stride,
+            })
+        }
+    }
+
+    fn matches(&self, format: wl_shm::Format, width: u32, height: u32, stride: u32) -> bool {
+        (self.format, self.width, self.height, self.stride) == (format, width, height, stride)
+    }
+
+    fn data(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.map, self.len) }
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+        self.pool.destroy();
+        unsafe {
+            libc::munmap(self.map.cast(), self.len);
+        }
+    }
+}
+
+/// Capture protocol negotiated with the compositor
+#[derive(Clone)]
+enum Protocol {
+    Ext {
+        // Kept alive for the session
+        _source: ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        session: ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+    },
+    Wlr {
+        manager: zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+    },
+}
+
+/// Frame object of the capture in flight
+enum PendingFrame {
+    Ext(ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1),
+    Wlr {
+        frame: zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1,
+        copied: bool,
+    },
+}
+
+impl PendingFrame {
+    fn destroy(self) {
+        match self {
+            PendingFrame::Ext(frame) => frame.destroy(),
+            PendingFrame::Wlr { frame, .. } => frame.destroy(),
+        }
+    }
+}
+
+/// Captures one Wayland output
+pub struct WaylandCapture {
+    // Declared before the connection so they are destroyed first
+    pending: Option<PendingFrame>,
+    buffer: Option<ShmBuffer>,
+    protocol: Protocol,
+    shm: wl_shm::WlShm,
+    connection: WaylandConnection,
+    output: wl_output::WlOutput,
+    /// Output transform, used when the frame does not report its own
+    transform: wl_output::Transform,
+    /// Captured area of the upright output in physical pixels
+    region: MonitorBounds,
+    overlay_cursor: bool,
+    /// Whether the next frame must be delivered whole regardless of damage
+    full_frame_pending: bool,
+    pool: BufferPool,
+}
+
+impl WaylandCapture {
+    /// Start capturing the output selected by `config`
+    ///
+    /// Only a single output can be captured; `config.region` is relative to
+    /// that output, in compositor space like the monitor bounds.
+    pub fn new(config: &CaptureConfig) -> Result<Self> {
+        let connection = WaylandConnection::connect()?;
+        let monitors = connection.monitors();
+        let desktop = VirtualDesktop::new(monitors.clone())?;
+
+        let indices = config.mode.get_monitor_indices(&desktop);
+        let index = match indices.as_slice() {
+            [index] => *index,
+            _ => {
+                return Err(RemoteCError::CaptureError(
+                    "Wayland capture supports one output at a time".to_string(),
+                ))
+            }
+        };
+        let monitor = desktop.get_monitor(index)
+            .ok_or_else(|| RemoteCError::CaptureError(format!("Invalid monitor index: {}", index)))?;
+        let bounds = super::capture_bounds(config, monitors.clone())?;
+        let (output, info) = connection.state.outputs[index].clone();
+        // Buffers are in physical pixels
+        let region = physical_region(&bounds, &monitor.bounds, info.physical_size());
+
+        let shm: wl_shm::WlShm = connection.bind(1)
+            .ok_or_else(|| RemoteCError::CaptureError("Compositor does not offer wl_shm".to_string()))?;
+        // A separate cursor channel is not available on Wayland, so separate
+        // mode only leaves the cursor out of the frames
+        let overlay_cursor = config.capture_cursor && config.cursor_mode == CursorMode::Embedded;
+        let protocol = Self::negotiate(&connection, &output, overlay_cursor)?;
+
+        let mut capture = Self {
+            pending: None,
+            buffer: None,
+            protocol,
+            shm,
+            connection,
+            output,
+            transform: info.transform,
+            region,
+            overlay_cursor,
+            full_frame_pending: true,
+            pool: BufferPool::default(),
+        };
+
+        if let Protocol::Ext { .. } = capture.protocol {
+            // Buffer constraints arrive right after the session is created
+            capture.connection.queue.roundtrip(&mut capture.connection.state).map_err(dispatch_error)?;
+            if !capture.connection.state.session.done {
+                return Err(RemoteCError::CaptureError("Capture session sent no buffer constraints".to_string()));
+            }
+        }
+
+        Ok(capture)
+    }
+
+    fn negotiate(connection: &WaylandConnection, output: &wl_output::WlOutput, overlay_cursor: bool) -> Result<Protocol> {
+        let qh = connection.queue.handle();
+
+        let ext_manager: Option<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1> = connection.bind(1);
+        let ext_sources: Option<ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1> =
+            connection.bind(1);
+        if let (Some(manager), Some(sources)) = (ext_manager, ext_sources) {
+            let options = if overlay_cursor {
+                ext_image_copy_capture_manager_v1::Options::PaintCursors
+            } else {
+                ext_image_copy_capture_manager_v1::Options::empty()
+            };
+            let source = sources.create_source(output, &qh, ());
+            let session = manager.create_session(&source, options, &qh, ());
+            log::info!("Capturing Wayland output with ext-image-copy-capture");
+            return Ok(Protocol::Ext { _source: source, session });
+        }
+
+        if let Some(manager) = connection.bind::<zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1>(3) {
+            log::info!("Capturing Wayland output with wlr-screencopy v{}", manager.version());
+            return Ok(Protocol::Wlr { manager });
+        }
+
+        Err(RemoteCError::UnsupportedPlatform(
+            "Compositor supports neither ext-image-copy-capture nor wlr-screencopy".to_string(),
+        ))
+    }
+
+    /// Captured area of the upright output in physical pixels
+    pub fn region(&self) -> MonitorBounds {
+        self.region
+    }
+
+    /// Poll for a captured frame
+    ///
+    /// Returns `None` while the compositor has not delivered new content.
+    pub fn capture_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        if self.pending.is_none() {
+            self.request_frame()?;
+        }
+        self.connection.poll()?;
+
+        if self.connection.state.session.stopped {
+            return Err(RemoteCError::CaptureError("Capture session stopped by the compositor".to_string()));
+        }
+        if let Some(PendingFrame::Wlr { copied: false, .. }) = self.pending {
+            self.copy_wlr_frame()?;
+        }
+
+        let frame = std::mem::take(&mut self.connection.state.frame);
+        if frame.failed {
+            if let Some(pending) = self.pending.take() {
+                pending.destroy();
+            }
+            self.full_frame_pending = true;
+            return Err(RemoteCError::CaptureError("Compositor failed to capture the output".to_string()));
+        }
+        if !frame.ready {
+            self.connection.state.frame = frame;
+            return Ok(None);
+        }
+
+        // wlr-screencopy v1 has no damage reporting
+        let tracks_damage = !matches!(&self.pending, Some(PendingFrame::Wlr { frame, .. }) if frame.version() < 2);
+        if let Some(pending) = self.pending.take() {
+            pending.destroy();
+        }
+        let buffer = self.buffer.as_ref()
+            .ok_or_else(|| RemoteCError::CaptureError("No capture buffer".to_string()))?;
+        let transform = frame.transform.unwrap_or(self.transform);
+        let crop = buffer_rect(transform, &self.region, buffer.width, buffer.height);
+
+        let dirty_rects = if !tracks_damage {
+            None
+        } else if std::mem::take(&mut self.full_frame_pending) {
+            Some(vec![CaptureRegion { x: 0, y: 0, width: self.region.width, height: self.region.height }])
+        } else {
+            let regions = frame_damage(&frame.damage, &crop, transform, buffer.height, frame.y_invert);
+            if regions.is_empty() {
+                // Damage outside the captured region
+                self.request_frame()?;
+                return Ok(None);
+            }
+            Some(regions)
+        };
+
+        let size = self.region.width as usize * self.region.height as usize * 4;
+        let mut data = self.pool.take(size);
+        if transform == wl_output::Transform::Normal {
+            shm_to_bgra(buffer.data(), buffer.width, buffer.stride, buffer.format, frame.y_invert, &crop, &mut data)?;
+        } else {
+            let mut cropped = Vec::with_capacity(size);
+            shm_to_bgra(buffer.data(), buffer.width, buffer.stride, buffer.format, frame.y_invert, &crop, &mut cropped)?;
+            transform_bgra(&cropped, crop.width, crop.height, transform, &mut data);
+        }
+        let screen_frame = ScreenFrame {
+            width: self.region.width,
+            height: self.region.height,
+            data: data.freeze(),
+            timestamp: std::time::Instant::now(),
+            dirty_rects,
+            keyframe: false,
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
+        };
+
+        // Keep the next capture in flight so it completes on the next change
+        self.request_frame()?;
+        Ok(Some(screen_frame))
+    }
+
+    fn request_frame(&mut self) -> Result<()> {
+        let qh = self.connection.queue.handle();
+        self.connection.state.frame = FrameState::default();
+
+        match self.protocol.clone() {
+            Protocol::Ext { session, .. } => {
+                let constraints = &self.connection.state.session;
+                let (width, height) = constraints.size
+                    .ok_or_else(|| RemoteCError::CaptureError("Capture session has no buffer size".to_string()))?;
+                let format = pick_format(&constraints.formats)
+                    .ok_or_else(|| RemoteCError::CaptureError("No supported shm format offered".to_string()))?;
+                let new_buffer = self.ensure_buffer(format, width, height, width * 4)?;
+
+                let frame = session.create_frame(&qh, ());
+                frame.attach_buffer(&self.buffer.as_ref().unwrap().buffer);
+                if new_buffer || self.full_frame_pending {
+                    frame.damage_buffer(0, 0, width as i32, height as i32);
+                }
+                frame.capture();
+                self.pending = Some(PendingFrame::Ext(frame));
+            }
+            Protocol::Wlr { manager } => {
+                let frame = manager.capture_output(i32::from(self.overlay_cursor), &self.output, &qh, ());
+                self.pending = Some(PendingFrame::Wlr { frame, copied: false });
+            }
+        }
+        Ok(())
+    }
+
+    /// Attach a buffer once wlr-screencopy has described the frame
+    fn copy_wlr_frame(&mut self) -> Result<()> {
+        let Some(PendingFrame::Wlr { frame, .. }) = self.pending.as_ref() else {
+            return Ok(());
+        };
+        let state = &self.connection.state.frame;
+        // Version 3 lists every buffer type before buffer_done; earlier
+        // versions send a single buffer event
+        if !state.buffer_done && (frame.version() >= 3 || state.buffers.is_empty()) {
+            return Ok(());
+        }
+
+        let formats: Vec<wl_shm::Format> = state.buffers.iter().map(|b| b.0).collect();
+        let format = pick_format(&formats)
+            .ok_or_else(|| RemoteCError::CaptureError("No supported shm format offered".to_string()))?;
+        let (_, width, height, stride) = *state.buffers.iter().find(|b| b.0 == format).unwrap();
+        let frame = frame.clone();
+
+        self.ensure_buffer(format, width, height, stride)?;
+        let buffer = &self.buffer.as_ref().unwrap().buffer;
+        if frame.version() >= 2 {
+            frame.copy_with_damage(buffer);
+        } else {
+            frame.copy(buffer);
+        }
+
+        self.pending = Some(PendingFrame::Wlr { frame, copied: true });
+        Ok(())
+    }
+
+    /// Reuse the current buffer if it fits; returns whether a new one was created
+    fn ensure_buffer(&mut self, format: wl_shm::Format, width: u32, height: u32, stride: u32) -> Result<bool> {
+        if self.buffer.as_ref().is_some_and(|b| b.matches(format, width, height, stride)) {
+            return Ok(false);
+        }
+
+        let (upright_width, upright_height) = upright_size(self.transform, width, height);
+        if self.region.x < 0
+            || self.region.y < 0
+            || self.region.x as u32 + self.region.width > upright_width
+            || self.region.y as u32 + self.region.height > upright_height
+        {
+            return Err(RemoteCError::CaptureError("Capture region exceeds the output buffer".to_string()));
+        }
+
+        let qh = self.connection.queue.handle();
+        self.buffer = Some(ShmBuffer::new(&self.shm, &qh, format, width, height, stride)?);
+        self.full_frame_pending = true;
+        Ok(true)
+    }
+}
+
+// SAFETY: the shared memory mapping is owned by the capture and only read
+// through `&mut self` methods; the Wayland connection and proxies are
+// thread-safe.
+unsafe impl Send for WaylandCapture {}
+unsafe impl Sync for WaylandCapture {}
+
+/// Map an area in compositor space onto the physical pixels of an output
+///
+/// The scale is derived per axis from the physical and logical output size,
+/// so fractional scales map edges to the nearest pixel.
+fn physical_region(area: &MonitorBounds, logical: &MonitorBounds, physical: (u32, u32)) -> MonitorBounds {
+    let map = |offset: i32, logical_size: u32, physical_size: u32| -> u32 {
+        let scaled = offset as f64 * physical_size as f64 / logical_size.max(1) as f64;
+        scaled.round().clamp(0.0, physical_size as f64) as u32
+    };
+
+    let left = map(area.x - logical.x, logical.width, physical.0);
+    let top = map(area.y - logical.y, logical.height, physical.1);
+    let right = map(area.x - logical.x + area.width as i32, logical.width, physical.0);
+    let bottom = map(area.y - logical.y + area.height as i32, logical.height, physical.1);
+    MonitorBounds::new(left as i32, top as i32, right - left, bottom - top)
+}
+
+/// Preferred shm format among the ones offered, all 32-bit
+fn pick_format(offered: &[wl_shm::Format]) -> Option<wl_shm::Format> {
+    use wl_shm::Format;
+
+    [Format::Xrgb8888, Format::Argb8888, Format::Xbgr8888, Format::Abgr8888]
+        .into_iter()
+        .find(|format| offered.contains(format))
+}
+
+/// Append the `crop` area of a 32-bit shm buffer to `out` as packed opaque BGRA
+fn shm_to_bgra(
+    data: &[u8],
+    width: u32,
+    stride: u32,
+    format: wl_shm::Format,
+    y_invert: bool,
+    crop: &MonitorBounds,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let swap_rb = match format {
+        // Little-endian ARGB is stored as B, G, R, A
+        wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888 => false,
+        wl_shm::Format::Xbgr8888 | wl_shm::Format::Abgr8888 => true,
+        other => {
+            return Err(RemoteCError::CaptureError(format!("Unsupported shm format {:?}", other)));
+        }
+    };
+
+    let height = data.len() / stride as usize;
+    let row_start = crop.x as usize * 4;
+    let row_len = crop.width as usize * 4;
+    if crop.x as u32 + crop.width > width || crop.y as usize + crop.height as usize > height {
+        return Err(RemoteCError::CaptureError("Capture region exceeds the output buffer".to_string()));
+    }
+
+    let start = out.len();
+    out.reserve(row_len * crop.height as usize);
+    for y in crop.y as usize..crop.y as usize + crop.height as usize {
+        let src_y = if y_invert { height - 1 - y } else { y };
+        let offset = src_y * stride as usize + row_start;
+        out.extend_from_slice(&data[offset..offset + row_len]);
+    }
+    for px in out[start..].chunks_exact_mut(4) {
+        if swap_rb {
+            px.swap(0, 2);
+        }
+        px[3] = 255;
+    }
+    Ok(())
+}
+
+/// Size of a `width` x `height` buffer once `transform` is applied
+fn upright_size(transform: wl_output::Transform, width: u32, height: u32) -> (u32, u32) {
+    use wl_output::Transform;
+
+    match transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => (height, width),
+        _ => (width, height),
+    }
+}
+
+/// Transform undoing `transform`
+fn inverse_transform(transform: wl_output::Transform) -> wl_output::Transform {
+    use wl_output::Transform;
+
+    match transform {
+        Transform::_90 => Transform::_270,
+        Transform::_270 => Transform::_90,
+        // Flips and 180 degree rotations undo themselves
+        other => other,
+    }
+}
+
+/// Pixel of a `width` x `height` buffer shown at `(x, y)` of the upright
+/// image, i.e. after the buffer is flipped and rotated counter-clockwise
+/// according to `transform`
+fn buffer_point(transform: wl_output::Transform, x: i32, y: i32, width: u32, height: u32) -> (i32, i32) {
+    use wl_output::Transform;
+
+    let (w, h) = (width as i32, height as i32);
+    let (x, y) = match transform {
+        Transform::_90 | Transform::Flipped90 => (w - 1 - y, x),
+        Transform::_180 | Transform::Flipped180 => (w - 1 - x, h - 1 - y),
+        Transform::_270 | Transform::Flipped270 => (y, h - 1 - x),
+        _ => (x, y),
+    };
+    match transform {
+        Transform::Flipped | Transform::Flipped90 | Transform::Flipped180 | Transform::Flipped270 => (w - 1 - x, y),
+        _ => (x, y),
+    }
+}
+
+/// Area of a `width` x `height` buffer shown in `rect` of the upright image
+fn buffer_rect(transform: wl_output::Transform, rect: &MonitorBounds, width: u32, height: u32) -> MonitorBounds {
+    if rect.width == 0 || rect.height == 0 {
+        return MonitorBounds::new(rect.x, rect.y, 0, 0);
+    }
+    let (x0, y0) = buffer_point(transform, rect.x, rect.y, width, height);
+    let (x1, y1) = buffer_point(
+        transform,
+        rect.x + rect.width as i32 - 1,
+        rect.y + rect.height as i32 - 1,
+        width,
+        height,
+    );
+    MonitorBounds::new(
+        x0.min(x1),
+        y0.min(y1),
+        x0.abs_diff(x1) + 1,
+        y0.abs_diff(y1) + 1,
+    )
+}
+
+/// Append the upright image of a packed BGRA buffer to `out`
+fn transform_bgra(src: &[u8], width: u32, height: u32, transform: wl_output::Transform, out: &mut Vec<u8>) {
+    let (upright_width, upright_height) = upright_size(transform, width, height);
+    out.reserve(src.len());
+    for y in 0..upright_height as i32 {
+        for x in 0..upright_width as i32 {
+            let (sx, sy) = buffer_point(transform, x, y, width, height);
+            let offset = (sy as usize * width as usize + sx as usize) * 4;
+            out.extend_from_slice(&src[offset..offset + 4]);
+        }
+    }
+}
+
+/// Map buffer damage to regions of the upright frame cut from `crop`
+fn frame_damage(
+    damage: &[MonitorBounds],
+    crop: &MonitorBounds,
+    transform: wl_output::Transform,
+    buffer_height: u32,
+    y_invert: bool,
+) -> Vec<CaptureRegion> {
+    let inverse = inverse_transform(transform);
+    let (upright_width, upright_height) = upright_size(transform, crop.width, crop.height);
+
+    damage
+        .iter()
+        .map(|rect| {
+            if y_invert {
+                let y = buffer_height as i32 - rect.y - rect.height as i32;
+                MonitorBounds::new(rect.x, y, rect.width, rect.height)
+            } else {
+                *rect
+            }
+        })
+        .filter_map(|rect| rect.intersection(crop))
+        .map(|rect| {
+            let relative = MonitorBounds::new(rect.x - crop.x, rect.y - crop.y, rect.width, rect.height);
+            let upright = buffer_rect(inverse, &relative, upright_width, upright_height);
+            CaptureRegion {
+                x: upright.x,
+                y: upright.y,
+                width: upright.width,
+                height: upright.height,
+            }
+        })
+        .collect()
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for WaylandState {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // Output hot-plug is not tracked during a capture
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        global_name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some((_, info)) = state.outputs.iter_mut().find(|(_, info)| info.global_name == *global_name) else {
+            return;
+        };
+
+        match event {
+            wl_output::Event::Geometry { x, y, make, model, transform, .. } => {
+                info.x = x;
+                info.y = y;
+                info.make = make;
+                info.model = model;
+                if let WEnum::Value(transform) = transform {
+                    info.transform = transform;
+                }
+            }
+            wl_output::Event::Mode { flags, width, height, refresh } => {
+                if matches!(flags, WEnum::Value(flags) if flags.contains(wl_output::Mode::Current)) {
+                    info.mode_width = width;
+                    info.mode_height = height;
+                    info.refresh_mhz = refresh;
+                }
+            }
+            wl_output::Event::Scale { factor } => info.scale = factor,
+            wl_output::Event::Name { name } => info.name = Some(name),
+            wl_output::Event::Description { description } => info.description = Some(description),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, u32> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &zxdg_output_v1::ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        global_name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some((_, info)) = state.outputs.iter_mut().find(|(_, info)| info.global_name == *global_name) else {
+            return;
+        };
+
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => info.logical_position = Some((x, y)),
+            zxdg_output_v1::Event::LogicalSize { width, height } => info.logical_size = Some((width, height)),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::Event;
+
+        let frame = &mut state.frame;
+        match event {
+            Event::Buffer { format: WEnum::Value(format), width, height, stride } => {
+                frame.buffers.push((format, width, height, stride));
+            }
+            Event::Flags { flags: WEnum::Value(flags) } => {
+                frame.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
+            Event::Damage { x, y, width, height } => {
+                frame.damage.push(MonitorBounds::new(x as i32, y as i32, width, height));
+            }
+            Event::BufferDone => frame.buffer_done = true,
+            Event::Ready { .. } => frame.ready = true,
+            Event::Failed => frame.failed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_session_v1::Event;
+
+        let session = &mut state.session;
+        match event {
+            Event::BufferSize { width, height } => {
+                // A new batch of constraints replaces the previous one
+                if session.done {
+                    session.formats.clear();
+                    session.done = false;
+                }
+                session.size = Some((width, height));
+            }
+            Event::ShmFormat { format: WEnum::Value(format) } => session.formats.push(format),
+            Event::Done => session.done = true,
+            Event::Stopped => session.stopped = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _: &ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_frame_v1::Event;
+
+        let frame = &mut state.frame;
+        match event {
+            Event::Transform { transform: WEnum::Value(transform) } => frame.transform = Some(transform),
+            Event::Damage { x, y, width, height } => {
+                frame.damage.push(MonitorBounds::new(x, y, width.max(0) as u32, height.max(0) as u32));
+            }
+            Event::Ready => frame.ready = true,
+            Event::Failed { .. } => frame.failed = true,
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(WaylandState: ignore wl_shm::WlShm);
+delegate_noop!(WaylandState: ignore wl_buffer::WlBuffer);
+delegate_noop!(WaylandState: wl_shm_pool::WlShmPool);
+delegate_noop!(WaylandState: zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1);
+delegate_noop!(WaylandState: zxdg_output_manager_v1::ZxdgOutputManagerV1);
+delegate_noop!(WaylandState: ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1);
+delegate_noop!(WaylandState: ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1);
+delegate_noop!(WaylandState: ext_image_capture_source_v1::ExtImageCaptureSourceV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::CaptureMode;
+
+    #[test]
+    fn test_output_to_monitor() {
+        let mut info = OutputInfo::new(42);
+        info.x = 1920;
+        info.mode_width = 2560;
+        info.mode_height = 1440;
+        info.refresh_mhz = 143_856;
+        info.scale = 2;
+        info.transform = wl_output::Transform::_90;
+        info.make = "ACME".to_string();
+        info.model = "Display".to_string();
+
+        // Rotated and scaled to compositor space like the position
+        let monitor = info.to_monitor(1);
+        assert_eq!(monitor.bounds, MonitorBounds::new(1920, 0, 720, 1280));
+        assert_eq!(monitor.orientation, MonitorOrientation::Portrait);
+        assert_eq!(monitor.refresh_rate, 144);
+        assert_eq!(monitor.scale_factor, 2.0);
+        assert_eq!(monitor.id, "wl-output-42");
+        assert_eq!(monitor.name, "ACME Display");
+        assert!(!monitor.is_primary);
+    }
+
+    #[test]
+    fn test_fractional_scale_uses_logical_geometry() {
+        // 2880x1800 panel at 1.5x, rounded up to a buffer scale of 2
+        let mut info = OutputInfo::new(7);
+        info.mode_width = 2880;
+        info.mode_height = 1800;
+        info.scale = 2;
+        info.logical_position = Some((0, 0));
+        info.logical_size = Some((1920, 1200));
+
+        let monitor = info.to_monitor(0);
+        assert_eq!(monitor.bounds, MonitorBounds::new(0, 0, 1920, 1200));
+        assert_eq!(monitor.scale_factor, 1.5);
+
+        // The whole output maps to the whole buffer
+        assert_eq!(
+            physical_region(&monitor.bounds, &monitor.bounds, info.physical_size()),
+            MonitorBounds::new(0, 0, 2880, 1800)
+        );
+        let area = MonitorBounds::new(101, 50, 640, 480);
+        assert_eq!(
+            physical_region(&area, &monitor.bounds, info.physical_size()),
+            MonitorBounds::new(152, 75, 960, 720)
+        );
+    }
+
+    #[test]
+    fn test_shm_to_bgra_crops_and_swizzles() {
+        // 3x2 XBGR buffer with a 16-byte stride
+        let mut data = vec![0u8; 16 * 2];
+        for y in 0..2 {
+            for x in 0..3 {
+                let offset = y * 16 + x * 4;
+                data[offset..offset + 4].copy_from_slice(&[10 * (x as u8 + 1), 0, y as u8, 0]);
+            }
+        }
+
+        let crop = MonitorBounds::new(1, 0, 2, 2);
+        let mut out = Vec::new();
+        shm_to_bgra(&data, 3, 16, wl_shm::Format::Xbgr8888, false, &crop, &mut out).unwrap();
+        assert_eq!(out, vec![0, 0, 20, 255, 0, 0, 30, 255, 1, 0, 20, 255, 1, 0, 30, 255]);
+
+        // Y-inverted buffers are flipped back
+        let mut flipped = Vec::new();
+        shm_to_bgra(&data, 3, 16, wl_shm::Format::Xrgb8888, true, &crop, &mut flipped).unwrap();
+        assert_eq!(&flipped[..4], &[20, 0, 1, 255]);
+
+        assert!(shm_to_bgra(&data, 3, 16, wl_shm::Format::Rgb565, false, &crop, &mut Vec::new()).is_err());
+        assert_eq!(pick_format(&[wl_shm::Format::Abgr8888, wl_shm::Format::Argb8888]), Some(wl_shm::Format::Argb8888));
+    }
+
+    #[test]
+    fn test_frame_damage() {
+        let crop = MonitorBounds::new(100, 100, 200, 200);
+        let damage = [MonitorBounds::new(50, 150, 100, 10), MonitorBounds::new(0, 0, 10, 10)];
+
+        let regions = frame_damage(&damage, &crop, wl_output::Transform::Normal, 1080, false);
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].x, regions[0].y, regions[0].width, regions[0].height), (0, 50, 50, 10));
+
+        // With y-invert, a rectangle at the bottom of the buffer is at the top of the frame
+        let inverted = frame_damage(&[MonitorBounds::new(100, 970, 10, 10)], &crop, wl_output::Transform::Normal, 1080, true);
+        assert_eq!((inverted[0].x, inverted[0].y), (0, 0));
+
+        // On a rotated output the damage is rotated with the frame
+        let rotated = frame_damage(&damage, &crop, wl_output::Transform::_90, 1080, false);
+        assert_eq!((rotated[0].x, rotated[0].y, rotated[0].width, rotated[0].height), (50, 150, 10, 50));
+    }
+
+    #[test]
+    fn test_rotated_output_crop_and_frame() {
+        use wl_output::Transform;
+
+        // 3x2 buffer, pixel value = index
+        let buffer: Vec<u8> = (0..6u8).flat_map(|i| [i, i, i, 255]).collect();
+        let pixels = |data: &[u8]| data.chunks_exact(4).map(|px| px[0]).collect::<Vec<_>>();
+
+        // Rotating counter-clockwise moves the right column to the top row
+        let mut out = Vec::new();
+        transform_bgra(&buffer, 3, 2, Transform::_90, &mut out);
+        assert_eq!(pixels(&out), [2, 5, 1, 4, 0, 3]);
+
+        for (transform, expected) in [
+            (Transform::_180, vec![5, 4, 3, 2, 1, 0]),
+            (Transform::_270, vec![3, 0, 4, 1, 5, 2]),
+            (Transform::Flipped, vec![2, 1, 0, 5, 4, 3]),
+            (Transform::Flipped90, vec![0, 3, 1, 4, 2, 5]),
+        ] {
+            let mut out = Vec::new();
+            transform_bgra(&buffer, 3, 2, transform, &mut out);
+            assert_eq!(pixels(&out), expected, "{:?}", transform);
+        }
+
+        // A portrait region maps to a landscape area of the buffer
+        let region = MonitorBounds::new(0, 1, 2, 2);
+        let crop = buffer_rect(Transform::_90, &region, 3, 2);
+        assert_eq!(crop, MonitorBounds::new(0, 0, 2, 2));
+        let mut cropped = Vec::new();
+        shm_to_bgra(&buffer, 3, 12, wl_shm::Format::Xrgb8888, false, &crop, &mut cropped).unwrap();
+        let mut upright = Vec::new();
+        transform_bgra(&cropped, crop.width, crop.height, Transform::_90, &mut upright);
+        assert_eq!(pixels(&upright), [1, 4, 0, 3]);
+        assert_eq!(upright_size(Transform::Flipped270, 3, 2), (2, 3));
+    }
+
+    #[test]
+    fn test_capture_live_output() {
+        // Runs only inside a Wayland session, e.g. headless sway
+        if !is_wayland_session() {
+            return;
+        }
+
+        let monitors = enumerate_monitors_wayland().unwrap();
+        assert!(!monitors.is_empty());
+
+        let config = CaptureConfig {
+            mode: CaptureMode::PrimaryMonitor,
+            ..Default::default()
+        };
+        let mut capture = match WaylandCapture::new(&config) {
+            Ok(capture) => capture,
+            // Compositors without a capture protocol (e.g. GNOME) are not testable here
+            Err(RemoteCError::UnsupportedPlatform(_)) => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let frame = loop {
+            if let Some(frame) = capture.capture_frame().unwrap() {
+                break frame;
+            }
+            assert!(std::time::Instant::now() < deadline, "no frame within 5s");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        assert_eq!(frame.data.len(), (frame.width * frame.height * 4) as usize);
+        assert!(frame.dirty_rects.map_or(true, |rects| rects.len() == 1));
+    }
+}
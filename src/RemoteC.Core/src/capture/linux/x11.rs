@@ -0,0 +1,476 @@
+//! X11 screen capture using the MIT-SHM extension
+//!
+//! Frames are read from the root window into a shared memory segment with
+//! `XShmGetImage`. When the extension is missing (e.g. remote X servers) or
+//! fails, capture falls back to plain `XGetImage`.
+
+use super::damage::{self, DamageTracker};
+use crate::capture::monitor::{Monitor, MonitorBounds, MonitorOrientation};
+use crate::capture::{BufferPool, CaptureRegion, FrameBuffer, FrameFormat, ScreenFrame};
+use crate::{RemoteCError, Result};
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use x11::xlib;
+use x11::xshm;
+
+/// Last X error code seen while an error trap is installed
+static X_ERROR_CODE: AtomicI32 = AtomicI32::new(0);
+
+/// Serializes error traps, the Xlib error handler is process-global
+static X_ERROR_TRAP: Mutex<()> = Mutex::new(());
+
+unsafe extern "C" fn record_x_error(_display: *mut xlib::Display, event: *mut xlib::XErrorEvent) -> c_int {
+    X_ERROR_CODE.store(i32::from((*event).error_code), Ordering::SeqCst);
+    0
+}
+
+/// Connection to an X server
+pub struct X11Display {
+    display: *mut xlib::Display,
+}
+
+impl X11Display {
+    /// Open the display named by `name`, or `$DISPLAY` when `None`
+    pub fn open(name: Option<&str>) -> Result<Self> {
+        let name = name
+            .map(|n| CString::new(n).map_err(|_| RemoteCError::CaptureError("Invalid display name".to_string())))
+            .transpose()?;
+
+        let display = unsafe { xlib::XOpenDisplay(name.as_ref().map_or(ptr::null(), |n| n.as_ptr())) };
+        if display.is_null() {
+            return Err(RemoteCError::CaptureError("Failed to open X display".to_string()));
+        }
+
+        Ok(Self { display })
+    }
+
+    /// Raw Xlib display pointer
+    pub fn raw(&self) -> *mut xlib::Display {
+        self.display
+    }
+
+    /// Root window of the default screen
+    pub fn root(&self) -> xlib::Window {
+        unsafe { xlib::XDefaultRootWindow(self.display) }
+    }
+
+    /// Size of the default screen in pixels
+    pub fn screen_size(&self) -> (u32, u32) {
+        unsafe {
+            let screen = xlib::XDefaultScreen(self.display);
+            (
+                xlib::XDisplayWidth(self.display, screen) as u32,
+                xlib::XDisplayHeight(self.display, screen) as u32,
+            )
+        }
+    }
+
+    /// Run `f` and report any X protocol error it caused
+    pub fn trap_errors<T>(&self, f: impl FnOnce() -> T) -> Result<T> {
+        let _guard = X_ERROR_TRAP.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            xlib::XSync(self.display, xlib::False);
+            X_ERROR_CODE.store(0, Ordering::SeqCst);
+            let previous = xlib::XSetErrorHandler(Some(record_x_error));
+
+            let result = f();
+            xlib::XSync(self.display, xlib::False);
+
+            xlib::XSetErrorHandler(previous);
+            match X_ERROR_CODE.swap(0, Ordering::SeqCst) {
+                0 => Ok(result),
+                code => Err(RemoteCError::CaptureError(format!("X protocol error {}", code))),
+            }
+        }
+    }
+}
+
+impl Drop for X11Display {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+/// Shared memory backed XImage
+struct ShmImage {
+    image: *mut xlib::XImage,
+    /// Boxed because Xlib keeps a pointer to it in the image
+    info: Box<xshm::XShmSegmentInfo>,
+    display: *mut xlib::Display,
+}
+
+impl ShmImage {
+    fn new(display: &X11Display, width: u32, height: u32) -> Result<Self> {
+        unsafe {
+            if xshm::XShmQueryExtension(display.raw()) == xlib::False {
+                return Err(RemoteCError::CaptureError("MIT-SHM extension not available".to_string()));
+            }
+
+            let screen = xlib::XDefaultScreen(display.raw());
+            let mut info = Box::new(xshm::XShmSegmentInfo {
+                shmseg: 0,
+                shmid: -1,
+                shmaddr: ptr::null_mut(),
+                readOnly: xlib::False,
+            });
+
+            let image = xshm::XShmCreateImage(
+                display.raw(),
+                xlib::XDefaultVisual(display.raw(), screen),
+                xlib::XDefaultDepth(display.raw(), screen) as u32,
+                xlib::ZPixmap,
+                ptr::null_mut(),
+                &mut *info,
+                width,
+                height,
+            );
+            if image.is_null() {
+                return Err(RemoteCError::CaptureError("XShmCreateImage failed".to_string()));
+            }
+
+            let size = (*image).bytes_per_line as usize * (*image).height as usize;
+            info.shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if info.shmid < 0 {
+                (*image).data = ptr::null_mut();
+                xlib::XDestroyImage(image);
+                return Err(RemoteCError::CaptureError("shmget failed".to_string()));
+            }
+
+            let addr = libc::shmat(info.shmid, ptr::null(), 0);
+            // Mark for removal now; it stays alive until both sides detach
+            libc::shmctl(info.shmid, libc::IPC_RMID, ptr::null_mut());
+            if addr as isize == -1 {
+                (*image).data = ptr::null_mut();
+                xlib::XDestroyImage(image);
+                return Err(RemoteCError::CaptureError("shmat failed".to_string()));
+            }
+            info.shmaddr = addr.cast();
+            (*image).data = info.shmaddr;
+
+            let mut shm = Self {
+                image,
+                info,
+                display: display.raw(),
+            };
+
+            let info_ptr: *mut xshm::XShmSegmentInfo = &mut *shm.info;
+            match display.trap_errors(|| xshm::XShmAttach(display.raw(), info_ptr)) {
+                Ok(attached) if attached != xlib::False => Ok(shm),
+                result => {
+                    // Nothing attached on the server side, skip XShmDetach
+                    shm.info.shmseg = 0;
+                    Err(result.err().unwrap_or_else(|| {
+                        RemoteCError::CaptureError("XShmAttach failed".to_string())
+                    }))
+                }
+            }
+        }
+    }
+
+    fn capture(&mut self, display: &X11Display, x: i32, y: i32) -> Result<&xlib::XImage> {
+        let (image, root) = (self.image, display.root());
+        let ok = display.trap_errors(|| unsafe {
+            xshm::XShmGetImage(display.raw(), root, image, x, y, xlib::XAllPlanes() as u32)
+        })?;
+        if ok == xlib::False {
+            return Err(RemoteCError::CaptureError("XShmGetImage failed".to_string()));
+        }
+        Ok(unsafe { &*self.image })
+    }
+}
+
+impl Drop for ShmImage {
+    fn drop(&mut self) {
+        unsafe {
+            if self.info.shmseg != 0 {
+                xshm::XShmDetach(self.display, &mut *self.info);
+                xlib::XSync(self.display, xlib::False);
+            }
+            // The data belongs to the shared segment, not to Xlib
+            (*self.image).data = ptr::null_mut();
+            xlib::XDestroyImage(self.image);
+            libc::shmdt(self.info.shmaddr as *const _);
+        }
+    }
+}
+
+/// Captures a rectangle of the X11 root window
+pub struct X11Capture {
+    // Declared before `display` so they are released first
+    shm: Option<ShmImage>,
+    damage: Option<DamageTracker>,
+    display: X11Display,
+    bounds: MonitorBounds,
+    /// Whether the next frame must be delivered whole regardless of damage
+    full_frame_pending: bool,
+    pool: BufferPool,
+}
+
+impl X11Capture {
+    /// Capture `bounds` (root window coordinates) from `display`
+    pub fn new(display: X11Display, bounds: MonitorBounds) -> Result<Self> {
+        let shm = match ShmImage::new(&display, bounds.width, bounds.height) {
+            Ok(shm) => Some(shm),
+            Err(e) => {
+                log::warn!("MIT-SHM unavailable, falling back to XGetImage: {}", e);
+                None
+            }
+        };
+
+        let damage = match DamageTracker::new(&display) {
+            Ok(damage) => Some(damage),
+            Err(e) => {
+                log::warn!("XDamage unavailable, dirty rectangles disabled: {}", e);
+                None
+            }
+        };
+
+        Ok(Self {
+            shm,
+            damage,
+            display,
+            bounds,
+            full_frame_pending: true,
+            pool: BufferPool::default(),
+        })
+    }
+
+    /// Whether frames are read through shared memory
+    pub fn uses_shm(&self) -> bool {
+        self.shm.is_some()
+    }
+
+    /// Captured rectangle
+    pub fn bounds(&self) -> MonitorBounds {
+        self.bounds
+    }
+
+    /// Whether frames carry dirty rectangles
+    pub fn tracks_damage(&self) -> bool {
+        self.damage.is_some()
+    }
+
+    /// Display connection used for capture
+    pub fn display(&self) -> &X11Display {
+        &self.display
+    }
+
+    /// Capture one frame
+    ///
+    /// With damage tracking, returns `None` when nothing in the captured
+    /// area changed since the previous frame.
+    pub fn capture_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        let dirty_rects = match self.damage.as_mut() {
+            Some(tracker) => {
+                // Fetch damage before grabbing so changes made during the
+                // grab are reported again with the next frame
+                let regions = damage::dirty_regions(&tracker.take(), &self.bounds);
+                if std::mem::take(&mut self.full_frame_pending) {
+                    Some(vec![CaptureRegion {
+                        x: 0,
+                        y: 0,
+                        width: self.bounds.width,
+                        height: self.bounds.height,
+                    }])
+                } else if regions.is_empty() {
+                    return Ok(None);
+                } else {
+                    Some(regions)
+                }
+            }
+            None => None,
+        };
+
+        match self.grab() {
+            Ok(data) => Ok(Some(ScreenFrame {
+                width: self.bounds.width,
+                height: self.bounds.height,
+                data,
+                timestamp: std::time::Instant::now(),
+                dirty_rects,
+                keyframe: false,
+                format: FrameFormat::Bgra8888,
+                change_ratio: None,
+            })),
+            Err(e) => {
+                // The fetched damage is lost with this frame
+                self.full_frame_pending = true;
+                Err(e)
+            }
+        }
+    }
+
+    /// Read the captured area as BGRA
+    fn grab(&mut self) -> Result<FrameBuffer> {
+        let MonitorBounds { x, y, width, height } = self.bounds;
+        let mut data = self.pool.take(width as usize * height as usize * 4);
+
+        if let Some(shm) = self.shm.as_mut() {
+            match shm.capture(&self.display, x, y) {
+                Ok(image) => {
+                    image_to_bgra(image, &mut data);
+                    return Ok(data.freeze());
+                }
+                Err(e) => {
+                    log::warn!("XShmGetImage failed, falling back to XGetImage: {}", e);
+                    self.shm = None;
+                }
+            }
+        }
+
+        let (display, root) = (self.display.raw(), self.display.root());
+        let image = self.display.trap_errors(|| unsafe {
+            xlib::XGetImage(display, root, x, y, width, height, xlib::XAllPlanes(), xlib::ZPixmap)
+        })?;
+        if image.is_null() {
+            return Err(RemoteCError::CaptureError("XGetImage failed".to_string()));
+        }
+
+        unsafe {
+            image_to_bgra(&*image, &mut data);
+            xlib::XDestroyImage(image);
+        }
+        Ok(data.freeze())
+    }
+}
+
+// SAFETY: X11Capture exclusively owns its display connection, shared
+// memory segment and damage object; they are only touched through `&mut self` methods, so at
+// most one thread uses the connection at a time.
+unsafe impl Send for X11Capture {}
+unsafe impl Sync for X11Capture {}
+
+/// Append an XImage to `out` as tightly packed BGRA
+pub(super) fn image_to_bgra(image: &xlib::XImage, out: &mut Vec<u8>) {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let is_bgrx = image.bits_per_pixel == 32
+        && image.byte_order == xlib::LSBFirst
+        && image.red_mask == 0xff0000
+        && image.green_mask == 0xff00
+        && image.blue_mask == 0xff;
+
+    if is_bgrx {
+        let stride = image.bytes_per_line as usize;
+        let data = unsafe { std::slice::from_raw_parts(image.data as *const u8, stride * height) };
+        convert_bgrx(data, width, height, stride, out);
+        return;
+    }
+
+    // Uncommon visuals (16-bit, big endian): go through XGetPixel
+    let image_ptr = image as *const xlib::XImage as *mut xlib::XImage;
+    let channel = |pixel: u64, mask: u64| -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let value = (pixel & mask) >> mask.trailing_zeros();
+        let max = mask >> mask.trailing_zeros();
+        (value * 255 / max) as u8
+    };
+
+    out.reserve(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = unsafe { xlib::XGetPixel(image_ptr, x as c_int, y as c_int) } as u64;
+            out.extend_from_slice(&[
+                channel(pixel, image.blue_mask as u64),
+                channel(pixel, image.green_mask as u64),
+                channel(pixel, image.red_mask as u64),
+                255,
+            ]);
+        }
+    }
+}
+
+/// Append padded BGRX rows to `out` as packed BGRA with opaque alpha
+fn convert_bgrx(data: &[u8], width: usize, height: usize, stride: usize, out: &mut Vec<u8>) {
+    let row_len = width * 4;
+    let start = out.len();
+    out.reserve(row_len * height);
+    for row in data.chunks(stride).take(height) {
+        out.extend_from_slice(&row[..row_len]);
+    }
+    for px in out[start..].chunks_exact_mut(4) {
+        px[3] = 255;
+    }
+}
+
+/// Enumerate monitors with RandR, falling back to the whole X screen
+pub fn enumerate_monitors_x11(display: &X11Display) -> Result<Vec<Monitor>> {
+    if let Some(monitors) = super::randr::enumerate_monitors_randr(display) {
+        return Ok(monitors);
+    }
+
+    log::debug!("RandR unavailable, reporting the X screen as a single monitor");
+    let (width, height) = display.screen_size();
+    let bounds = MonitorBounds::new(0, 0, width, height);
+
+    Ok(vec![Monitor {
+        id: "x11-screen-0".to_string(),
+        index: 0,
+        name: "X11 Screen".to_string(),
+        is_primary: true,
+        bounds,
+        work_area: bounds,
+        scale_factor: 1.0,
+        refresh_rate: 60,
+        bit_depth: 32,
+        orientation: MonitorOrientation::Landscape,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Live X server tests run only when one is reachable (e.g. under Xvfb)
+    fn test_display() -> Option<X11Display> {
+        std::env::var_os("DISPLAY")?;
+        X11Display::open(None).ok()
+    }
+
+    #[test]
+    fn test_convert_bgrx_strips_padding() {
+        // 2x2 image, 12-byte stride, alpha left undefined by the server
+        let data = [
+            1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9, //
+            7, 8, 9, 0, 10, 11, 12, 0, 9, 9, 9, 9,
+        ];
+        let mut out = Vec::new();
+        convert_bgrx(&data, 2, 2, 12, &mut out);
+        assert_eq!(out, vec![1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255, 10, 11, 12, 255]);
+    }
+
+    #[test]
+    fn test_capture_root_window() {
+        let Some(display) = test_display() else {
+            return;
+        };
+        let (width, height) = display.screen_size();
+        assert!(width > 0 && height > 0);
+
+        let bounds = MonitorBounds::new(0, 0, width.min(64), height.min(48));
+        let mut capture = X11Capture::new(display, bounds).unwrap();
+        let frame = capture.capture_frame().unwrap().expect("first frame is always delivered");
+
+        assert_eq!((frame.width, frame.height), (bounds.width, bounds.height));
+        assert_eq!(frame.data.len(), (bounds.width * bounds.height * 4) as usize);
+        assert!(frame.data.chunks(4).all(|px| px[3] == 255));
+    }
+
+    #[test]
+    fn test_capture_outside_screen_fails() {
+        let Some(display) = test_display() else {
+            return;
+        };
+        let (width, height) = display.screen_size();
+
+        let bounds = MonitorBounds::new(width as i32, height as i32, 16, 16);
+        let mut capture = X11Capture::new(display, bounds).unwrap();
+        assert!(capture.capture_frame().is_err());
+    }
+}
@@ -2,10 +2,32 @@
 //! 
 //! Provides cross-platform screen capture functionality with high performance.
 
-use crate::Result;
+use crate::{RemoteCError, Result};
 
+pub mod buffer;
+pub mod compositor;
+pub mod coords;
+pub mod cursor;
+pub mod driver;
+pub mod hotplug;
 pub mod monitor;
+pub mod postprocess;
+pub mod redaction;
+pub mod replay;
+pub mod synthetic;
+pub mod window;
+pub use buffer::{BufferPool, FrameBuffer, PooledBuffer};
+pub use compositor::{Compositor, CompositorConfig};
+pub use coords::CoordinateTransform;
+pub use cursor::{CursorEvent, CursorPosition, CursorShape, CursorState};
+pub use driver::{CaptureDriver, CaptureStats};
+pub use hotplug::{DisplayChange, DisplayWatcher};
 pub use monitor::{Monitor, MonitorBounds, MonitorOrientation, VirtualDesktop};
+pub use postprocess::{FrameProcessor, ProcessedCapture};
+pub use redaction::{RedactedCapture, RedactionRule, RedactionStyle, RedactionTarget, Redactor, WindowMatcher};
+pub use replay::{ReplayCapture, ReplayConfig};
+pub use synthetic::{SyntheticCapture, SyntheticConfig, SyntheticPattern};
+pub use window::{enumerate_windows, WindowEnumerator, WindowInfo};
 
 /// Represents a captured screen frame
 #[derive(Debug, Clone)]
@@ -14,10 +36,56 @@ pub struct ScreenFrame {
     pub width: u32,
     /// Height of the frame in pixels
     pub height: u32,
-    /// Raw pixel data in BGRA format
-    pub data: Vec<u8>,
+    /// Pixel data laid out as `format`, shared between clones
+    pub data: FrameBuffer,
+    /// Layout of `data`; backends produce [`FrameFormat::Bgra8888`]
+    pub format: FrameFormat,
     /// Timestamp when the frame was captured
     pub timestamp: std::time::Instant,
+    /// Regions changed since the previous frame, relative to the frame origin
+    ///
+    /// `None` when the backend cannot track changes; treat the whole frame
+    /// as changed.
+    pub dirty_rects: Option<Vec<CaptureRegion>>,
+    /// Whether encoders must start a new keyframe here, e.g. because the
+    /// display layout changed and the capture was reinitialized
+    pub keyframe: bool,
+    /// Fraction of the frame that changed since the previous one, set when
+    /// frame differencing is enabled
+    pub change_ratio: Option<f32>,
+}
+
+/// Pixel layout of [`ScreenFrame::data`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameFormat {
+    /// 32 bits per pixel: blue, green, red, alpha bytes
+    #[default]
+    Bgra8888,
+    /// 24 bits per pixel: blue, green, red bytes
+    Bgr888,
+    /// 16 bits per pixel, little-endian, red in the high 5 bits
+    Rgb565,
+}
+
+impl FrameFormat {
+    /// Format for a [`CaptureQuality::color_depth`] of 16, 24 or 32 bits
+    pub fn from_color_depth(bits: u8) -> Result<Self> {
+        match bits {
+            16 => Ok(FrameFormat::Rgb565),
+            24 => Ok(FrameFormat::Bgr888),
+            32 => Ok(FrameFormat::Bgra8888),
+            _ => Err(RemoteCError::CaptureError(format!("Unsupported color depth: {} bits", bits))),
+        }
+    }
+
+    /// Bytes used by one pixel
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            FrameFormat::Bgra8888 => 4,
+            FrameFormat::Bgr888 => 3,
+            FrameFormat::Rgb565 => 2,
+        }
+    }
 }
 
 /// Screen capture configuration
@@ -29,10 +97,28 @@ pub struct CaptureConfig {
     pub target_fps: u32,
     /// Whether to capture cursor
     pub capture_cursor: bool,
+    /// How the cursor is delivered when `capture_cursor` is set
+    pub cursor_mode: CursorMode,
     /// Capture region (None for full screen)
     pub region: Option<CaptureRegion>,
     /// Quality settings
     pub quality: CaptureQuality,
+    /// Where frames come from
+    pub source: CaptureSource,
+    /// Privacy rules applied to every frame by [`create_capture`]
+    pub redaction: Option<Redactor>,
+}
+
+/// Frame source selected by [`create_capture`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CaptureSource {
+    /// The platform's screen capture
+    #[default]
+    Platform,
+    /// Generated test patterns, for headless machines and demos
+    Synthetic(SyntheticConfig),
+    /// Frames replayed from PNG files or a Y4M file
+    Replay(ReplayConfig),
 }
 
 /// Capture mode determines which monitors to capture
@@ -48,6 +134,20 @@ pub enum CaptureMode {
     SelectedMonitors(Vec<usize>),
     /// Capture the monitor containing a specific window
     WindowMonitor(String), // Window title or ID
+    /// Capture only the contents of a window, following it as it moves
+    Window(String), // Window title or ID
+}
+
+/// How the cursor reaches the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorMode {
+    /// Left to the platform grab: Wayland compositors paint it into the
+    /// frames, while X11 and GDI grabs leave it out
+    Embedded,
+    /// Left out of the frames and reported through
+    /// [`ScreenCapture::cursor_events`]
+    #[default]
+    Separate,
 }
 
 /// Quality settings for capture
@@ -57,14 +157,16 @@ pub struct CaptureQuality {
     pub use_hardware_acceleration: bool,
     /// Color depth (16, 24, or 32 bits)
     pub color_depth: u8,
-    /// Enable frame differencing for optimization
+    /// Dither when reducing the color depth to 16 bits
+    pub dither: bool,
+    /// Drop frames identical to the previous one and report change ratios
     pub enable_frame_diff: bool,
     /// JPEG quality for compression (0-100)
     pub jpeg_quality: u8,
 }
 
 /// Defines a region of the screen to capture
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CaptureRegion {
     /// X coordinate of the top-left corner
     pub x: i32,
@@ -85,8 +187,20 @@ pub trait ScreenCapture: Send + Sync {
     fn stop(&mut self) -> Result<()>;
     
     /// Get the next captured frame (non-blocking)
+    ///
+    /// Returns `None` when no new frame is due or, for backends that track
+    /// damage, when the screen has not changed. [`CaptureDriver`] polls
+    /// this at `target_fps` on a dedicated thread.
     fn get_frame(&mut self) -> Result<Option<ScreenFrame>>;
     
+    /// Cursor changes since the last call (non-blocking)
+    ///
+    /// Only produces events with [`CursorMode::Separate`]; backends that
+    /// cannot read the cursor return an empty list.
+    fn cursor_events(&mut self) -> Result<Vec<CursorEvent>> {
+        Ok(Vec::new())
+    }
+    
     /// Check if capture is active
     fn is_active(&self) -> bool;
     
@@ -100,8 +214,11 @@ impl Default for CaptureConfig {
             mode: CaptureMode::PrimaryMonitor,
             target_fps: 30,
             capture_cursor: true,
+            cursor_mode: CursorMode::default(),
             region: None,
             quality: CaptureQuality::default(),
+            source: CaptureSource::default(),
+            redaction: None,
         }
     }
 }
@@ -111,23 +228,72 @@ impl Default for CaptureQuality {
         Self {
             use_hardware_acceleration: true,
             color_depth: 32,
+            dither: true,
             enable_frame_diff: true,
             jpeg_quality: 85,
         }
     }
 }
 
+impl CaptureConfig {
+    /// Desktop rectangle captured with this configuration
+    ///
+    /// The selected monitors are combined into their bounding rectangle. A
+    /// capture region is relative to that rectangle's top-left corner and is
+    /// clipped to it.
+    pub fn capture_area(&self, desktop: &VirtualDesktop) -> Result<MonitorBounds> {
+        let mut bounds: Option<MonitorBounds> = None;
+        for index in self.mode.get_monitor_indices(desktop) {
+            let monitor = desktop.get_monitor(index).ok_or_else(|| {
+                RemoteCError::CaptureError(format!("Invalid monitor index: {}", index))
+            })?;
+            bounds = Some(match bounds {
+                Some(b) => b.union(&monitor.bounds),
+                None => monitor.bounds,
+            });
+        }
+        let bounds = bounds.ok_or_else(|| RemoteCError::CaptureError("No monitors selected".to_string()))?;
+
+        match self.region {
+            Some(region) => {
+                let region = MonitorBounds::new(
+                    bounds.x + region.x,
+                    bounds.y + region.y,
+                    region.width,
+                    region.height,
+                );
+                region.intersection(&bounds).ok_or_else(|| {
+                    RemoteCError::CaptureError("Capture region is outside the captured area".to_string())
+                })
+            }
+            None => Ok(bounds),
+        }
+    }
+}
+
 impl CaptureMode {
     /// Get the monitor indices to capture based on the mode
+    ///
+    /// Window modes resolve to the monitor showing the window, or to no
+    /// monitor when the window cannot be found.
     pub fn get_monitor_indices(&self, desktop: &VirtualDesktop) -> Vec<usize> {
         match self {
             CaptureMode::SingleMonitor(idx) => vec![*idx],
             CaptureMode::PrimaryMonitor => vec![desktop.primary_index],
             CaptureMode::AllMonitors => (0..desktop.monitors.len()).collect(),
             CaptureMode::SelectedMonitors(indices) => indices.clone(),
-            CaptureMode::WindowMonitor(_) => {
-                // This requires window manager integration
-                vec![desktop.primary_index]
+            CaptureMode::WindowMonitor(query) | CaptureMode::Window(query) => {
+                match window::resolve_window_monitor(desktop, query) {
+                    Ok(index) => vec![index],
+                    Err(RemoteCError::NotImplemented(_)) => {
+                        log::warn!("Window lookup unsupported, using the primary monitor");
+                        vec![desktop.primary_index]
+                    }
+                    Err(e) => {
+                        log::warn!("{}", e);
+                        Vec::new()
+                    }
+                }
             }
         }
     }
@@ -146,8 +312,31 @@ pub mod linux;
 #[cfg(target_os = "macos")]
 pub mod macos;
 
-/// Create a platform-specific screen capture instance
+/// Create a screen capture instance for the configured source
+///
+/// Frames are redacted according to [`CaptureConfig::redaction`], then
+/// differenced and reduced in color depth according to
+/// [`CaptureConfig::quality`], before they are returned.
 pub fn create_capture(config: CaptureConfig) -> Result<Box<dyn ScreenCapture>> {
+    let redactor = config.redaction.clone();
+    let quality = config.quality;
+    let mut capture = create_backend(config)?;
+    if let Some(redactor) = redactor {
+        capture = Box::new(RedactedCapture::new(capture, redactor));
+    }
+    if quality.color_depth != 32 || quality.enable_frame_diff {
+        capture = Box::new(ProcessedCapture::new(capture, FrameProcessor::new(&quality)?));
+    }
+    Ok(capture)
+}
+
+fn create_backend(config: CaptureConfig) -> Result<Box<dyn ScreenCapture>> {
+    match config.source {
+        CaptureSource::Synthetic(_) => return Ok(Box::new(SyntheticCapture::new(config)?)),
+        CaptureSource::Replay(_) => return Ok(Box::new(ReplayCapture::new(config)?)),
+        CaptureSource::Platform => {}
+    }
+
     #[cfg(target_os = "windows")]
     {
         Ok(Box::new(windows::WindowsCapture::new(config)?))
@@ -237,6 +237,25 @@ pub fn get_virtual_desktop() -> Result<VirtualDesktop> {
     VirtualDesktop::new(monitors)
 }
 
+#[cfg(test)]
+impl Monitor {
+    /// Landscape monitor at 1x scale for tests; index 0 is the primary
+    pub(crate) fn test(index: usize, bounds: MonitorBounds) -> Self {
+        Self {
+            id: format!("test-{}", index),
+            index,
+            name: format!("Test {}", index),
+            is_primary: index == 0,
+            bounds,
+            work_area: bounds,
+            scale_factor: 1.0,
+            refresh_rate: 60,
+            bit_depth: 32,
+            orientation: MonitorOrientation::Landscape,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
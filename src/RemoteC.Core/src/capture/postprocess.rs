@@ -0,0 +1,292 @@
+//! Quality post-processing of captured frames
+//!
+//! [`FrameProcessor`] applies the [`CaptureQuality`] settings backends
+//! leave alone. Frame differencing drops frames identical to the previous
+//! one, replaces the dirty rectangles with the tiles that actually changed
+//! and reports the changed fraction as [`ScreenFrame::change_ratio`].
+//! Color-depth reduction then packs BGRA pixels into 24-bit BGR or 16-bit
+//! RGB565, the latter with optional ordered dithering.
+//! [`create_capture`](super::create_capture) adds this stage last, after
+//! redaction, since the other stages expect BGRA pixels.
+
+use super::cursor::CursorEvent;
+use super::{
+    BufferPool, CaptureConfig, CaptureQuality, CaptureRegion, FrameBuffer, FrameFormat, ScreenCapture, ScreenFrame,
+};
+use crate::{RemoteCError, Result};
+use rayon::prelude::*;
+
+/// Edge length of the square tiles compared by frame differencing
+pub const DIFF_TILE_SIZE: usize = 32;
+
+/// 4x4 Bayer matrix, thresholds 0..16
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Changed tiles of a `width` x `height` BGRA frame, merged along rows
+pub fn diff_tiles(previous: &[u8], current: &[u8], width: u32, height: u32) -> Vec<CaptureRegion> {
+    let (width, height) = (width as usize, height as usize);
+    let stride = width * 4;
+
+    (0..height.div_ceil(DIFF_TILE_SIZE))
+        .into_par_iter()
+        .flat_map_iter(|tile_row| {
+            let top = tile_row * DIFF_TILE_SIZE;
+            let rows = DIFF_TILE_SIZE.min(height - top);
+            let changed = |left: usize, right: usize| {
+                (top..top + rows).any(|y| {
+                    let span = y * stride + left * 4..y * stride + right * 4;
+                    previous[span.clone()] != current[span]
+                })
+            };
+
+            let mut regions: Vec<CaptureRegion> = Vec::new();
+            for left in (0..width).step_by(DIFF_TILE_SIZE) {
+                let right = (left + DIFF_TILE_SIZE).min(width);
+                if !changed(left, right) {
+                    continue;
+                }
+                match regions.last_mut() {
+                    Some(last) if last.x as usize + last.width as usize == left => {
+                        last.width += (right - left) as u32;
+                    }
+                    _ => regions.push(CaptureRegion {
+                        x: left as i32,
+                        y: top as i32,
+                        width: (right - left) as u32,
+                        height: rows as u32,
+                    }),
+                }
+            }
+            regions
+        })
+        .collect()
+}
+
+/// Pack `width` x `height` BGRA pixels into `format`, appending to `out`
+///
+/// Dithering only affects [`FrameFormat::Rgb565`].
+pub fn pack_pixels(data: &[u8], width: u32, height: u32, format: FrameFormat, dither: bool, out: &mut Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    let row_len = width * format.bytes_per_pixel();
+    out.resize(row_len * height, 0);
+    if row_len == 0 {
+        return;
+    }
+
+    out.par_chunks_exact_mut(row_len)
+        .zip(data.par_chunks_exact(width * 4))
+        .enumerate()
+        .for_each(|(y, (dst, src))| match format {
+            FrameFormat::Bgra8888 => dst.copy_from_slice(src),
+            FrameFormat::Bgr888 => {
+                for (d, s) in dst.chunks_exact_mut(3).zip(src.chunks_exact(4)) {
+                    d.copy_from_slice(&s[..3]);
+                }
+            }
+            FrameFormat::Rgb565 => {
+                for (x, (d, s)) in dst.chunks_exact_mut(2).zip(src.chunks_exact(4)).enumerate() {
+                    let threshold = if dither { u16::from(BAYER[y % 4][x % 4]) } else { 0 };
+                    // Spread the dropped low bits (3 or 2) over the threshold range
+                    let channel = |value: u8, dropped: u32| {
+                        let offset = (threshold << dropped) / 16;
+                        (u16::from(value) + offset).min(255) >> dropped
+                    };
+                    let (b, g, r) = (channel(s[0], 3), channel(s[1], 2), channel(s[2], 3));
+                    d.copy_from_slice(&((r << 11) | (g << 5) | b).to_le_bytes());
+                }
+            }
+        });
+}
+
+/// Applies frame differencing and color-depth reduction
+pub struct FrameProcessor {
+    format: FrameFormat,
+    dither: bool,
+    frame_diff: bool,
+    /// Previous BGRA frame, kept by reference rather than copied
+    previous: Option<(u32, u32, FrameBuffer)>,
+    pool: BufferPool,
+}
+
+impl FrameProcessor {
+    /// Create a processor for `quality`
+    pub fn new(quality: &CaptureQuality) -> Result<Self> {
+        Ok(Self {
+            format: FrameFormat::from_color_depth(quality.color_depth)?,
+            dither: quality.dither,
+            frame_diff: quality.enable_frame_diff,
+            previous: None,
+            pool: BufferPool::default(),
+        })
+    }
+
+    /// Forget the previous frame so the next one is delivered in full
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Process a BGRA frame; `None` if it is identical to the previous one
+    pub fn process(&mut self, mut frame: ScreenFrame) -> Result<Option<ScreenFrame>> {
+        if frame.format != FrameFormat::Bgra8888 {
+            return Err(RemoteCError::CaptureError(format!(
+                "Frame post-processing expects BGRA pixels, got {:?}",
+                frame.format
+            )));
+        }
+
+        if self.frame_diff {
+            let tiles = match &self.previous {
+                Some((width, height, previous)) if !frame.keyframe && (*width, *height) == (frame.width, frame.height) => {
+                    diff_tiles(previous, &frame.data, frame.width, frame.height)
+                }
+                _ => vec![CaptureRegion { x: 0, y: 0, width: frame.width, height: frame.height }],
+            };
+            self.previous = Some((frame.width, frame.height, frame.data.clone()));
+            if tiles.is_empty() {
+                return Ok(None);
+            }
+
+            let area = |r: &CaptureRegion| u64::from(r.width) * u64::from(r.height);
+            let total = (u64::from(frame.width) * u64::from(frame.height)).max(1);
+            frame.change_ratio = Some(tiles.iter().map(area).sum::<u64>() as f32 / total as f32);
+            frame.dirty_rects = Some(tiles);
+        }
+
+        if self.format != FrameFormat::Bgra8888 {
+            let mut packed = self.pool.take(frame.width as usize * frame.height as usize * self.format.bytes_per_pixel());
+            pack_pixels(&frame.data, frame.width, frame.height, self.format, self.dither, &mut packed);
+            frame.data = packed.freeze();
+            frame.format = self.format;
+        }
+
+        Ok(Some(frame))
+    }
+}
+
+/// Capture backend wrapper running a [`FrameProcessor`] on every frame
+pub struct ProcessedCapture {
+    inner: Box<dyn ScreenCapture>,
+    processor: FrameProcessor,
+}
+
+impl ProcessedCapture {
+    /// Process frames of `inner` with `processor`
+    pub fn new(inner: Box<dyn ScreenCapture>, processor: FrameProcessor) -> Self {
+        Self { inner, processor }
+    }
+}
+
+impl ScreenCapture for ProcessedCapture {
+    fn start(&mut self) -> Result<()> {
+        self.processor.reset();
+        self.inner.start()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.inner.stop()
+    }
+
+    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        match self.inner.get_frame()? {
+            Some(frame) => self.processor.process(frame),
+            None => Ok(None),
+        }
+    }
+
+    fn cursor_events(&mut self) -> Result<Vec<CursorEvent>> {
+        self.inner.cursor_events()
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn config(&self) -> &CaptureConfig {
+        self.inner.config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn frame(width: u32, height: u32, data: Vec<u8>) -> ScreenFrame {
+        ScreenFrame {
+            width,
+            height,
+            data: data.into(),
+            format: FrameFormat::Bgra8888,
+            timestamp: Instant::now(),
+            dirty_rects: None,
+            keyframe: false,
+            change_ratio: None,
+        }
+    }
+
+    fn quality(color_depth: u8, enable_frame_diff: bool) -> CaptureQuality {
+        CaptureQuality { color_depth, enable_frame_diff, dither: false, ..Default::default() }
+    }
+
+    #[test]
+    fn test_frame_diff_suppresses_identical_frames() {
+        let (width, height) = (80, 40);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        let mut processor = FrameProcessor::new(&quality(32, true)).unwrap();
+
+        let first = processor.process(frame(width, height, pixels.clone())).unwrap().unwrap();
+        assert_eq!(first.change_ratio, Some(1.0));
+        assert!(processor.process(frame(width, height, pixels.clone())).unwrap().is_none());
+
+        // One pixel in the second tile column of the second tile row
+        let at = ((35 * width + 40) * 4) as usize;
+        pixels[at] = 1;
+        let changed = processor.process(frame(width, height, pixels.clone())).unwrap().unwrap();
+        assert_eq!(changed.dirty_rects, Some(vec![CaptureRegion { x: 32, y: 32, width: 32, height: 8 }]));
+        assert_eq!(changed.change_ratio, Some(256.0 / 3200.0));
+
+        // Keyframes are never suppressed
+        let mut keyframe = frame(width, height, pixels);
+        keyframe.keyframe = true;
+        assert_eq!(processor.process(keyframe).unwrap().unwrap().change_ratio, Some(1.0));
+    }
+
+    #[test]
+    fn test_color_depth_packing() {
+        let bgra = vec![0x10, 0x20, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+        let mut processor = FrameProcessor::new(&quality(24, false)).unwrap();
+        let packed = processor.process(frame(2, 1, bgra.clone())).unwrap().unwrap();
+        assert_eq!(packed.format, FrameFormat::Bgr888);
+        assert_eq!(&packed.data[..], [0x10, 0x20, 0xF8, 0xFF, 0xFF, 0xFF]);
+
+        let mut processor = FrameProcessor::new(&quality(16, false)).unwrap();
+        let packed = processor.process(frame(2, 1, bgra)).unwrap().unwrap();
+        assert_eq!(packed.format, FrameFormat::Rgb565);
+        let pixel = u16::from_le_bytes([packed.data[0], packed.data[1]]);
+        assert_eq!((pixel >> 11, (pixel >> 5) & 0x3F, pixel & 0x1F), (0x1F, 0x08, 0x02));
+        assert_eq!(&packed.data[2..], [0xFF, 0xFF]);
+
+        assert!(FrameProcessor::new(&quality(8, false)).is_err());
+    }
+
+    #[test]
+    fn test_dithering_preserves_average_color() {
+        // A level between two RGB565 steps
+        let (width, height) = (8u32, 8u32);
+        let bgra: Vec<u8> = (0..width * height).flat_map(|_| [0, 0, 0x14, 0xFF]).collect();
+
+        let red_levels = |dither: bool| {
+            let mut out = Vec::new();
+            pack_pixels(&bgra, width, height, FrameFormat::Rgb565, dither, &mut out);
+            out.chunks_exact(2).map(|px| u16::from_le_bytes([px[0], px[1]]) >> 11).collect::<Vec<_>>()
+        };
+        let mean = |levels: &[u16]| levels.iter().map(|&l| f64::from(l) * 8.0).sum::<f64>() / levels.len() as f64;
+
+        let flat = red_levels(false);
+        assert!(flat.iter().all(|&l| l == 2));
+        let dithered = red_levels(true);
+        assert!(dithered.contains(&2) && dithered.contains(&3));
+        assert!((mean(&dithered) - 20.0).abs() < (mean(&flat) - 20.0).abs());
+    }
+}
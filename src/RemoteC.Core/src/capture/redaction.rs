@@ -0,0 +1,654 @@
+//! Privacy redaction of captured frames
+//!
+//! A [`Redactor`] holds rules naming screen areas that must never leave the
+//! host: fixed desktop regions, whole monitors, or windows matched by
+//! title, id or process. When [`CaptureConfig::redaction`] is set,
+//! [`create_capture`](super::create_capture) wraps the backend in a
+//! [`RedactedCapture`], so matched pixels are blacked out, blurred or
+//! pixelated before any encoder sees the frame. Rules can be changed at
+//! runtime through any clone of the redactor. Rule changes and every area
+//! that starts or stops being redacted are logged under [`AUDIT_TARGET`].
+//!
+//! Window rules fail closed: if the windows cannot be listed, the whole
+//! frame is blacked out. Windows are listed again for every frame, so a
+//! moved or newly opened window is hidden in the first frame showing it.
+
+use super::cursor::CursorEvent;
+use super::monitor::{get_virtual_desktop, MonitorBounds, VirtualDesktop};
+use super::window::{find_window, WindowEnumerator, WindowInfo};
+use super::{BufferPool, CaptureConfig, CaptureMode, CaptureRegion, CaptureSource, ScreenCapture, ScreenFrame};
+use crate::Result;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Log target of redaction audit records
+pub const AUDIT_TARGET: &str = "remotec::audit";
+
+/// Which windows a rule covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowMatcher {
+    /// Windows whose title contains this text, ignoring case
+    Title(String),
+    /// The window with this native id
+    Id(u64),
+    /// Every window of this process
+    Process(u32),
+}
+
+impl WindowMatcher {
+    /// Whether `window` is covered
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        match self {
+            WindowMatcher::Title(text) => window.title.to_lowercase().contains(&text.to_lowercase()),
+            WindowMatcher::Id(id) => window.id == *id,
+            WindowMatcher::Process(pid) => window.pid == Some(*pid),
+        }
+    }
+}
+
+/// Area covered by a rule
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedactionTarget {
+    /// Fixed rectangle in desktop coordinates
+    Region(MonitorBounds),
+    /// A whole monitor by index
+    Monitor(usize),
+    /// Every visible window matching
+    Window(WindowMatcher),
+}
+
+/// How covered pixels are hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactionStyle {
+    /// Solid black
+    Blackout,
+    /// Box blur with this radius in pixels (at least 1)
+    Blur(u32),
+    /// Blocks of this size filled with their average color (at least 2)
+    Pixelate(u32),
+}
+
+impl fmt::Display for RedactionStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedactionStyle::Blackout => write!(f, "blackout"),
+            RedactionStyle::Blur(radius) => write!(f, "blur {}", radius),
+            RedactionStyle::Pixelate(block) => write!(f, "pixelate {}", block),
+        }
+    }
+}
+
+/// A named redaction rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactionRule {
+    /// Name used in audit records
+    pub name: String,
+    /// Area to hide
+    pub target: RedactionTarget,
+    /// How to hide it
+    pub style: RedactionStyle,
+}
+
+impl RedactionRule {
+    /// Create a rule
+    pub fn new(name: impl Into<String>, target: RedactionTarget, style: RedactionStyle) -> Self {
+        Self {
+            name: name.into(),
+            target,
+            style,
+        }
+    }
+}
+
+/// Shared, runtime-updatable set of redaction rules
+///
+/// Clones share the same rules, so a capture running on another thread
+/// picks up changes with its next frame.
+#[derive(Clone, Default)]
+pub struct Redactor {
+    rules: Arc<Mutex<Vec<RedactionRule>>>,
+}
+
+impl Redactor {
+    /// Create a redactor with `rules`
+    pub fn new(rules: Vec<RedactionRule>) -> Self {
+        let redactor = Self::default();
+        redactor.set_rules(rules);
+        redactor
+    }
+
+    /// Current rules
+    pub fn rules(&self) -> Vec<RedactionRule> {
+        self.lock().clone()
+    }
+
+    /// Replace all rules
+    pub fn set_rules(&self, rules: Vec<RedactionRule>) {
+        let mut current = self.lock();
+        *current = rules;
+        log::info!(target: AUDIT_TARGET, "Redaction rules set: {}", rule_names(&current));
+    }
+
+    /// Add a rule, replacing any rule with the same name
+    pub fn add_rule(&self, rule: RedactionRule) {
+        let mut rules = self.lock();
+        rules.retain(|r| r.name != rule.name);
+        log::info!(target: AUDIT_TARGET, "Redaction rule added: {} ({:?}, {})", rule.name, rule.target, rule.style);
+        rules.push(rule);
+    }
+
+    /// Remove the rule named `name`; returns whether it existed
+    pub fn remove_rule(&self, name: &str) -> bool {
+        let mut rules = self.lock();
+        let before = rules.len();
+        rules.retain(|r| r.name != name);
+        let removed = rules.len() != before;
+        if removed {
+            log::info!(target: AUDIT_TARGET, "Redaction rule removed: {}", name);
+        }
+        removed
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<RedactionRule>> {
+        self.rules.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redactor").field("rules", &*self.lock()).finish()
+    }
+}
+
+fn rule_names(rules: &[RedactionRule]) -> String {
+    if rules.is_empty() {
+        return "none".to_string();
+    }
+    rules.iter().map(|r| r.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Frame rectangle hidden by a rule
+#[derive(Debug, Clone, PartialEq)]
+struct Redaction {
+    rule: String,
+    style: RedactionStyle,
+    rect: CaptureRegion,
+}
+
+/// Where a frame lies on the desktop and what it may show
+struct FrameView<'a> {
+    /// Desktop rectangle the frame shows, `None` for non-desktop sources
+    area: Option<MonitorBounds>,
+    desktop: Option<&'a VirtualDesktop>,
+    /// Listed windows, `None` if listing failed
+    windows: Option<&'a [WindowInfo]>,
+}
+
+/// Frame rectangles covered by `rules`
+fn resolve(rules: &[RedactionRule], view: &FrameView<'_>, width: u32, height: u32) -> Vec<Redaction> {
+    let whole = CaptureRegion { x: 0, y: 0, width, height };
+    let mut redactions = Vec::new();
+
+    for rule in rules {
+        let mut push = |rect: CaptureRegion| {
+            redactions.push(Redaction { rule: rule.name.clone(), style: rule.style, rect });
+        };
+        let Some(area) = view.area else {
+            // Synthetic and replayed frames have no desktop; only fixed
+            // regions apply, in frame coordinates
+            if let RedactionTarget::Region(region) = rule.target {
+                let frame = MonitorBounds::new(0, 0, width, height);
+                if let Some(rect) = to_frame(region, frame, width, height) {
+                    push(rect);
+                }
+            }
+            continue;
+        };
+
+        let desktop_rects: Vec<MonitorBounds> = match &rule.target {
+            RedactionTarget::Region(region) => vec![*region],
+            RedactionTarget::Monitor(index) => view
+                .desktop
+                .and_then(|desktop| desktop.get_monitor(*index))
+                .map(|monitor| monitor.bounds)
+                .into_iter()
+                .collect(),
+            RedactionTarget::Window(matcher) => match view.windows {
+                Some(windows) => windows
+                    .iter()
+                    .filter(|w| w.visible && matcher.matches(w))
+                    .map(|w| w.bounds)
+                    .collect(),
+                None => {
+                    push(whole);
+                    continue;
+                }
+            },
+        };
+        for bounds in desktop_rects {
+            if let Some(rect) = to_frame(bounds, area, width, height) {
+                push(rect);
+            }
+        }
+    }
+
+    redactions
+}
+
+/// Map desktop `bounds` into a `width` x `height` frame showing `area`
+///
+/// Edges are rounded outwards so scaled frames never leak a partial pixel.
+fn to_frame(bounds: MonitorBounds, area: MonitorBounds, width: u32, height: u32) -> Option<CaptureRegion> {
+    let visible = bounds.intersection(&area)?;
+    let scale = |offset: i32, extent: u32, size: u32, round_up: bool| {
+        let scaled = i64::from(offset) * i64::from(size);
+        let extent = i64::from(extent.max(1));
+        let value = if round_up { (scaled + extent - 1) / extent } else { scaled / extent };
+        value.clamp(0, i64::from(size)) as u32
+    };
+
+    let left = scale(visible.x - area.x, area.width, width, false);
+    let top = scale(visible.y - area.y, area.height, height, false);
+    let right = scale(visible.x + visible.width as i32 - area.x, area.width, width, true);
+    let bottom = scale(visible.y + visible.height as i32 - area.y, area.height, height, true);
+    (right > left && bottom > top).then(|| CaptureRegion {
+        x: left as i32,
+        y: top as i32,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+fn overlaps(a: &CaptureRegion, b: &CaptureRegion) -> bool {
+    a.x < b.x + b.width as i32 && b.x < a.x + a.width as i32 && a.y < b.y + b.height as i32 && b.y < a.y + a.height as i32
+}
+
+/// Hide `rect` of a BGRA image `width` pixels wide
+fn apply_style(data: &mut [u8], width: usize, rect: &CaptureRegion, style: RedactionStyle) {
+    let (x0, y0) = (rect.x as usize, rect.y as usize);
+    let (w, h) = (rect.width as usize, rect.height as usize);
+    let stride = width * 4;
+    let pixel = |x: usize, y: usize| (y * stride) + x * 4;
+
+    match style {
+        RedactionStyle::Blackout => {
+            for y in y0..y0 + h {
+                for px in data[pixel(x0, y)..pixel(x0 + w, y)].chunks_exact_mut(4) {
+                    px.copy_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+        }
+        RedactionStyle::Pixelate(block) => {
+            let block = block.max(2) as usize;
+            for by in (y0..y0 + h).step_by(block) {
+                for bx in (x0..x0 + w).step_by(block) {
+                    let (bw, bh) = (block.min(x0 + w - bx), block.min(y0 + h - by));
+                    let mut sum = [0u64; 4];
+                    for y in by..by + bh {
+                        for px in data[pixel(bx, y)..pixel(bx + bw, y)].chunks_exact(4) {
+                            for (s, &c) in sum.iter_mut().zip(px) {
+                                *s += u64::from(c);
+                            }
+                        }
+                    }
+                    let count = (bw * bh) as u64;
+                    let average = sum.map(|s| (s / count) as u8);
+                    for y in by..by + bh {
+                        for px in data[pixel(bx, y)..pixel(bx + bw, y)].chunks_exact_mut(4) {
+                            px.copy_from_slice(&average);
+                        }
+                    }
+                }
+            }
+        }
+        RedactionStyle::Blur(radius) => {
+            let radius = radius.max(1) as usize;
+            let mut line = Vec::with_capacity(w.max(h));
+            // Two box passes in each direction approximate a gaussian
+            for _ in 0..2 {
+                for y in y0..y0 + h {
+                    line.clear();
+                    line.extend((x0..x0 + w).map(|x| pixel(x, y)));
+                    blur_line(data, &line, radius);
+                }
+                for x in x0..x0 + w {
+                    line.clear();
+                    line.extend((y0..y0 + h).map(|y| pixel(x, y)));
+                    blur_line(data, &line, radius);
+                }
+            }
+        }
+    }
+}
+
+/// Box-blur the pixels at byte `offsets`, averaging over what lies inside
+fn blur_line(data: &mut [u8], offsets: &[usize], radius: usize) {
+    let mut prefix = Vec::with_capacity(offsets.len() + 1);
+    prefix.push([0u32; 4]);
+    for &offset in offsets {
+        let mut next = *prefix.last().unwrap_or(&[0; 4]);
+        for (s, &c) in next.iter_mut().zip(&data[offset..offset + 4]) {
+            *s += u32::from(c);
+        }
+        prefix.push(next);
+    }
+
+    for (i, &offset) in offsets.iter().enumerate() {
+        let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(offsets.len()));
+        let count = (end - start) as u32;
+        for c in 0..4 {
+            data[offset + c] = ((prefix[end][c] - prefix[start][c]) / count) as u8;
+        }
+    }
+}
+
+/// Capture backend wrapper applying a [`Redactor`] to every frame
+pub struct RedactedCapture {
+    inner: Box<dyn ScreenCapture>,
+    redactor: Redactor,
+    /// Cached layout, refreshed when the backend reports a keyframe
+    desktop: Option<VirtualDesktop>,
+    /// Display connection kept open to list windows every frame
+    enumerator: WindowEnumerator,
+    /// Areas hidden in the previous frame
+    previous: Vec<Redaction>,
+    pool: BufferPool,
+}
+
+impl RedactedCapture {
+    /// Redact frames of `inner` according to `redactor`
+    pub fn new(inner: Box<dyn ScreenCapture>, redactor: Redactor) -> Self {
+        Self {
+            inner,
+            redactor,
+            desktop: None,
+            enumerator: WindowEnumerator::new(),
+            previous: Vec::new(),
+            pool: BufferPool::default(),
+        }
+    }
+
+    /// The shared rules applied to frames
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Areas of a `width` x `height` frame the rules cover
+    fn redactions(&mut self, rules: &[RedactionRule], width: u32, height: u32) -> Vec<Redaction> {
+        let config = self.inner.config();
+        if config.source != CaptureSource::Platform {
+            let view = FrameView { area: None, desktop: None, windows: None };
+            return resolve(rules, &view, width, height);
+        }
+
+        let whole = || {
+            rules
+                .iter()
+                .map(|rule| Redaction {
+                    rule: rule.name.clone(),
+                    style: RedactionStyle::Blackout,
+                    rect: CaptureRegion { x: 0, y: 0, width, height },
+                })
+                .collect()
+        };
+
+        let window_capture = matches!(config.mode, CaptureMode::Window(_));
+        let needs_windows = window_capture || rules.iter().any(|r| matches!(r.target, RedactionTarget::Window(_)));
+        let listed = if needs_windows {
+            match self.enumerator.enumerate() {
+                Ok(windows) => Some(windows),
+                Err(e) => {
+                    log::warn!(target: AUDIT_TARGET, "Cannot list windows, redacting the whole frame: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let windows = listed.as_deref();
+
+        if self.desktop.is_none() {
+            match get_virtual_desktop() {
+                Ok(desktop) => self.desktop = Some(desktop),
+                Err(e) => {
+                    log::warn!(target: AUDIT_TARGET, "Cannot locate the frame, redacting it whole: {}", e);
+                    return whole();
+                }
+            }
+        }
+        let Some(desktop) = self.desktop.as_ref() else {
+            return whole();
+        };
+
+        let area = match &config.mode {
+            CaptureMode::Window(query) => {
+                match windows.and_then(|windows| find_window(windows, query)) {
+                    Some(window) => Ok(window.bounds),
+                    None => {
+                        log::warn!(target: AUDIT_TARGET, "Captured window not found, redacting the whole frame");
+                        return whole();
+                    }
+                }
+            }
+            _ => config.capture_area(desktop),
+        };
+        let area = match area {
+            Ok(area) => area,
+            Err(e) => {
+                log::warn!(target: AUDIT_TARGET, "Cannot locate the frame, redacting it whole: {}", e);
+                return whole();
+            }
+        };
+
+        let view = FrameView {
+            area: Some(area),
+            desktop: Some(desktop),
+            windows,
+        };
+        resolve(rules, &view, width, height)
+    }
+
+    /// Hide covered areas of `frame` and log what changed
+    fn redact(&mut self, frame: &mut ScreenFrame) {
+        let rules = self.redactor.rules();
+        if rules.is_empty() && self.previous.is_empty() {
+            return;
+        }
+        if frame.keyframe {
+            self.desktop = None;
+        }
+
+        let redactions = if rules.is_empty() {
+            Vec::new()
+        } else {
+            self.redactions(&rules, frame.width, frame.height)
+        };
+
+        if !redactions.is_empty() {
+            let mut data = self.pool.take(frame.data.len());
+            data.extend_from_slice(&frame.data);
+            for redaction in &redactions {
+                apply_style(&mut data, frame.width as usize, &redaction.rect, redaction.style);
+            }
+            frame.data = data.freeze();
+        }
+
+        // Encoders only re-encode dirty areas: include redactions over
+        // changed pixels and every area that appeared or disappeared
+        if let Some(dirty) = frame.dirty_rects.as_mut() {
+            let changed: Vec<CaptureRegion> = redactions
+                .iter()
+                .filter(|r| !self.previous.contains(r) || dirty.iter().any(|d| overlaps(d, &r.rect)))
+                .chain(self.previous.iter().filter(|r| !redactions.contains(r)))
+                .map(|r| r.rect)
+                .collect();
+            dirty.extend(changed);
+        }
+
+        for redaction in redactions.iter().filter(|r| !self.previous.contains(r)) {
+            let rect = redaction.rect;
+            log::info!(
+                target: AUDIT_TARGET,
+                "Redacting {} ({}) at {}x{}+{}+{}",
+                redaction.rule, redaction.style, rect.width, rect.height, rect.x, rect.y
+            );
+        }
+        for redaction in self.previous.iter().filter(|r| !redactions.iter().any(|n| n.rule == r.rule)) {
+            log::info!(target: AUDIT_TARGET, "Redaction {} no longer applied", redaction.rule);
+        }
+        log::trace!(target: AUDIT_TARGET, "Redacted {} area(s)", redactions.len());
+
+        self.previous = redactions;
+    }
+}
+
+impl ScreenCapture for RedactedCapture {
+    fn start(&mut self) -> Result<()> {
+        self.desktop = None;
+        self.previous.clear();
+        self.inner.start()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.inner.stop()
+    }
+
+    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        let Some(mut frame) = self.inner.get_frame()? else {
+            return Ok(None);
+        };
+        self.redact(&mut frame);
+        Ok(Some(frame))
+    }
+
+    fn cursor_events(&mut self) -> Result<Vec<CursorEvent>> {
+        self.inner.cursor_events()
+    }
+
+    fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    fn config(&self) -> &CaptureConfig {
+        self.inner.config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{SyntheticCapture, SyntheticConfig, SyntheticPattern};
+
+    fn gradient(width: usize, height: usize) -> Vec<u8> {
+        (0..width * height).flat_map(|i| [(i % 256) as u8, (i / 7 % 256) as u8, 128, 255]).collect()
+    }
+
+    fn region(x: i32, y: i32, width: u32, height: u32) -> CaptureRegion {
+        CaptureRegion { x, y, width, height }
+    }
+
+    #[test]
+    fn test_styles_only_touch_their_rect() {
+        let (width, height) = (16, 12);
+        let original = gradient(width, height);
+        let rect = region(4, 2, 8, 6);
+        let inside = |x: usize, y: usize| (4..12).contains(&x) && (2..8).contains(&y);
+
+        for style in [RedactionStyle::Blackout, RedactionStyle::Blur(2), RedactionStyle::Pixelate(4)] {
+            let mut data = original.clone();
+            apply_style(&mut data, width, &rect, style);
+            for y in 0..height {
+                for x in 0..width {
+                    let i = (y * width + x) * 4;
+                    if !inside(x, y) {
+                        assert_eq!(data[i..i + 4], original[i..i + 4], "{} leaked outside at {},{}", style, x, y);
+                    }
+                }
+            }
+            assert_ne!(data, original, "{} changed nothing", style);
+        }
+
+        let mut data = original.clone();
+        apply_style(&mut data, width, &rect, RedactionStyle::Pixelate(4));
+        let at = |x: usize, y: usize| &data[(y * width + x) * 4..(y * width + x) * 4 + 4];
+        assert_eq!(at(4, 2), at(7, 5));
+        assert_ne!(at(4, 2), at(8, 2));
+    }
+
+    #[test]
+    fn test_resolve_maps_desktop_targets_into_frame() {
+        let windows = vec![
+            WindowInfo {
+                id: 1,
+                title: "Password Manager".to_string(),
+                pid: Some(42),
+                bounds: MonitorBounds::new(1900, 100, 100, 50),
+                visible: true,
+            },
+            WindowInfo {
+                id: 2,
+                title: "password notes".to_string(),
+                pid: None,
+                bounds: MonitorBounds::new(2000, 0, 10, 10),
+                visible: false,
+            },
+        ];
+        // A 1920x1080 monitor at x=1920 captured at half size
+        let view = FrameView {
+            area: Some(MonitorBounds::new(1920, 0, 1920, 1080)),
+            desktop: None,
+            windows: Some(&windows),
+        };
+        let rules = vec![
+            RedactionRule::new("vault", RedactionTarget::Window(WindowMatcher::Title("PASSWORD".to_string())), RedactionStyle::Blackout),
+            RedactionRule::new("offscreen", RedactionTarget::Region(MonitorBounds::new(0, 0, 100, 100)), RedactionStyle::Blackout),
+        ];
+
+        let redactions = resolve(&rules, &view, 960, 540);
+        assert_eq!(redactions.len(), 1);
+        assert_eq!(redactions[0].rule, "vault");
+        // Clipped to the monitor, halved and rounded outwards
+        assert_eq!(redactions[0].rect, region(0, 50, 40, 25));
+
+        // Window rules fail closed when windows cannot be listed
+        let view = FrameView { windows: None, ..view };
+        assert_eq!(resolve(&rules[..1], &view, 960, 540)[0].rect, region(0, 0, 960, 540));
+    }
+
+    #[test]
+    fn test_rules_update_at_runtime() {
+        let config = CaptureConfig {
+            target_fps: 1000,
+            source: CaptureSource::Synthetic(SyntheticConfig {
+                width: 32,
+                height: 16,
+                pattern: SyntheticPattern::MovingBars,
+            }),
+            ..Default::default()
+        };
+        let redactor = Redactor::default();
+        let mut capture = RedactedCapture::new(Box::new(SyntheticCapture::new(config).unwrap()), redactor.clone());
+        capture.start().unwrap();
+
+        let next = |capture: &mut RedactedCapture| loop {
+            if let Some(frame) = capture.get_frame().unwrap() {
+                break frame;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        let black = |frame: &ScreenFrame| frame.data.chunks_exact(4).all(|px| px == [0, 0, 0, 255]);
+
+        assert!(!black(&next(&mut capture)));
+        redactor.add_rule(RedactionRule::new(
+            "all",
+            RedactionTarget::Region(MonitorBounds::new(0, 0, 32, 16)),
+            RedactionStyle::Blackout,
+        ));
+        let frame = next(&mut capture);
+        assert!(black(&frame));
+        assert!(frame.dirty_rects.as_ref().map_or(true, |dirty| dirty.contains(&region(0, 0, 32, 16))));
+
+        assert!(redactor.remove_rule("all"));
+        assert!(!black(&next(&mut capture)));
+    }
+}
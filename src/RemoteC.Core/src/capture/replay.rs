@@ -0,0 +1,496 @@
+//! File replay screen capture
+//!
+//! Streams previously recorded frames from disk: either a directory of PNG
+//! images, played in file name order with embedded numbers compared by
+//! value (`frame-2.png` before `frame-10.png`), or a YUV4MPEG2 (Y4M) file with 4:2:0
+//! chroma as written by ffmpeg and most encoder test suites.
+
+use super::{CaptureConfig, CaptureSource, FrameFormat, ScreenCapture, ScreenFrame};
+use crate::video::{DecodedPicture, DecoderOutput, PixelFormat};
+use crate::{RemoteCError, Result};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Replay source settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayConfig {
+    /// Directory of PNG files or path of a `.y4m` file
+    pub path: PathBuf,
+    /// Restart from the first frame after the last one
+    pub looping: bool,
+}
+
+impl ReplayConfig {
+    /// Replay `path` once
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            looping: false,
+        }
+    }
+}
+
+/// Frames read from disk
+enum ReplaySource {
+    Png { files: Vec<PathBuf>, next: usize },
+    Y4m(Y4mReader<BufReader<File>>),
+}
+
+impl ReplaySource {
+    fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            let mut files = Vec::new();
+            for entry in std::fs::read_dir(path)? {
+                let file = entry?.path();
+                let is_png = file
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+                if is_png && file.is_file() {
+                    files.push(file);
+                }
+            }
+            if files.is_empty() {
+                return Err(RemoteCError::CaptureError(format!(
+                    "No PNG files in {}",
+                    path.display()
+                )));
+            }
+            files.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+            Ok(ReplaySource::Png { files, next: 0 })
+        } else {
+            let reader = Y4mReader::new(BufReader::new(File::open(path)?))?;
+            Ok(ReplaySource::Y4m(reader))
+        }
+    }
+
+    /// Next frame as `(width, height, bgra)`, `None` at the end
+    fn next_frame(&mut self) -> Result<Option<(u32, u32, Vec<u8>)>> {
+        match self {
+            ReplaySource::Png { files, next } => {
+                let Some(file) = files.get(*next) else {
+                    return Ok(None);
+                };
+                *next += 1;
+                decode_png(file).map(Some)
+            }
+            ReplaySource::Y4m(reader) => {
+                let Some(data) = reader.read_frame()? else {
+                    return Ok(None);
+                };
+                let picture = DecodedPicture::new(reader.width, reader.height, PixelFormat::I420, data)?
+                    .into_output(&DecoderOutput::default())?;
+                Ok(Some((picture.width, picture.height, picture.data)))
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        match self {
+            ReplaySource::Png { next, .. } => *next = 0,
+            ReplaySource::Y4m(reader) => reader.rewind()?,
+        }
+        Ok(())
+    }
+}
+
+/// Screen capture replaying recorded frames
+pub struct ReplayCapture {
+    config: CaptureConfig,
+    settings: ReplayConfig,
+    source: Option<ReplaySource>,
+    /// Size of the first frame, later frames must match
+    size: Option<(u32, u32)>,
+    finished: bool,
+    last_frame: Option<Instant>,
+}
+
+impl ReplayCapture {
+    /// Create a replay capture from a config whose source is
+    /// [`CaptureSource::Replay`]
+    ///
+    /// Frames are paced to `target_fps`; the capture mode and region are
+    /// ignored.
+    pub fn new(config: CaptureConfig) -> Result<Self> {
+        let CaptureSource::Replay(settings) = &config.source else {
+            return Err(RemoteCError::CaptureError("Capture source is not a replay".to_string()));
+        };
+        if !settings.path.exists() {
+            return Err(RemoteCError::CaptureError(format!(
+                "Replay source not found: {}",
+                settings.path.display()
+            )));
+        }
+
+        Ok(Self {
+            settings: settings.clone(),
+            config,
+            source: None,
+            size: None,
+            finished: false,
+            last_frame: None,
+        })
+    }
+
+    /// Whether every frame has been delivered and looping is off
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Minimum time between two frames
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.config.target_fps.max(1)
+    }
+
+    fn read_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        let source = self
+            .source
+            .as_mut()
+            .ok_or_else(|| RemoteCError::CaptureError("Not capturing".to_string()))?;
+
+        let mut next = source.next_frame()?;
+        if next.is_none() && self.settings.looping {
+            source.rewind()?;
+            next = source.next_frame()?;
+        }
+        let Some((width, height, data)) = next else {
+            self.finished = true;
+            return Ok(None);
+        };
+
+        let size = *self.size.get_or_insert((width, height));
+        if size != (width, height) {
+            return Err(RemoteCError::CaptureError(format!(
+                "Replay frame is {}x{}, expected {}x{}",
+                width, height, size.0, size.1
+            )));
+        }
+
+        Ok(Some(ScreenFrame {
+            width,
+            height,
+            // Decoders allocate each frame, so there is nothing to pool
+            data: data.into(),
+            timestamp: Instant::now(),
+            // Recorded frames carry no change information
+            dirty_rects: None,
+            keyframe: false,
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
+        }))
+    }
+}
+
+impl ScreenCapture for ReplayCapture {
+    fn start(&mut self) -> Result<()> {
+        if self.source.is_some() {
+            return Err(RemoteCError::CaptureError("Already capturing".to_string()));
+        }
+
+        self.source = Some(ReplaySource::open(&self.settings.path)?);
+        self.size = None;
+        self.finished = false;
+        self.last_frame = None;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if self.source.take().is_none() {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+        Ok(())
+    }
+
+    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        if self.source.is_none() {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+        if self.finished {
+            return Ok(None);
+        }
+
+        if let Some(last) = self.last_frame {
+            if last.elapsed() < self.frame_interval() {
+                return Ok(None);
+            }
+        }
+
+        let frame = self.read_frame()?;
+        self.last_frame = Some(Instant::now());
+        Ok(frame)
+    }
+
+    fn is_active(&self) -> bool {
+        self.source.is_some()
+    }
+
+    fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+}
+
+/// Decode a PNG file to BGRA
+fn decode_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let png_error = |e: png::DecodingError| {
+        RemoteCError::CaptureError(format!("Failed to decode {}: {}", path.display(), e))
+    };
+
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // Expand palettes and low bit depths, strip 16-bit samples to 8 bits
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut buffer = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(png_error)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let mut bgra = Vec::with_capacity(width * height * 4);
+    for row in buffer.chunks_exact(info.line_size).take(height) {
+        for px in row[..width * channels].chunks_exact(channels) {
+            let pixel = match px {
+                [l] => [*l, *l, *l, 255],
+                [l, a] => [*l, *l, *l, *a],
+                [r, g, b] => [*b, *g, *r, 255],
+                [r, g, b, a] => [*b, *g, *r, *a],
+                _ => unreachable!("PNG pixels have 1 to 4 samples"),
+            };
+            bgra.extend_from_slice(&pixel);
+        }
+    }
+
+    Ok((info.width, info.height, bgra))
+}
+
+/// Reader for YUV4MPEG2 streams with 4:2:0 chroma
+struct Y4mReader<R> {
+    reader: R,
+    width: u32,
+    height: u32,
+    /// Offset of the first frame header
+    data_start: u64,
+}
+
+impl<R: BufRead + Seek> Y4mReader<R> {
+    fn new(mut reader: R) -> Result<Self> {
+        let mut header = Vec::new();
+        reader.read_until(b'\n', &mut header)?;
+        let (width, height) = parse_y4m_header(&String::from_utf8_lossy(&header))?;
+        let data_start = reader.stream_position()?;
+
+        Ok(Self {
+            reader,
+            width,
+            height,
+            data_start,
+        })
+    }
+
+    /// Read the next I420 frame, `None` at the end of the stream
+    fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut header = Vec::new();
+        if self.reader.read_until(b'\n', &mut header)? == 0 {
+            return Ok(None);
+        }
+        if !header.starts_with(b"FRAME") {
+            return Err(RemoteCError::CaptureError("Invalid Y4M frame header".to_string()));
+        }
+
+        let mut data = vec![0u8; PixelFormat::I420.frame_size(self.width, self.height)];
+        self.reader.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        Ok(())
+    }
+}
+
+/// Parse the frame size from a Y4M stream header
+fn parse_y4m_header(header: &str) -> Result<(u32, u32)> {
+    let mut params = header.split_ascii_whitespace();
+    if params.next() != Some("YUV4MPEG2") {
+        return Err(RemoteCError::CaptureError("Not a Y4M file".to_string()));
+    }
+
+    let (mut width, mut height) = (None, None);
+    for param in params {
+        let mut chars = param.chars();
+        let tag = chars.next().filter(char::is_ascii_alphabetic).ok_or_else(|| {
+            RemoteCError::CaptureError(format!("Malformed Y4M header parameter: {}", param))
+        })?;
+        let value = chars.as_str();
+        match tag {
+            'W' => width = value.parse::<u32>().ok(),
+            'H' => height = value.parse::<u32>().ok(),
+            // 4:2:0 variants only differ in chroma siting
+            'C' if !matches!(value, "420" | "420jpeg" | "420paldv" | "420mpeg2") => {
+                return Err(RemoteCError::CaptureError(format!("Unsupported Y4M colorspace: {}", value)));
+            }
+            _ => {}
+        }
+    }
+
+    match (width, height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(RemoteCError::CaptureError("Y4M header lacks a frame size".to_string())),
+    }
+}
+
+/// Compare names with runs of digits ordered by their value
+fn natural_cmp(mut a: &str, mut b: &str) -> Ordering {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (number_a, rest_a) = a.split_at(digits(a));
+                let (number_b, rest_b) = b.split_at(digits(b));
+                let (value_a, value_b) = (number_a.trim_start_matches('0'), number_b.trim_start_matches('0'));
+                let order = value_a
+                    .len()
+                    .cmp(&value_b.len())
+                    .then_with(|| value_a.cmp(value_b))
+                    .then_with(|| number_a.len().cmp(&number_b.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Scratch directory removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("remotec-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write_png(path: &Path, color_type: png::ColorType, data: &[u8]) {
+        let mut encoder = png::Encoder::new(File::create(path).unwrap(), 2, 1);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+    }
+
+    fn y4m(frames: &[[u8; 6]]) -> Vec<u8> {
+        let mut data = b"YUV4MPEG2 W2 H2 F30:1 Ip A1:1 C420jpeg\n".to_vec();
+        for frame in frames {
+            data.extend_from_slice(b"FRAME\n");
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn replay_config(path: &Path, looping: bool) -> CaptureConfig {
+        CaptureConfig {
+            target_fps: 1000,
+            source: CaptureSource::Replay(ReplayConfig { path: path.to_path_buf(), looping }),
+            ..Default::default()
+        }
+    }
+
+    fn next_frame(capture: &mut ReplayCapture) -> Option<ScreenFrame> {
+        std::thread::sleep(Duration::from_millis(2));
+        capture.get_frame().unwrap()
+    }
+
+    #[test]
+    fn test_parse_y4m_header() {
+        assert_eq!(parse_y4m_header("YUV4MPEG2 W1920 H1080 F30000:1001 Ip\n").unwrap(), (1920, 1080));
+        assert!(parse_y4m_header("YUV4MPEG2 W16 H16 C444").is_err());
+        assert!(parse_y4m_header("YUV4MPEG2 W16").is_err());
+        assert!(parse_y4m_header("RIFF W16 H16").is_err());
+        // Corrupt bytes decoded as U+FFFD
+        assert!(parse_y4m_header("YUV4MPEG2 W16 H16 \u{FFFD}x").is_err());
+    }
+
+    #[test]
+    fn test_y4m_reader_frames_and_rewind() {
+        let mut reader = Y4mReader::new(Cursor::new(y4m(&[[16; 6], [235, 235, 235, 235, 128, 128]]))).unwrap();
+        assert_eq!((reader.width, reader.height), (2, 2));
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![16; 6]);
+        assert_eq!(reader.read_frame().unwrap().unwrap()[0], 235);
+        assert!(reader.read_frame().unwrap().is_none());
+
+        reader.rewind().unwrap();
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![16; 6]);
+    }
+
+    #[test]
+    fn test_replay_png_directory() {
+        let dir = TempDir::new("replay-png");
+        // Played in name order regardless of color type
+        write_png(&dir.0.join("frame-1.png"), png::ColorType::Rgb, &[255, 0, 0, 0, 0, 255]);
+        write_png(&dir.0.join("frame-0.png"), png::ColorType::GrayscaleAlpha, &[10, 20, 30, 40]);
+        std::fs::write(dir.0.join("notes.txt"), "ignored").unwrap();
+
+        let mut capture = ReplayCapture::new(replay_config(&dir.0, false)).unwrap();
+        capture.start().unwrap();
+
+        let first = next_frame(&mut capture).unwrap();
+        assert_eq!((first.width, first.height), (2, 1));
+        assert_eq!(first.data[..], [10, 10, 10, 20, 30, 30, 30, 40]);
+        let second = next_frame(&mut capture).unwrap();
+        assert_eq!(second.data[..], [0, 0, 255, 255, 255, 0, 0, 255]);
+
+        assert!(next_frame(&mut capture).is_none());
+        assert!(capture.is_finished());
+    }
+
+    #[test]
+    fn test_natural_file_order() {
+        let mut names = vec!["frame-10.png", "frame-2.png", "frame-02.png", "frame-1.png", "frame-a.png"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["frame-1.png", "frame-2.png", "frame-02.png", "frame-10.png", "frame-a.png"]);
+    }
+
+    #[test]
+    fn test_replay_y4m_file_loops() {
+        let dir = TempDir::new("replay-y4m");
+        let path = dir.0.join("clip.y4m");
+        std::fs::write(&path, y4m(&[[16, 16, 16, 16, 128, 128], [235, 235, 235, 235, 128, 128]])).unwrap();
+
+        let mut capture = ReplayCapture::new(replay_config(&path, true)).unwrap();
+        capture.start().unwrap();
+
+        let shades: Vec<u8> = (0..3).map(|_| next_frame(&mut capture).unwrap().data[0]).collect();
+        assert_eq!(shades, vec![0, 255, 0]);
+        assert!(!capture.is_finished());
+    }
+
+    #[test]
+    fn test_replay_missing_source() {
+        assert!(ReplayCapture::new(replay_config(Path::new("/nonexistent/remotec"), false)).is_err());
+        assert!(ReplayCapture::new(CaptureConfig::default()).is_err());
+    }
+}
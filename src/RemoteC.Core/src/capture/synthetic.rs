@@ -0,0 +1,302 @@
+//! Synthetic screen capture
+//!
+//! Generates deterministic test patterns at a configurable resolution so
+//! the capture and encoding pipeline can run on headless machines and in
+//! demos. Frame `n` of a pattern is always identical, which makes encoder
+//! output reproducible.
+
+use super::{BufferPool, CaptureConfig, CaptureSource, FrameFormat, ScreenCapture, ScreenFrame};
+use crate::{RemoteCError, Result};
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Content generated by [`SyntheticCapture`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyntheticPattern {
+    /// Color bars with a box sweeping across them
+    #[default]
+    MovingBars,
+    /// Lines of word-like blocks scrolling upwards, resembling a document
+    ScrollingText,
+    /// Drifting gradients with per-pixel noise, resembling camera video
+    Noise,
+}
+
+/// Synthetic source settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticConfig {
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Generated content
+    pub pattern: SyntheticPattern,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            pattern: SyntheticPattern::default(),
+        }
+    }
+}
+
+/// Bar colors in BGRA: white, yellow, cyan, green, magenta, red, blue, black
+const BARS: [[u8; 4]; 8] = [
+    [235, 235, 235, 255],
+    [16, 235, 235, 255],
+    [235, 235, 16, 255],
+    [16, 235, 16, 255],
+    [235, 16, 235, 255],
+    [16, 16, 235, 255],
+    [235, 16, 16, 255],
+    [16, 16, 16, 255],
+];
+
+/// Text line height and glyph cell width in pixels
+const LINE_HEIGHT: usize = 16;
+const GLYPH_WIDTH: usize = 6;
+/// Glyph rows within a line, the rest is line spacing
+const GLYPH_HEIGHT: usize = 10;
+
+/// Screen capture producing generated frames
+pub struct SyntheticCapture {
+    config: CaptureConfig,
+    settings: SyntheticConfig,
+    active: bool,
+    frame_index: u64,
+    last_frame: Option<Instant>,
+    pool: BufferPool,
+}
+
+impl SyntheticCapture {
+    /// Create a synthetic capture from a config whose source is
+    /// [`CaptureSource::Synthetic`]
+    ///
+    /// Frames are paced to `target_fps`; the capture mode and region are
+    /// ignored.
+    pub fn new(config: CaptureConfig) -> Result<Self> {
+        let CaptureSource::Synthetic(settings) = config.source else {
+            return Err(RemoteCError::CaptureError("Capture source is not synthetic".to_string()));
+        };
+        if settings.width == 0 || settings.height == 0 {
+            return Err(RemoteCError::CaptureError("Synthetic frame size must be non-zero".to_string()));
+        }
+
+        Ok(Self {
+            config,
+            settings,
+            active: false,
+            frame_index: 0,
+            last_frame: None,
+            pool: BufferPool::default(),
+        })
+    }
+
+    /// Number of frames produced so far
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// Minimum time between two frames
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.config.target_fps.max(1)
+    }
+}
+
+impl ScreenCapture for SyntheticCapture {
+    fn start(&mut self) -> Result<()> {
+        if self.active {
+            return Err(RemoteCError::CaptureError("Already capturing".to_string()));
+        }
+
+        self.last_frame = None;
+        self.active = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if !self.active {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+
+        self.active = false;
+        Ok(())
+    }
+
+    fn get_frame(&mut self) -> Result<Option<ScreenFrame>> {
+        if !self.active {
+            return Err(RemoteCError::CaptureError("Not capturing".to_string()));
+        }
+
+        if let Some(last) = self.last_frame {
+            if last.elapsed() < self.frame_interval() {
+                return Ok(None);
+            }
+        }
+
+        let len = self.settings.width as usize * self.settings.height as usize * 4;
+        let mut data = self.pool.take(len);
+        data.resize(len, 0);
+        render_into(&self.settings, self.frame_index, &mut data);
+
+        let frame = ScreenFrame {
+            width: self.settings.width,
+            height: self.settings.height,
+            data: data.freeze(),
+            timestamp: Instant::now(),
+            // Every pattern changes most of the frame
+            dirty_rects: None,
+            keyframe: false,
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
+        };
+        self.frame_index += 1;
+        self.last_frame = Some(frame.timestamp);
+        Ok(Some(frame))
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+}
+
+/// Render frame `index` of the configured pattern as BGRA
+pub fn render(settings: &SyntheticConfig, index: u64) -> Vec<u8> {
+    let mut data = vec![0u8; settings.width as usize * settings.height as usize * 4];
+    render_into(settings, index, &mut data);
+    data
+}
+
+/// Render frame `index` into a BGRA buffer of exactly the frame size
+fn render_into(settings: &SyntheticConfig, index: u64, data: &mut [u8]) {
+    let (width, height) = (settings.width as usize, settings.height as usize);
+    data.par_chunks_mut(width * 4).enumerate().for_each(|(y, row)| match settings.pattern {
+        SyntheticPattern::MovingBars => bars_row(row, y, width, height, index),
+        SyntheticPattern::ScrollingText => text_row(row, y, width, index),
+        SyntheticPattern::Noise => noise_row(row, y, index),
+    });
+}
+
+fn bars_row(row: &mut [u8], y: usize, width: usize, height: usize, index: u64) {
+    let size = (width.min(height) / 8).max(1);
+    let top = height.saturating_sub(size) / 2;
+    let left = (index as usize * 8) % width;
+    let in_box_row = y >= top && y < top + size;
+
+    for (x, px) in row.chunks_exact_mut(4).enumerate() {
+        // The box wraps around the right edge
+        let in_box = in_box_row && (x + width - left) % width < size;
+        let color = if in_box { [255, 255, 255, 255] } else { BARS[x * BARS.len() / width] };
+        px.copy_from_slice(&color);
+    }
+}
+
+fn text_row(row: &mut [u8], y: usize, width: usize, index: u64) {
+    row.fill(255);
+
+    let y = y + index as usize * 2;
+    let (line, line_row) = (y / LINE_HEIGHT, y % LINE_HEIGHT);
+    if line_row >= GLYPH_HEIGHT {
+        return;
+    }
+
+    // Lay out words of 2 to 11 glyphs separated by one empty cell
+    let mut seed = mix(line as u64);
+    let mut x = GLYPH_WIDTH;
+    let mut glyph = 0u64;
+    while x + GLYPH_WIDTH < width {
+        seed = mix(seed);
+        let glyphs = 2 + (seed % 10) as usize;
+        for _ in 0..glyphs {
+            if x + GLYPH_WIDTH >= width {
+                break;
+            }
+            let bits = mix(mix(line as u64) ^ glyph) >> (line_row * 5);
+            for column in 0..GLYPH_WIDTH - 1 {
+                if bits & (1 << column) != 0 {
+                    row[(x + column) * 4..(x + column) * 4 + 3].fill(32);
+                }
+            }
+            x += GLYPH_WIDTH;
+            glyph += 1;
+        }
+        x += GLYPH_WIDTH;
+    }
+}
+
+fn noise_row(row: &mut [u8], y: usize, index: u64) {
+    let t = index as usize;
+    let mut seed = mix((index << 32) | y as u64);
+
+    for (x, px) in row.chunks_exact_mut(4).enumerate() {
+        seed = mix(seed);
+        let noise = (seed & 31) as i32 - 16;
+        let base = [(x + y) / 2 + t, y + t * 2, x + t * 3];
+        for (channel, value) in px[..3].iter_mut().zip(base) {
+            *channel = ((value & 255) as i32 + noise).clamp(0, 255) as u8;
+        }
+        px[3] = 255;
+    }
+}
+
+/// SplitMix64 step, used as a cheap deterministic hash
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(pattern: SyntheticPattern) -> SyntheticConfig {
+        SyntheticConfig { width: 64, height: 48, pattern }
+    }
+
+    #[test]
+    fn test_patterns_are_deterministic_and_animated() {
+        for pattern in [SyntheticPattern::MovingBars, SyntheticPattern::ScrollingText, SyntheticPattern::Noise] {
+            let settings = settings(pattern);
+            let first = render(&settings, 3);
+            assert_eq!(first.len(), 64 * 48 * 4);
+            assert_eq!(first, render(&settings, 3));
+            assert_ne!(first, render(&settings, 4), "{:?} does not move", pattern);
+            assert!(first.chunks(4).all(|px| px[3] == 255));
+        }
+    }
+
+    #[test]
+    fn test_scrolling_text_scrolls_up() {
+        let settings = settings(SyntheticPattern::ScrollingText);
+        let row = 64 * 4;
+        // Two pixels per frame
+        assert_eq!(render(&settings, 0)[2 * row..], render(&settings, 1)[..46 * row]);
+    }
+
+    #[test]
+    fn test_synthetic_capture_frames() {
+        let config = CaptureConfig {
+            target_fps: 1000,
+            source: CaptureSource::Synthetic(settings(SyntheticPattern::MovingBars)),
+            ..Default::default()
+        };
+        let mut capture = SyntheticCapture::new(config).unwrap();
+        assert!(capture.get_frame().is_err());
+
+        capture.start().unwrap();
+        let frame = capture.get_frame().unwrap().expect("first frame is immediate");
+        assert_eq!((frame.width, frame.height), (64, 48));
+        assert_eq!(capture.frame_index(), 1);
+
+        assert!(SyntheticCapture::new(CaptureConfig::default()).is_err());
+    }
+}
@@ -30,8 +30,12 @@ fn test_screen_frame() {
     let frame = ScreenFrame {
         width: 1920,
         height: 1080,
-        data: vec![0; 1920 * 1080 * 4], // BGRA format
+        data: vec![0; 1920 * 1080 * 4].into(), // BGRA format
         timestamp: std::time::Instant::now(),
+        dirty_rects: None,
+        keyframe: false,
+        format: FrameFormat::Bgra8888,
+        change_ratio: None,
     };
     
     assert_eq!(frame.width, 1920);
@@ -61,8 +65,12 @@ impl MockCapture {
         self.frames.push(ScreenFrame {
             width,
             height,
-            data: vec![0; (width * height * 4) as usize],
+            data: vec![0; (width * height * 4) as usize].into(),
             timestamp: std::time::Instant::now(),
+            dirty_rects: None,
+            keyframe: false,
+            format: FrameFormat::Bgra8888,
+            change_ratio: None,
         });
     }
 }
@@ -0,0 +1,173 @@
+//! Top-level window enumeration and lookup
+//!
+//! Used by [`CaptureMode::Window`](super::CaptureMode::Window) to capture a
+//! single window and by
+//! [`CaptureMode::WindowMonitor`](super::CaptureMode::WindowMonitor) to find
+//! the monitor showing it. Windows are named by title or by their native id.
+
+use super::monitor::{MonitorBounds, VirtualDesktop};
+use crate::{RemoteCError, Result};
+
+/// Top-level application window
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowInfo {
+    /// Native window id (X11 window XID)
+    pub id: u64,
+    /// Window title, empty if unnamed
+    pub title: String,
+    /// Owning process, if the window advertises it
+    pub pid: Option<u32>,
+    /// Position and size of the window contents in desktop coordinates
+    pub bounds: MonitorBounds,
+    /// Whether the window is mapped (not minimized or withdrawn)
+    pub visible: bool,
+}
+
+/// List the top-level windows of the desktop session
+///
+/// Opens a new display connection for every call; use a
+/// [`WindowEnumerator`] to list windows repeatedly.
+pub fn enumerate_windows() -> Result<Vec<WindowInfo>> {
+    WindowEnumerator::new().enumerate()
+}
+
+/// Lists top-level windows over one persistent display connection
+///
+/// The connection is opened on first use and reopened after a failure.
+#[derive(Default)]
+pub struct WindowEnumerator {
+    #[cfg(all(target_os = "linux", feature = "x11"))]
+    display: Option<super::linux::x11::X11Display>,
+}
+
+impl WindowEnumerator {
+    /// Create an enumerator without connecting yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// List the top-level windows of the desktop session
+    pub fn enumerate(&mut self) -> Result<Vec<WindowInfo>> {
+        #[cfg(all(target_os = "linux", feature = "x11"))]
+        {
+            let display = match self.display.take() {
+                Some(display) => display,
+                None => super::linux::x11::X11Display::open(None)?,
+            };
+            let windows = super::linux::window::enumerate_windows_x11(&display)?;
+            self.display = Some(display);
+            Ok(windows)
+        }
+
+        #[cfg(not(all(target_os = "linux", feature = "x11")))]
+        Err(RemoteCError::NotImplemented(
+            "Window enumeration is only available on X11".to_string()
+        ))
+    }
+}
+
+// SAFETY: the enumerator exclusively owns its display connection and only
+// uses it through `&mut self`, so at most one thread touches it at a time.
+#[cfg(all(target_os = "linux", feature = "x11"))]
+unsafe impl Send for WindowEnumerator {}
+#[cfg(all(target_os = "linux", feature = "x11"))]
+unsafe impl Sync for WindowEnumerator {}
+
+/// Find the window named by `query`
+///
+/// `query` is matched against window ids (decimal or `0x` hexadecimal),
+/// then exact titles, then case-insensitive title substrings. Visible
+/// windows win over hidden ones with the same match.
+pub fn find_window<'a>(windows: &'a [WindowInfo], query: &str) -> Option<&'a WindowInfo> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let id = match query.strip_prefix("0x").or_else(|| query.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => query.parse::<u64>().ok(),
+    };
+    if let Some(window) = id.and_then(|id| windows.iter().find(|w| w.id == id)) {
+        return Some(window);
+    }
+
+    let best = |matches: &dyn Fn(&WindowInfo) -> bool| {
+        windows.iter().filter(|w| matches(w)).max_by_key(|w| w.visible)
+    };
+    let needle = query.to_lowercase();
+    best(&|w| w.title == query).or_else(|| best(&|w| w.title.to_lowercase().contains(&needle)))
+}
+
+/// Index of the monitor showing most of `window`
+pub fn window_monitor_index(desktop: &VirtualDesktop, window: &WindowInfo) -> Option<usize> {
+    let area = |b: MonitorBounds| u64::from(b.width) * u64::from(b.height);
+
+    desktop
+        .monitors
+        .iter()
+        .enumerate()
+        .filter_map(|(index, monitor)| {
+            let overlap = monitor.bounds.intersection(&window.bounds)?;
+            Some((index, area(overlap)))
+        })
+        .max_by_key(|&(_, overlap)| overlap)
+        .map(|(index, _)| index)
+}
+
+/// Resolve the monitor showing the window named by `query`
+pub fn resolve_window_monitor(desktop: &VirtualDesktop, query: &str) -> Result<usize> {
+    let windows = enumerate_windows()?;
+    let window = find_window(&windows, query)
+        .ok_or_else(|| RemoteCError::CaptureError(format!("Window not found: {}", query)))?;
+    window_monitor_index(desktop, window).ok_or_else(|| {
+        RemoteCError::CaptureError(format!("Window '{}' is not on any monitor", window.title))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::monitor::Monitor;
+
+    fn window(id: u64, title: &str, x: i32, visible: bool) -> WindowInfo {
+        WindowInfo {
+            id,
+            title: title.to_string(),
+            pid: None,
+            bounds: MonitorBounds::new(x, 100, 800, 600),
+            visible,
+        }
+    }
+
+    #[test]
+    fn test_find_window_by_id_and_title() {
+        let windows = vec![
+            window(0x3a00007, "Terminal", 0, true),
+            window(0x4200001, "Firefox - Mozilla", 0, false),
+            window(0x4200002, "firefox", 0, true),
+        ];
+
+        assert_eq!(find_window(&windows, "0x3a00007").unwrap().id, 0x3a00007);
+        assert_eq!(find_window(&windows, "60817415").unwrap().id, 0x3a00007);
+        // Exact title beats substring matches
+        assert_eq!(find_window(&windows, "firefox").unwrap().id, 0x4200002);
+        // Visible windows win among substring matches
+        assert_eq!(find_window(&windows, "FIREFOX").unwrap().id, 0x4200002);
+        assert_eq!(find_window(&windows, "mozilla").unwrap().id, 0x4200001);
+        assert!(find_window(&windows, "Editor").is_none());
+        assert!(find_window(&windows, "").is_none());
+    }
+
+    #[test]
+    fn test_window_monitor_is_largest_overlap() {
+        let desktop = VirtualDesktop::new(vec![
+            Monitor::test(0, MonitorBounds::new(0, 0, 1920, 1080)),
+            Monitor::test(1, MonitorBounds::new(1920, 0, 1920, 1080)),
+        ]).unwrap();
+
+        assert_eq!(window_monitor_index(&desktop, &window(1, "a", 100, true)), Some(0));
+        // 300 px on the first monitor, 500 px on the second
+        assert_eq!(window_monitor_index(&desktop, &window(1, "a", 1620, true)), Some(1));
+        assert_eq!(window_monitor_index(&desktop, &window(1, "a", 5000, true)), None);
+    }
+}
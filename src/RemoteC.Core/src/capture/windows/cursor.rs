@@ -0,0 +1,204 @@
+//! Windows cursor source using `GetCursorInfo`
+//!
+//! The shape is only read when the cursor handle changes. Color cursors are
+//! read as 32-bit bitmaps; monochrome cursors store an AND mask above an
+//! XOR mask in a single double-height bitmap.
+
+use crate::capture::cursor::{CursorSample, CursorShape, CursorSource};
+use crate::{RemoteCError, Result};
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::windef::{HBITMAP, HCURSOR, HDC};
+use winapi::um::wingdi::{
+    DeleteObject, GetDIBits, GetObjectW, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+};
+use winapi::um::winuser::{GetCursorInfo, GetDC, GetIconInfo, ReleaseDC, CURSORINFO, CURSOR_SHOWING, ICONINFO};
+
+/// Cursor shape and position read through the Win32 cursor API
+#[derive(Default)]
+pub struct WindowsCursorSource {
+    /// Handle of the last reported shape, kept as an integer for comparison
+    last_cursor: Option<usize>,
+}
+
+impl WindowsCursorSource {
+    /// Create a cursor source that reports the current shape first
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CursorSource for WindowsCursorSource {
+    fn sample(&mut self) -> Result<CursorSample> {
+        unsafe {
+            let mut info: CURSORINFO = std::mem::zeroed();
+            info.cbSize = std::mem::size_of::<CURSORINFO>() as DWORD;
+            if GetCursorInfo(&mut info) == 0 {
+                return Err(RemoteCError::CaptureError("GetCursorInfo failed".to_string()));
+            }
+
+            let visible = info.flags & CURSOR_SHOWING != 0 && !info.hCursor.is_null();
+            let handle = info.hCursor as usize;
+            let shape = if visible && self.last_cursor != Some(handle) {
+                let shape = read_cursor_shape(info.hCursor)?;
+                self.last_cursor = Some(handle);
+                Some(shape)
+            } else {
+                None
+            };
+
+            Ok(CursorSample {
+                x: info.ptScreenPos.x,
+                y: info.ptScreenPos.y,
+                visible,
+                shape,
+            })
+        }
+    }
+}
+
+/// Read the image and hotspot of `cursor`
+unsafe fn read_cursor_shape(cursor: HCURSOR) -> Result<CursorShape> {
+    let mut icon: ICONINFO = std::mem::zeroed();
+    if GetIconInfo(cursor, &mut icon) == 0 {
+        return Err(RemoteCError::CaptureError("GetIconInfo failed".to_string()));
+    }
+
+    let dc = GetDC(std::ptr::null_mut());
+    let result = read_icon_bitmaps(dc, &icon);
+    ReleaseDC(std::ptr::null_mut(), dc);
+
+    // GetIconInfo hands out copies of the bitmaps
+    if !icon.hbmColor.is_null() {
+        DeleteObject(icon.hbmColor as *mut _);
+    }
+    if !icon.hbmMask.is_null() {
+        DeleteObject(icon.hbmMask as *mut _);
+    }
+
+    let (width, height, data) = result?;
+    CursorShape::new(width, height, icon.xHotspot, icon.yHotspot, data)
+}
+
+unsafe fn read_icon_bitmaps(dc: HDC, icon: &ICONINFO) -> Result<(u32, u32, Vec<u8>)> {
+    if icon.hbmColor.is_null() {
+        let (width, mask_height) = bitmap_size(icon.hbmMask)?;
+        let height = mask_height / 2;
+        let mask = bitmap_bgra(dc, icon.hbmMask, width, mask_height)?;
+        Ok((width, height, monochrome_cursor_to_bgra(&mask, width, height)))
+    } else {
+        let (width, height) = bitmap_size(icon.hbmColor)?;
+        let mut color = bitmap_bgra(dc, icon.hbmColor, width, height)?;
+        let mask = bitmap_bgra(dc, icon.hbmMask, width, height)?;
+        apply_cursor_mask(&mut color, &mask);
+        Ok((width, height, color))
+    }
+}
+
+unsafe fn bitmap_size(bitmap: HBITMAP) -> Result<(u32, u32)> {
+    let mut info: BITMAP = std::mem::zeroed();
+    let size = std::mem::size_of::<BITMAP>() as i32;
+    if GetObjectW(bitmap as *mut _, size, &mut info as *mut BITMAP as *mut _) == 0 {
+        return Err(RemoteCError::CaptureError("Failed to query cursor bitmap".to_string()));
+    }
+    Ok((info.bmWidth.max(0) as u32, info.bmHeight.max(0) as u32))
+}
+
+/// Read a bitmap as top-down 32-bit BGRA; 1-bit bitmaps expand to black and white
+unsafe fn bitmap_bgra(dc: HDC, bitmap: HBITMAP, width: u32, height: u32) -> Result<Vec<u8>> {
+    let mut bitmap_info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as DWORD,
+            biWidth: width as i32,
+            biHeight: -(height as i32), // Negative for top-down
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        bmiColors: [std::mem::zeroed()],
+    };
+
+    let mut buffer = vec![0u8; (width * height * 4) as usize];
+    let scan_lines = GetDIBits(
+        dc,
+        bitmap,
+        0,
+        height,
+        buffer.as_mut_ptr() as *mut _,
+        &mut bitmap_info,
+        DIB_RGB_COLORS,
+    );
+    if scan_lines == 0 {
+        return Err(RemoteCError::CaptureError("GetDIBits failed for cursor".to_string()));
+    }
+    Ok(buffer)
+}
+
+/// Derive alpha from the AND mask for color cursors without an alpha channel
+fn apply_cursor_mask(color: &mut [u8], mask: &[u8]) {
+    if color.chunks_exact(4).any(|px| px[3] != 0) {
+        return;
+    }
+    for (px, and) in color.chunks_exact_mut(4).zip(mask.chunks_exact(4)) {
+        px[3] = if and[0] == 0 { 255 } else { 0 };
+    }
+}
+
+/// Convert a monochrome AND/XOR mask pair to BGRA
+///
+/// Pixels that invert the screen cannot be expressed with alpha and are
+/// drawn black.
+fn monochrome_cursor_to_bgra(mask: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let plane = (width * height * 4) as usize;
+    let (and, xor) = mask.split_at(plane);
+
+    let mut out = Vec::with_capacity(plane);
+    for (and, xor) in and.chunks_exact(4).zip(xor.chunks_exact(4)) {
+        let pixel = match (and[0] != 0, xor[0] != 0) {
+            (false, false) => [0, 0, 0, 255],
+            (false, true) => [255, 255, 255, 255],
+            (true, false) => [0, 0, 0, 0],
+            (true, true) => [0, 0, 0, 255],
+        };
+        out.extend_from_slice(&pixel);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monochrome_cursor_masks() {
+        // 4x1 cursor: AND row above XOR row
+        let white = [255, 255, 255, 0];
+        let black = [0, 0, 0, 0];
+        let mask = [black, black, white, white, black, white, black, white].concat();
+
+        let out = monochrome_cursor_to_bgra(&mask, 4, 1);
+        assert_eq!(out, [[0, 0, 0, 255], [255, 255, 255, 255], [0, 0, 0, 0], [0, 0, 0, 255]].concat());
+    }
+
+    #[test]
+    fn test_color_cursor_alpha_from_mask() {
+        let mut color = vec![10, 20, 30, 0, 40, 50, 60, 0];
+        apply_cursor_mask(&mut color, &[0, 0, 0, 0, 255, 255, 255, 0]);
+        assert_eq!(color, vec![10, 20, 30, 255, 40, 50, 60, 0]);
+
+        // Existing alpha is kept
+        let mut color = vec![10, 20, 30, 128];
+        apply_cursor_mask(&mut color, &[255, 255, 255, 0]);
+        assert_eq!(color[3], 128);
+    }
+
+    #[test]
+    fn test_sample_cursor() {
+        let sample = WindowsCursorSource::new().sample();
+        assert!(sample.is_ok());
+    }
+}
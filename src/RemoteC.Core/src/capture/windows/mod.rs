@@ -1,10 +1,16 @@
 //! Windows-specific screen capture implementation
 
+mod cursor;
 mod monitor;
+pub use cursor::WindowsCursorSource;
 pub use monitor::{enumerate_monitors_windows, get_monitor_at_point_windows};
 
+use crate::capture::compositor::{Compositor, CompositorConfig};
+use crate::capture::cursor::{CursorChannel, CursorEvent};
+use crate::capture::hotplug::{self, DisplayWatcher};
+use crate::capture::monitor::MonitorBounds;
 use crate::capture::{
-    CaptureConfig, CaptureMode, ScreenCapture, ScreenFrame,
+    BufferPool, CaptureConfig, CaptureMode, CursorMode, FrameFormat, ScreenCapture, ScreenFrame,
 };
 use crate::{RemoteCError, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -26,6 +32,14 @@ pub struct WindowsCapture {
     bitmap: Option<HBITMAP>,
     capture_width: u32,
     capture_height: u32,
+    cursor: Option<CursorChannel>,
+    pool: BufferPool,
+    /// Selected monitors grabbed one by one and stitched together
+    composed: Vec<(usize, MonitorBounds)>,
+    compositor: Option<Compositor>,
+    watcher: Option<DisplayWatcher>,
+    /// Mark the next delivered frame as a keyframe
+    keyframe_pending: bool,
 }
 
 impl WindowsCapture {
@@ -34,6 +48,8 @@ impl WindowsCapture {
         // Get virtual desktop info to determine capture dimensions
         let desktop = super::monitor::get_virtual_desktop()?;
         
+        let mut composed = Vec::new();
+        let mut compositor = None;
         let (capture_width, capture_height) = match &config.mode {
             CaptureMode::SingleMonitor(idx) => {
                 let monitor = desktop.get_monitor(*idx)
@@ -48,28 +64,28 @@ impl WindowsCapture {
                 (desktop.total_bounds.width, desktop.total_bounds.height)
             }
             CaptureMode::SelectedMonitors(indices) => {
-                // Calculate combined bounds
-                let mut min_x = i32::MAX;
-                let mut min_y = i32::MAX;
-                let mut max_x = i32::MIN;
-                let mut max_y = i32::MIN;
-                
-                for idx in indices {
-                    if let Some(monitor) = desktop.get_monitor(*idx) {
-                        min_x = min_x.min(monitor.bounds.x);
-                        min_y = min_y.min(monitor.bounds.y);
-                        max_x = max_x.max(monitor.bounds.x + monitor.bounds.width as i32);
-                        max_y = max_y.max(monitor.bounds.y + monitor.bounds.height as i32);
+                compositor = Some(Compositor::new(&desktop, indices, CompositorConfig::default())?);
+
+                for &idx in indices {
+                    if let Some(monitor) = desktop.get_monitor(idx) {
+                        composed.push((idx, monitor.bounds));
                     }
                 }
-                
-                ((max_x - min_x) as u32, (max_y - min_y) as u32)
+                // The capture bitmap only needs to hold one monitor at a time
+                let width = composed.iter().map(|(_, b)| b.width).max().unwrap_or(0);
+                let height = composed.iter().map(|(_, b)| b.height).max().unwrap_or(0);
+                (width, height)
             }
             CaptureMode::WindowMonitor(_) => {
                 // Default to primary monitor for now
                 let monitor = desktop.primary_monitor();
                 (monitor.bounds.width, monitor.bounds.height)
             }
+            CaptureMode::Window(_) => {
+                return Err(RemoteCError::NotImplemented(
+                    "Window capture is not implemented on Windows".to_string(),
+                ));
+            }
         };
         
         Ok(Self {
@@ -80,6 +96,12 @@ impl WindowsCapture {
             bitmap: None,
             capture_width,
             capture_height,
+            cursor: None,
+            pool: BufferPool::default(),
+            composed,
+            compositor,
+            watcher: None,
+            keyframe_pending: false,
         })
     }
 
@@ -138,17 +160,49 @@ impl WindowsCapture {
         }
     }
 
-    /// Capture a single frame
-    fn capture_frame(&self) -> Result<ScreenFrame> {
-        if self.desktop_dc.is_none() || self.memory_dc.is_none() || self.bitmap.is_none() {
-            return Err(RemoteCError::CaptureError(
-                "Capture not initialized".to_string(),
-            ));
+    /// Rebuild capture resources for the current display layout
+    fn reinitialize(&mut self) -> Result<()> {
+        self.cleanup();
+        let mut fresh = Self::new(self.config.clone())?;
+        self.capture_width = fresh.capture_width;
+        self.capture_height = fresh.capture_height;
+        self.composed = std::mem::take(&mut fresh.composed);
+        self.compositor = fresh.compositor.take();
+        self.init_capture()?;
+
+        if let Some(cursor) = self.cursor.as_mut() {
+            cursor.set_origin(self.capture_origin()?);
         }
+        self.keyframe_pending = true;
+        Ok(())
+    }
+
+    /// Reinitialize if the display layout changed since the last call
+    fn handle_display_changes(&mut self) -> Result<()> {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return Ok(());
+        };
+        let changes = match watcher.poll() {
+            Ok(changes) => changes,
+            Err(e) => {
+                log::warn!("Failed to check the display configuration: {}", e);
+                return Ok(());
+            }
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        for change in &changes {
+            log::info!("{}", change);
+        }
+        self.reinitialize()
+    }
 
-        // Determine source coordinates based on capture mode
+    /// Desktop coordinates of the captured area's top-left corner
+    fn capture_origin(&self) -> Result<(i32, i32)> {
         let desktop = super::monitor::get_virtual_desktop()?;
-        let (src_x, src_y) = match &self.config.mode {
+        let origin = match &self.config.mode {
             CaptureMode::SingleMonitor(idx) => {
                 let monitor = desktop.get_monitor(*idx)
                     .ok_or_else(|| RemoteCError::CaptureError("Invalid monitor index".to_string()))?;
@@ -159,12 +213,43 @@ impl WindowsCapture {
                 (monitor.bounds.x, monitor.bounds.y)
             }
             CaptureMode::AllMonitors => (desktop.total_bounds.x, desktop.total_bounds.y),
-            CaptureMode::SelectedMonitors(_) => {
-                // TODO: Handle multiple monitors
-                (0, 0)
-            }
-            CaptureMode::WindowMonitor(_) => (0, 0),
+            CaptureMode::SelectedMonitors(_) => self
+                .compositor
+                .as_ref()
+                .map_or((0, 0), Compositor::origin),
+            CaptureMode::WindowMonitor(_) | CaptureMode::Window(_) => (0, 0),
         };
+        Ok(origin)
+    }
+
+    /// Capture a single frame
+    fn capture_frame(&mut self) -> Result<ScreenFrame> {
+        if self.compositor.is_none() {
+            let (src_x, src_y) = self.capture_origin()?;
+            return self.grab(src_x, src_y, self.capture_width, self.capture_height);
+        }
+
+        // Grabbing monitors one by one keeps unselected monitors and the
+        // gaps between them out of the canvas
+        let frames = self
+            .composed
+            .iter()
+            .map(|&(index, b)| Ok((index, self.grab(b.x, b.y, b.width, b.height)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let frames: Vec<(usize, &ScreenFrame)> = frames.iter().map(|(index, frame)| (*index, frame)).collect();
+        match self.compositor.as_mut() {
+            Some(compositor) => compositor.compose(&frames),
+            None => Err(RemoteCError::CaptureError("Compositor not initialized".to_string())),
+        }
+    }
+
+    /// Grab `width` x `height` desktop pixels at `(src_x, src_y)`
+    fn grab(&self, src_x: i32, src_y: i32, width: u32, height: u32) -> Result<ScreenFrame> {
+        if self.desktop_dc.is_none() || self.memory_dc.is_none() || self.bitmap.is_none() {
+            return Err(RemoteCError::CaptureError(
+                "Capture not initialized".to_string(),
+            ));
+        }
 
         unsafe {
             // Copy screen to memory DC
@@ -172,8 +257,8 @@ impl WindowsCapture {
                 self.memory_dc.unwrap(),
                 0,
                 0,
-                self.capture_width as i32,
-                self.capture_height as i32,
+                width as i32,
+                height as i32,
                 self.desktop_dc.unwrap(),
                 src_x,
                 src_y,
@@ -206,7 +291,8 @@ impl WindowsCapture {
 
             // Calculate buffer size
             let buffer_size = (self.capture_width * self.capture_height * 4) as usize;
-            let mut buffer = vec![0u8; buffer_size];
+            let mut buffer = self.pool.take(buffer_size);
+            buffer.resize(buffer_size, 0);
 
             // Get bitmap bits
             let scan_lines = GetDIBits(
@@ -225,11 +311,25 @@ impl WindowsCapture {
                 ));
             }
 
+            // Monitors smaller than the bitmap only fill its top-left corner
+            if (width, height) != (self.capture_width, self.capture_height) {
+                let (row_len, stride) = (width as usize * 4, self.capture_width as usize * 4);
+                for y in 0..height as usize {
+                    buffer.copy_within(y * stride..y * stride + row_len, y * row_len);
+                }
+                buffer.truncate(row_len * height as usize);
+            }
+
             Ok(ScreenFrame {
-                width: self.capture_width,
-                height: self.capture_height,
-                data: buffer,
+                width,
+                height,
+                data: buffer.freeze(),
                 timestamp: std::time::Instant::now(),
+                // GDI BitBlt has no change tracking
+                dirty_rects: None,
+                keyframe: false,
+                format: FrameFormat::Bgra8888,
+                change_ratio: None,
             })
         }
     }
@@ -242,12 +342,20 @@ impl ScreenCapture for WindowsCapture {
         }
 
         self.init_capture()?;
+        if self.config.capture_cursor && self.config.cursor_mode == CursorMode::Separate {
+            let origin = self.capture_origin()?;
+            self.cursor = Some(CursorChannel::new(Box::new(WindowsCursorSource::new()), origin));
+        }
+        self.watcher = hotplug::watch_displays();
+        self.keyframe_pending = false;
         self.is_active.store(true, Ordering::Relaxed);
         Ok(())
     }
 
     fn stop(&mut self) -> Result<()> {
         self.is_active.store(false, Ordering::Relaxed);
+        self.cursor = None;
+        self.watcher = None;
         self.cleanup();
         Ok(())
     }
@@ -257,7 +365,17 @@ impl ScreenCapture for WindowsCapture {
             return Ok(None);
         }
 
-        Ok(Some(self.capture_frame()?))
+        self.handle_display_changes()?;
+        let mut frame = self.capture_frame()?;
+        frame.keyframe |= std::mem::take(&mut self.keyframe_pending);
+        Ok(Some(frame))
+    }
+
+    fn cursor_events(&mut self) -> Result<Vec<CursorEvent>> {
+        match self.cursor.as_mut() {
+            Some(cursor) => cursor.poll(),
+            None => Ok(Vec::new()),
+        }
     }
 
     fn is_active(&self) -> bool {
@@ -4,6 +4,7 @@ use std::ffi::{c_char, CString};
 use std::ptr;
 use tokio::runtime::Runtime;
 use once_cell::sync::Lazy;
+use crate::input::InputSimulator;
 
 /// Global runtime for async operations
 static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
@@ -34,6 +35,12 @@ pub struct TransportHandle {
     _private: [u8; 0],
 }
 
+/// Frame pixel buffer shared with the caller
+#[repr(C)]
+pub struct FrameBufferHandle {
+    _private: [u8; 0],
+}
+
 /// Video encoder handle
 #[repr(C)]
 pub struct EncoderHandle {
@@ -48,6 +55,11 @@ pub struct FrameData {
     pub data: *const u8,
     pub data_len: usize,
     pub timestamp: u64,
+    /// Keeps `data` alive until `remotec_frame_release`
+    pub buffer: *mut FrameBufferHandle,
+    /// Pixel layout of `data`: 0 = BGRA (32-bit), 1 = BGR (24-bit),
+    /// 2 = RGB565 (16-bit)
+    pub format: u32,
 }
 
 /// FFI-safe input event
@@ -109,7 +121,16 @@ pub unsafe extern "C" fn remotec_free_string(s: *mut c_char) {
 /// Create a screen capture instance
 #[no_mangle]
 pub extern "C" fn remotec_capture_create() -> *mut CaptureHandle {
-    match crate::capture::create_capture(crate::capture::CaptureConfig::default()) {
+    use crate::capture::{CaptureConfig, CaptureQuality, CursorMode};
+    
+    let config = CaptureConfig {
+        // Cursor events are not exposed through the FFI
+        cursor_mode: CursorMode::Embedded,
+        // Callers poll for whole frames and keep no previous one to diff against
+        quality: CaptureQuality { enable_frame_diff: false, ..Default::default() },
+        ..Default::default()
+    };
+    match crate::capture::create_capture(config) {
         Ok(capture) => {
             let boxed = Box::new(capture);
             Box::into_raw(boxed) as *mut CaptureHandle
@@ -140,6 +161,9 @@ pub unsafe extern "C" fn remotec_capture_start(handle: *mut CaptureHandle) -> i3
 }
 
 /// Get a frame from screen capture
+///
+/// On success `frame_data` points into the captured frame without copying
+/// it; release it with `remotec_frame_release` once done.
 #[no_mangle]
 pub unsafe extern "C" fn remotec_capture_get_frame(
     handle: *mut CaptureHandle,
@@ -157,13 +181,32 @@ pub unsafe extern "C" fn remotec_capture_get_frame(
             (*frame_data).data = frame.data.as_ptr();
             (*frame_data).data_len = frame.data.len();
             (*frame_data).timestamp = frame.timestamp.elapsed().as_micros() as u64;
+            (*frame_data).buffer = Box::into_raw(Box::new(frame.data)) as *mut FrameBufferHandle;
+            (*frame_data).format = match frame.format {
+                crate::capture::FrameFormat::Bgra8888 => 0,
+                crate::capture::FrameFormat::Bgr888 => 1,
+                crate::capture::FrameFormat::Rgb565 => 2,
+            };
             0
         }
-        Ok(None) => 1, // No frame available
+        Ok(None) => 1, // No new frame yet or the screen is unchanged
         Err(_) => -1,
     }
 }
 
+/// Release the pixels of a frame returned by `remotec_capture_get_frame`
+#[no_mangle]
+pub unsafe extern "C" fn remotec_frame_release(frame_data: *mut FrameData) {
+    if frame_data.is_null() || (*frame_data).buffer.is_null() {
+        return;
+    }
+
+    let _ = Box::from_raw((*frame_data).buffer as *mut crate::capture::FrameBuffer);
+    (*frame_data).buffer = ptr::null_mut();
+    (*frame_data).data = ptr::null();
+    (*frame_data).data_len = 0;
+}
+
 /// Stop screen capture
 #[no_mangle]
 pub unsafe extern "C" fn remotec_capture_stop(handle: *mut CaptureHandle) -> i32 {
@@ -186,19 +229,106 @@ pub unsafe extern "C" fn remotec_capture_destroy(handle: *mut CaptureHandle) {
     }
 }
 
+/// Transform for frames captured with `config` on the current desktop
+fn capture_transform(config: &crate::capture::CaptureConfig) -> crate::Result<crate::capture::CoordinateTransform> {
+    let desktop = crate::capture::monitor::get_virtual_desktop()?;
+    crate::capture::CoordinateTransform::new(config, &desktop)
+}
+
 /// Create an input simulator instance
+///
+/// Mouse positions are desktop coordinates until `remotec_input_set_capture`
+/// maps them from the viewer.
 #[no_mangle]
 pub extern "C" fn remotec_input_create() -> *mut InputHandle {
-    match crate::input::create_simulator() {
+    match crate::input::create_simulator().map(crate::input::MappedInputSimulator::unmapped) {
         Ok(simulator) => {
             let boxed = Box::new(simulator);
             Box::into_raw(boxed) as *mut InputHandle
         }
-        Err(_) => ptr::null_mut(),
+        Err(e) => {
+            log::error!("Failed to create input simulator: {}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Map mouse positions onto the area shown by `capture`
+///
+/// Resets the viewer and stream sizes to the captured area's size.
+#[no_mangle]
+pub unsafe extern "C" fn remotec_input_set_capture(
+    handle: *mut InputHandle,
+    capture: *mut CaptureHandle,
+) -> i32 {
+    if handle.is_null() || capture.is_null() {
+        return -1;
+    }
+
+    let capture = &*(capture as *mut Box<dyn crate::capture::ScreenCapture>);
+    match capture_transform(capture.config()) {
+        Ok(transform) => {
+            let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
+            simulator.set_transform(transform);
+            0
+        }
+        Err(e) => {
+            log::error!("Failed to map input onto the capture: {}", e);
+            -1
+        }
+    }
+}
+
+/// Set the size of the surface the viewer shows the stream on
+///
+/// Fails until a capture is attached with `remotec_input_set_capture`.
+#[no_mangle]
+pub unsafe extern "C" fn remotec_input_set_viewer_size(
+    handle: *mut InputHandle,
+    width: u32,
+    height: u32,
+) -> i32 {
+    if handle.is_null() || width == 0 || height == 0 {
+        return -1;
+    }
+
+    let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
+    match simulator.transform_mut() {
+        Some(transform) => {
+            transform.set_viewer_size(width, height);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Set the size of the streamed frames
+///
+/// Fails until a capture is attached with `remotec_input_set_capture`.
+#[no_mangle]
+pub unsafe extern "C" fn remotec_input_set_stream_size(
+    handle: *mut InputHandle,
+    width: u32,
+    height: u32,
+) -> i32 {
+    if handle.is_null() || width == 0 || height == 0 {
+        return -1;
+    }
+
+    let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
+    match simulator.transform_mut() {
+        Some(transform) => {
+            transform.set_stream_size(width, height);
+            0
+        }
+        None => -1,
     }
 }
 
 /// Send mouse event
+///
+/// `x` and `y` are viewer coordinates once a capture is attached; see
+/// `remotec_input_set_viewer_size`.
 #[no_mangle]
 pub unsafe extern "C" fn remotec_input_mouse_move(
     handle: *mut InputHandle,
@@ -209,7 +339,7 @@ pub unsafe extern "C" fn remotec_input_mouse_move(
         return -1;
     }
     
-    let simulator = &mut *(handle as *mut Box<dyn crate::input::InputSimulator>);
+    let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
     match simulator.mouse_event(crate::input::MouseEvent::Move { x, y }) {
         Ok(_) => 0,
         Err(_) => -1,
@@ -233,7 +363,7 @@ pub unsafe extern "C" fn remotec_input_mouse_click(
         _ => return -1,
     };
     
-    let simulator = &mut *(handle as *mut Box<dyn crate::input::InputSimulator>);
+    let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
     match simulator.mouse_event(crate::input::MouseEvent::Click { button }) {
         Ok(_) => 0,
         Err(_) => -1,
@@ -258,7 +388,7 @@ pub unsafe extern "C" fn remotec_input_key_press(
         _ => return -1,
     };
     
-    let simulator = &mut *(handle as *mut Box<dyn crate::input::InputSimulator>);
+    let simulator = &mut *(handle as *mut crate::input::MappedInputSimulator);
     match simulator.keyboard_event(crate::input::KeyboardEvent::KeyPress { code }) {
         Ok(_) => 0,
         Err(_) => -1,
@@ -269,7 +399,7 @@ pub unsafe extern "C" fn remotec_input_key_press(
 #[no_mangle]
 pub unsafe extern "C" fn remotec_input_destroy(handle: *mut InputHandle) {
     if !handle.is_null() {
-        let _ = Box::from_raw(handle as *mut Box<dyn crate::input::InputSimulator>);
+        let _ = Box::from_raw(handle as *mut crate::input::MappedInputSimulator);
     }
 }
 
@@ -437,7 +567,7 @@ pub extern "C" fn remotec_capture_create_with_config(
     target_fps: u32,
     capture_cursor: u8,
 ) -> *mut CaptureHandle {
-    use crate::capture::{CaptureConfig, CaptureMode, CaptureQuality};
+    use crate::capture::{CaptureConfig, CaptureMode, CaptureQuality, CaptureSource, CursorMode};
     
     let mode = match mode {
         0 => CaptureMode::PrimaryMonitor,
@@ -469,8 +599,13 @@ pub extern "C" fn remotec_capture_create_with_config(
         mode,
         target_fps,
         capture_cursor: capture_cursor != 0,
+        // Cursor events are not exposed through the FFI
+        cursor_mode: CursorMode::Embedded,
         region: None,
-        quality: CaptureQuality::default(),
+        // Callers poll for whole frames and keep no previous one to diff against
+        quality: CaptureQuality { enable_frame_diff: false, ..Default::default() },
+        source: CaptureSource::Platform,
+        redaction: None,
     };
     
     match crate::capture::create_capture(config) {
@@ -0,0 +1,86 @@
+//! Viewer-space input
+//!
+//! [`MappedInputSimulator`] accepts mouse positions in viewer coordinates
+//! and injects them at the desktop points the viewer is showing, using a
+//! [`CoordinateTransform`] built from the active capture configuration.
+//! Without a transform, positions are desktop coordinates and pass through.
+
+use super::{InputSimulator, KeyCode, KeyboardEvent, MouseEvent};
+use crate::capture::CoordinateTransform;
+use crate::Result;
+
+/// Input simulator translating viewer coordinates to the desktop
+pub struct MappedInputSimulator {
+    inner: Box<dyn InputSimulator>,
+    transform: Option<CoordinateTransform>,
+}
+
+impl MappedInputSimulator {
+    /// Inject events through `inner`, mapping positions with `transform`
+    pub fn new(inner: Box<dyn InputSimulator>, transform: CoordinateTransform) -> Self {
+        Self { inner, transform: Some(transform) }
+    }
+
+    /// Inject events through `inner` unchanged until a transform is set
+    pub fn unmapped(inner: Box<dyn InputSimulator>) -> Self {
+        Self { inner, transform: None }
+    }
+
+    /// Current mapping, `None` while positions pass through
+    pub fn transform(&self) -> Option<&CoordinateTransform> {
+        self.transform.as_ref()
+    }
+
+    /// Replace the mapping, e.g. after the viewer was resized or the
+    /// capture was reconfigured
+    pub fn set_transform(&mut self, transform: CoordinateTransform) {
+        self.transform = Some(transform);
+    }
+
+    /// Mutable access to the mapping, e.g. to update the viewer size
+    pub fn transform_mut(&mut self) -> Option<&mut CoordinateTransform> {
+        self.transform.as_mut()
+    }
+
+    /// Return the wrapped simulator
+    pub fn into_inner(self) -> Box<dyn InputSimulator> {
+        self.inner
+    }
+}
+
+impl InputSimulator for MappedInputSimulator {
+    fn mouse_event(&mut self, event: MouseEvent) -> Result<()> {
+        let Some(transform) = self.transform.as_ref() else {
+            return self.inner.mouse_event(event);
+        };
+        let event = match event {
+            MouseEvent::Move { x, y } => {
+                let (x, y) = transform.viewer_to_desktop(x, y);
+                MouseEvent::Move { x, y }
+            }
+            MouseEvent::MoveRelative { dx, dy } => {
+                let (dx, dy) = transform.viewer_delta_to_desktop(dx, dy);
+                MouseEvent::MoveRelative { dx, dy }
+            }
+            other => other,
+        };
+        self.inner.mouse_event(event)
+    }
+
+    fn keyboard_event(&mut self, event: KeyboardEvent) -> Result<()> {
+        self.inner.keyboard_event(event)
+    }
+
+    /// Mouse position in viewer coordinates, clamped to the viewer
+    fn get_mouse_position(&self) -> Result<(i32, i32)> {
+        let (x, y) = self.inner.get_mouse_position()?;
+        Ok(match self.transform.as_ref() {
+            Some(transform) => transform.desktop_to_viewer(x, y),
+            None => (x, y),
+        })
+    }
+
+    fn is_key_pressed(&self, code: KeyCode) -> Result<bool> {
+        self.inner.is_key_pressed(code)
+    }
+}
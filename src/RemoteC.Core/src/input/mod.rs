@@ -4,6 +4,9 @@
 
 use crate::Result;
 
+pub mod mapped;
+pub use mapped::MappedInputSimulator;
+
 /// Mouse button types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseButton {
@@ -22,7 +25,9 @@ pub enum MouseButton {
 /// Mouse event types
 #[derive(Debug, Clone)]
 pub enum MouseEvent {
-    /// Move mouse to absolute position
+    /// Move mouse to an absolute desktop position
+    ///
+    /// Use [`MappedInputSimulator`] for positions in viewer coordinates.
     Move { x: i32, y: i32 },
     /// Move mouse relative to current position
     MoveRelative { dx: i32, dy: i32 },
@@ -181,6 +181,31 @@ fn test_modifier_keys() {
     }
 }
 
+#[test]
+fn test_mapped_input_uses_viewer_coordinates() {
+    use crate::capture::{CoordinateTransform, MonitorBounds, VirtualDesktop};
+
+    let monitor = crate::capture::Monitor::test(0, MonitorBounds::new(-1920, 0, 1920, 1080));
+    let desktop = VirtualDesktop::new(vec![monitor]).unwrap();
+    let mut transform = CoordinateTransform::from_area(desktop.total_bounds, &desktop);
+    transform.set_viewer_size(960, 540);
+
+    let mut sim = MappedInputSimulator::new(Box::new(MockInputSimulator::new()), transform);
+    sim.mouse_event(MouseEvent::Move { x: 480, y: 270 }).unwrap();
+    assert_eq!(sim.get_mouse_position().unwrap(), (480, 270));
+
+    sim.mouse_event(MouseEvent::MoveRelative { dx: 10, dy: 0 }).unwrap();
+    assert_eq!(sim.get_mouse_position().unwrap(), (490, 270));
+
+    let inner = sim.into_inner();
+    assert_eq!(inner.get_mouse_position().unwrap(), (-939, 541));
+
+    // Without a transform, positions are desktop coordinates
+    let mut sim = MappedInputSimulator::unmapped(inner);
+    sim.mouse_event(MouseEvent::Move { x: -1000, y: 20 }).unwrap();
+    assert_eq!(sim.get_mouse_position().unwrap(), (-1000, 20));
+}
+
 #[cfg(test)]
 mod platform_tests {
     use super::*;
@@ -13,35 +13,35 @@ use winapi::{
             INPUT_MOUSE, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, MOUSEEVENTF_ABSOLUTE,
             MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN,
             MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN,
-            MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_WHEEL, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_XDOWN,
-            MOUSEEVENTF_XUP, SM_CXSCREEN, SM_CYSCREEN, VK_CONTROL, VK_SHIFT,
+            MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_XDOWN,
+            MOUSEEVENTF_XUP, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+            SM_YVIRTUALSCREEN, VK_CONTROL, VK_SHIFT,
             VK_MENU, VK_LWIN, WHEEL_DELTA, XBUTTON1, XBUTTON2,
         },
     },
 };
 use std::mem::{size_of, zeroed};
 
-pub struct WindowsInputSimulator {
-    screen_width: i32,
-    screen_height: i32,
-}
+pub struct WindowsInputSimulator;
 
 impl WindowsInputSimulator {
     pub fn new() -> Result<Self> {
+        Self::virtual_screen().ok_or_else(|| {
+            RemoteCError::InitializationFailed("Failed to get screen dimensions".to_string())
+        })?;
+        Ok(Self)
+    }
+
+    /// Origin and size of the virtual desktop spanning all monitors
+    ///
+    /// Read on every use so monitors added or removed later are covered.
+    fn virtual_screen() -> Option<(i32, i32, i32, i32)> {
         unsafe {
-            let screen_width = GetSystemMetrics(SM_CXSCREEN);
-            let screen_height = GetSystemMetrics(SM_CYSCREEN);
-            
-            if screen_width <= 0 || screen_height <= 0 {
-                return Err(RemoteCError::InitializationFailed(
-                    "Failed to get screen dimensions".to_string()
-                ));
-            }
-            
-            Ok(Self {
-                screen_width,
-                screen_height,
-            })
+            let x = GetSystemMetrics(SM_XVIRTUALSCREEN);
+            let y = GetSystemMetrics(SM_YVIRTUALSCREEN);
+            let width = GetSystemMetrics(SM_CXVIRTUALSCREEN);
+            let height = GetSystemMetrics(SM_CYVIRTUALSCREEN);
+            (width > 0 && height > 0).then_some((x, y, width, height))
         }
     }
     
@@ -209,11 +209,19 @@ impl InputSimulator for WindowsInputSimulator {
     fn mouse_event(&mut self, event: MouseEvent) -> Result<()> {
         match event {
             MouseEvent::Move { x, y } => {
-                // Convert to normalized absolute coordinates (0-65535)
-                let norm_x = (x * 65535) / self.screen_width;
-                let norm_y = (y * 65535) / self.screen_height;
+                let (left, top, width, height) = Self::virtual_screen().ok_or_else(|| {
+                    RemoteCError::InputError("Failed to get screen dimensions".to_string())
+                })?;
+                // Convert desktop coordinates to normalized virtual desktop
+                // coordinates (0-65535)
+                let normalize = |value: i32, origin: i32, size: i32| {
+                    let offset = i64::from(value - origin).clamp(0, i64::from(size - 1));
+                    (offset * 65535 / i64::from((size - 1).max(1))) as i32
+                };
+                let norm_x = normalize(x, left, width);
+                let norm_y = normalize(y, top, height);
                 self.send_mouse_input(
-                    MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE,
+                    MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
                     norm_x,
                     norm_y,
                     0
@@ -1,6 +1,6 @@
 //! H.264/AVC video encoder implementation
 
-use super::{VideoEncoder, VideoDecoder, EncoderConfig, EncodedFrame, EncoderStats, DecoderStats, VideoCodec};
+use super::{scale::{self, Scaler}, svc, ScalabilityMode, VideoEncoder, VideoDecoder, EncoderConfig, EncodedFrame, EncoderStats, DecoderStats, VideoCodec, DecodedPicture, DecoderOutput, PixelFormat};
 use crate::{Result, RemoteCError};
 use std::sync::Mutex;
 use std::time::Instant;
@@ -15,6 +15,8 @@ pub struct H264Encoder {
     config: Option<EncoderConfig>,
     stats: Mutex<EncoderStats>,
     frame_counter: u64,
+    /// Resampler from the input to the encoded resolution
+    scaler: Option<Scaler>,
     #[cfg(feature = "openh264")]
     encoder: Option<Encoder>,
 }
@@ -25,6 +27,7 @@ impl H264Encoder {
             config: None,
             stats: Mutex::new(EncoderStats::default()),
             frame_counter: 0,
+            scaler: None,
             #[cfg(feature = "openh264")]
             encoder: None,
         })
@@ -47,6 +50,34 @@ impl H264Encoder {
             .map_err(|e| RemoteCError::EncodingError(format!("Failed to create OpenH264 encoder: {:?}", e)))
     }
     
+    /// Retune the bitrate of a running OpenH264 encoder
+    #[cfg(feature = "openh264")]
+    fn set_encoder_bitrate(encoder: &mut Encoder, bitrate: u32) -> Result<()> {
+        use std::os::raw::c_int;
+        
+        // `SBitrateInfo`, `ENCODER_OPTION_BITRATE` and `SPATIAL_LAYER_ALL`
+        // from codec_app_def.h; the openh264 crate does not re-export them
+        #[repr(C)]
+        struct BitrateInfo {
+            layer: c_int,
+            bitrate: c_int,
+        }
+        const ENCODER_OPTION_BITRATE: c_int = 5;
+        const SPATIAL_LAYER_ALL: c_int = 4;
+        
+        let mut info = BitrateInfo {
+            layer: SPATIAL_LAYER_ALL,
+            bitrate: c_int::try_from(bitrate).unwrap_or(c_int::MAX),
+        };
+        let status = unsafe {
+            encoder.raw_api().set_option(ENCODER_OPTION_BITRATE, (&mut info as *mut BitrateInfo).cast())
+        };
+        if status != 0 {
+            return Err(RemoteCError::EncodingError(format!("OpenH264 rejected bitrate {}: {}", bitrate, status)));
+        }
+        Ok(())
+    }
+    
     #[cfg(not(feature = "openh264"))]
     fn encode_frame_internal(&mut self, _frame: &[u8], _timestamp: u64) -> Result<Vec<u8>> {
         // Fallback implementation
@@ -88,6 +119,12 @@ impl VideoEncoder for H264Encoder {
             ));
         }
         
+        if !self.supports_scalability_mode(config.scalability_mode) {
+            return Err(RemoteCError::EncodingError(
+                format!("Scalability mode {} not supported by H264Encoder", config.scalability_mode)
+            ));
+        }
+        
         #[cfg(feature = "openh264")]
         {
             self.encoder = Some(Self::create_encoder(&config)?);
@@ -95,33 +132,49 @@ impl VideoEncoder for H264Encoder {
         
         log::info!("H.264 encoder configured: {}x{} @ {} bps", 
                    config.width, config.height, config.bitrate);
+        self.scaler = None;
+        self.config = Some(config);
+        Ok(())
+    }
+    
+    fn update_bitrate(&mut self, config: EncoderConfig) -> Result<()> {
+        if self.config.is_none() {
+            return self.configure(config);
+        }
+        
+        // Recreating the OpenH264 encoder would restart the stream with an IDR frame
+        #[cfg(feature = "openh264")]
+        if let Some(encoder) = self.encoder.as_mut() {
+            if let Err(e) = Self::set_encoder_bitrate(encoder, config.bitrate) {
+                log::warn!("{}, recreating the encoder", e);
+                return self.configure(config);
+            }
+        }
+        
+        log::debug!("H.264 encoder bitrate changed to {} bps", config.bitrate);
         self.config = Some(config);
         Ok(())
     }
     
     fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame> {
-        let (_width, _height, keyframe_interval) = {
+        let (frame, width, height, keyframe_interval, scalability_mode) = {
             let config = self.config.as_ref()
                 .ok_or_else(|| RemoteCError::EncodingError("Encoder not configured".to_string()))?;
             
-            // Validate frame size
-            let expected_size = (config.width * config.height * 4) as usize;
-            if frame.len() != expected_size {
-                return Err(RemoteCError::EncodingError(
-                    format!("Invalid frame size: expected {}, got {}", expected_size, frame.len())
-                ));
-            }
+            // Validate frame size and scale to the encoded resolution
+            let frame = scale::prepare_encoder_input(config, &mut self.scaler, frame)?;
             
-            (config.width, config.height, config.keyframe_interval)
+            (frame, config.width, config.height, config.keyframe_interval, config.scalability_mode)
         };
         
         let start = Instant::now();
         
         // Encode frame
-        let encoded_data = self.encode_frame_internal(frame, timestamp)?;
+        let encoded_data = self.encode_frame_internal(&frame, timestamp)?;
         
         self.frame_counter += 1;
         let is_keyframe = self.frame_counter % keyframe_interval as u64 == 1;
+        let temporal_layer = svc::temporal_layer(scalability_mode, self.frame_counter, keyframe_interval);
         
         // Update statistics
         let encode_time = start.elapsed().as_micros() as f64;
@@ -142,9 +195,18 @@ impl VideoEncoder for H264Encoder {
             timestamp,
             is_keyframe,
             sequence: self.frame_counter,
+            layer_id: 0,
+            temporal_layer,
+            width,
+            height,
         })
     }
     
+    fn supports_scalability_mode(&self, mode: ScalabilityMode) -> bool {
+        // The OpenH264 bindings do not expose temporal layer configuration
+        mode == ScalabilityMode::L1T1 || cfg!(not(feature = "openh264"))
+    }
+    
     fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
         // TODO: Implement flush
         Ok(Vec::new())
@@ -210,12 +272,15 @@ fn rgba_to_yuv420(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
 /// H.264 video decoder
 pub struct H264Decoder {
     stats: Mutex<DecoderStats>,
+    /// Resampler to the requested output size
+    scaler: Option<Scaler>,
 }
 
 impl H264Decoder {
     pub fn new() -> Result<Self> {
         Ok(Self {
             stats: Mutex::new(DecoderStats::default()),
+            scaler: None,
         })
     }
 }
@@ -230,13 +295,17 @@ impl VideoDecoder for H264Decoder {
         Ok(())
     }
     
-    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<Vec<u8>> {
+    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<DecodedPicture> {
         // TODO: Implement actual H.264 decoding
         let mut stats = self.stats.lock().unwrap();
         stats.frames_decoded += 1;
         
         // Placeholder: return dummy decoded data
-        Ok(vec![0; frame.data.len() * 100]) // Simulate decompression
+        Ok(DecodedPicture::black(frame.width, frame.height, PixelFormat::I420))
+    }
+    
+    fn decode_frame_to(&mut self, frame: &EncodedFrame, output: &DecoderOutput) -> Result<DecodedPicture> {
+        self.decode_frame(frame)?.into_output_with(output, &mut self.scaler)
     }
     
     fn get_stats(&self) -> DecoderStats {
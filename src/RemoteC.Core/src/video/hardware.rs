@@ -1,23 +1,321 @@
 //! Hardware acceleration detection and management
 
+use super::VideoCodec;
 use crate::Result;
+#[cfg(feature = "nvenc")]
+use crate::RemoteCError;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Once;
 
 static INIT: Once = Once::new();
 static mut HW_CAPABILITIES: Option<HardwareCapabilities> = None;
 
+/// PCI vendor IDs of GPU vendors with hardware video engines
+const PCI_VENDOR_NVIDIA: u16 = 0x10de;
+const PCI_VENDOR_INTEL: u16 = 0x8086;
+const PCI_VENDOR_AMD: u16 = 0x1002;
+
+/// PCI base class for display controllers
+const PCI_CLASS_DISPLAY: u32 = 0x03;
+
 /// Hardware acceleration capabilities
 #[derive(Debug, Clone, Default)]
 pub struct HardwareCapabilities {
+    /// NVIDIA NVENC is usable
     pub nvidia_nvenc: bool,
+    /// Intel Quick Sync Video is usable
     pub intel_qsv: bool,
+    /// AMD VCE/VCN is usable
     pub amd_vce: bool,
+    /// Apple VideoToolbox is usable
     pub apple_vt: bool,
+    /// CUDA runtime is available
     pub cuda_available: bool,
+    /// OpenCL runtime is available
     pub opencl_available: bool,
+    /// Name of the primary GPU
     pub gpu_name: String,
+    /// Memory of the primary GPU in MB (0 if unknown)
     pub gpu_memory_mb: u32,
+    /// Number of GPUs with a hardware encoder
     pub encoder_count: u32,
+    /// GPUs found on the system
+    pub gpus: Vec<GpuDevice>,
+    /// Per-codec hardware encode/decode support
+    pub codecs: Vec<CodecCapability>,
+}
+
+impl HardwareCapabilities {
+    /// Check whether any GPU can encode the codec in hardware
+    pub fn can_encode(&self, codec: VideoCodec) -> bool {
+        self.codecs.iter().any(|c| c.codec == codec && c.encode)
+    }
+    
+    /// Check whether any GPU can decode the codec in hardware
+    pub fn can_decode(&self, codec: VideoCodec) -> bool {
+        self.codecs.iter().any(|c| c.codec == codec && c.decode)
+    }
+}
+
+/// GPU vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuVendor {
+    /// NVIDIA
+    Nvidia,
+    /// Intel
+    Intel,
+    /// AMD
+    Amd,
+    /// Any other vendor, by PCI vendor ID
+    Other(u16),
+}
+
+impl GpuVendor {
+    /// Map a PCI vendor ID to a vendor
+    pub fn from_pci_id(vendor_id: u16) -> Self {
+        match vendor_id {
+            PCI_VENDOR_NVIDIA => GpuVendor::Nvidia,
+            PCI_VENDOR_INTEL => GpuVendor::Intel,
+            PCI_VENDOR_AMD => GpuVendor::Amd,
+            other => GpuVendor::Other(other),
+        }
+    }
+}
+
+/// A display controller found on the PCI bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuDevice {
+    /// PCI slot name, e.g. `0000:01:00.0`
+    pub pci_address: String,
+    /// Vendor
+    pub vendor: GpuVendor,
+    /// PCI vendor ID
+    pub vendor_id: u16,
+    /// PCI device ID
+    pub device_id: u16,
+    /// Bound kernel driver, if any
+    pub driver: Option<String>,
+    /// DRM render node, e.g. `/dev/dri/renderD128`
+    pub render_node: Option<PathBuf>,
+}
+
+impl GpuDevice {
+    /// Human-readable name from the vendor and PCI IDs
+    pub fn name(&self) -> String {
+        let vendor = match self.vendor {
+            GpuVendor::Nvidia => "NVIDIA",
+            GpuVendor::Intel => "Intel",
+            GpuVendor::Amd => "AMD",
+            GpuVendor::Other(_) => "Unknown",
+        };
+        format!("{} GPU [{:04x}:{:04x}]", vendor, self.vendor_id, self.device_id)
+    }
+    
+    /// Hardware codec support, as far as the vendor and driver allow
+    ///
+    /// NVENC/NVDEC require the proprietary `nvidia` driver. Intel and AMD
+    /// engines are reached through VA-API and therefore need a render node.
+    pub fn codec_capabilities(&self) -> Vec<CodecCapability> {
+        let driver = self.driver.as_deref();
+        let has_render_node = self.render_node.is_some();
+        
+        let (encode, decode): (&[VideoCodec], &[VideoCodec]) = match self.vendor {
+            GpuVendor::Nvidia if driver == Some("nvidia") => (
+                &[VideoCodec::H264, VideoCodec::H265],
+                &[VideoCodec::H264, VideoCodec::H265, VideoCodec::VP8, VideoCodec::VP9],
+            ),
+            GpuVendor::Intel if has_render_node && matches!(driver, Some("i915" | "xe")) => (
+                &[VideoCodec::H264, VideoCodec::H265, VideoCodec::VP9],
+                &[VideoCodec::H264, VideoCodec::H265, VideoCodec::VP8, VideoCodec::VP9],
+            ),
+            GpuVendor::Amd if has_render_node && driver == Some("amdgpu") => (
+                &[VideoCodec::H264, VideoCodec::H265],
+                &[VideoCodec::H264, VideoCodec::H265, VideoCodec::VP9],
+            ),
+            GpuVendor::Amd if has_render_node && driver == Some("radeon") => (
+                &[VideoCodec::H264],
+                &[VideoCodec::H264],
+            ),
+            _ => (&[], &[]),
+        };
+        
+        [VideoCodec::H264, VideoCodec::H265, VideoCodec::VP8, VideoCodec::VP9]
+            .into_iter()
+            .filter_map(|codec| {
+                let capability = CodecCapability {
+                    codec,
+                    encode: encode.contains(&codec),
+                    decode: decode.contains(&codec),
+                };
+                (capability.encode || capability.decode).then_some(capability)
+            })
+            .collect()
+    }
+}
+
+/// Hardware encode/decode support for a codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodecCapability {
+    /// Codec
+    pub codec: VideoCodec,
+    /// Hardware encoding is supported
+    pub encode: bool,
+    /// Hardware decoding is supported
+    pub decode: bool,
+}
+
+/// GPU probe reading PCI and DRM information from sysfs
+///
+/// The sysfs and dev roots are configurable so detection can be tested
+/// against fixture trees and run inside containers with remapped mounts.
+#[derive(Debug, Clone)]
+pub struct LinuxGpuProbe {
+    sysfs_root: PathBuf,
+    dev_root: PathBuf,
+}
+
+impl Default for LinuxGpuProbe {
+    fn default() -> Self {
+        Self::with_roots("/sys", "/dev")
+    }
+}
+
+impl LinuxGpuProbe {
+    /// Create a probe for the live system (`/sys` and `/dev`)
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Create a probe with custom sysfs and dev roots
+    pub fn with_roots(sysfs_root: impl Into<PathBuf>, dev_root: impl Into<PathBuf>) -> Self {
+        Self {
+            sysfs_root: sysfs_root.into(),
+            dev_root: dev_root.into(),
+        }
+    }
+    
+    /// Enumerate display controllers on the PCI bus, sorted by PCI address
+    pub fn probe_gpus(&self) -> Vec<GpuDevice> {
+        let Ok(entries) = fs::read_dir(self.sysfs_root.join("bus/pci/devices")) else {
+            return Vec::new();
+        };
+        
+        let render_nodes = self.render_nodes();
+        let mut gpus: Vec<GpuDevice> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let class = read_hex(&path.join("class"))?;
+                if class >> 16 != PCI_CLASS_DISPLAY {
+                    return None;
+                }
+                
+                let vendor_id = u16::try_from(read_hex(&path.join("vendor"))?).ok()?;
+                let device_id = u16::try_from(read_hex(&path.join("device"))?).ok()?;
+                let pci_address = entry.file_name().to_string_lossy().into_owned();
+                let driver = read_uevent(&path.join("uevent"), "DRIVER");
+                let render_node = render_nodes
+                    .iter()
+                    .find(|(slot, _)| *slot == pci_address)
+                    .map(|(_, node)| node.clone());
+                
+                Some(GpuDevice {
+                    pci_address,
+                    vendor: GpuVendor::from_pci_id(vendor_id),
+                    vendor_id,
+                    device_id,
+                    driver,
+                    render_node,
+                })
+            })
+            .collect();
+        
+        gpus.sort_by(|a, b| a.pci_address.cmp(&b.pci_address));
+        gpus
+    }
+    
+    /// Build hardware capabilities from the probed GPUs
+    pub fn capabilities(&self) -> HardwareCapabilities {
+        let gpus = self.probe_gpus();
+        let mut caps = HardwareCapabilities::default();
+        
+        for gpu in &gpus {
+            let codecs = gpu.codec_capabilities();
+            let can_encode = codecs.iter().any(|c| c.encode);
+            
+            if can_encode {
+                caps.encoder_count += 1;
+                match gpu.vendor {
+                    GpuVendor::Nvidia => {
+                        caps.nvidia_nvenc = true;
+                        caps.cuda_available = true;
+                    }
+                    GpuVendor::Intel => caps.intel_qsv = true,
+                    GpuVendor::Amd => caps.amd_vce = true,
+                    GpuVendor::Other(_) => {}
+                }
+            }
+            
+            for codec in codecs {
+                match caps.codecs.iter_mut().find(|c| c.codec == codec.codec) {
+                    Some(existing) => {
+                        existing.encode |= codec.encode;
+                        existing.decode |= codec.decode;
+                    }
+                    None => caps.codecs.push(codec),
+                }
+            }
+        }
+        
+        // Prefer a GPU with an encoder as the primary one
+        if let Some(primary) = gpus
+            .iter()
+            .find(|g| g.codec_capabilities().iter().any(|c| c.encode))
+            .or_else(|| gpus.first())
+        {
+            caps.gpu_name = primary.name();
+        }
+        
+        caps.gpus = gpus;
+        caps
+    }
+    
+    /// Map PCI slot names to render node paths under the dev root
+    fn render_nodes(&self) -> Vec<(String, PathBuf)> {
+        let Ok(entries) = fs::read_dir(self.sysfs_root.join("class/drm")) else {
+            return Vec::new();
+        };
+        
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !name.starts_with("renderD") {
+                    return None;
+                }
+                
+                let slot = read_uevent(&entry.path().join("device/uevent"), "PCI_SLOT_NAME")?;
+                let node = self.dev_root.join("dri").join(&name);
+                node.exists().then_some((slot, node))
+            })
+            .collect()
+    }
+}
+
+/// Read a sysfs attribute such as `0x10de` or `0x030000`
+fn read_hex(path: &Path) -> Option<u32> {
+    let text = fs::read_to_string(path).ok()?;
+    let text = text.trim();
+    u32::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16).ok()
+}
+
+/// Read a `KEY=value` entry from a sysfs uevent file
+fn read_uevent(path: &Path, key: &str) -> Option<String> {
+    fs::read_to_string(path).ok()?.lines().find_map(|line| {
+        let (k, v) = line.split_once('=')?;
+        (k == key).then(|| v.trim().to_string())
+    })
 }
 
 /// Detect available hardware acceleration
@@ -31,6 +329,10 @@ pub fn detect_hardware_acceleration() -> &'static HardwareCapabilities {
 }
 
 fn detect_capabilities() -> HardwareCapabilities {
+    // Linux reads PCI/DRM information from sysfs
+    #[cfg(target_os = "linux")]
+    let mut caps = LinuxGpuProbe::new().capabilities();
+    #[cfg(not(target_os = "linux"))]
     let mut caps = HardwareCapabilities::default();
     
     // Detect NVIDIA hardware
@@ -41,20 +343,25 @@ fn detect_capabilities() -> HardwareCapabilities {
             caps.gpu_name = get_nvidia_gpu_name();
             caps.gpu_memory_mb = get_nvidia_memory();
             caps.cuda_available = true;
+            caps.encoder_count = 1;
+            caps.codecs = vec![
+                CodecCapability { codec: VideoCodec::H264, encode: true, decode: true },
+                CodecCapability { codec: VideoCodec::H265, encode: true, decode: true },
+            ];
         }
     }
     
-    #[cfg(target_os = "linux")]
-    {
-        caps.nvidia_nvenc = detect_nvenc_linux();
-        caps.intel_qsv = detect_qsv_linux();
-        caps.amd_vce = detect_vce_linux();
-    }
-    
     #[cfg(target_os = "macos")]
     {
         caps.apple_vt = detect_videotoolbox();
         caps.gpu_name = get_macos_gpu_name();
+        if caps.apple_vt {
+            caps.encoder_count = 1;
+            caps.codecs = vec![
+                CodecCapability { codec: VideoCodec::H264, encode: true, decode: true },
+                CodecCapability { codec: VideoCodec::H265, encode: true, decode: true },
+            ];
+        }
     }
     
     // Detect OpenCL support
@@ -86,51 +393,6 @@ fn detect_nvenc_windows() -> bool {
     }
 }
 
-#[cfg(target_os = "linux")]
-fn detect_nvenc_linux() -> bool {
-    use std::process::Command;
-    
-    // Check for NVIDIA driver
-    let output = Command::new("nvidia-smi")
-        .arg("--query-gpu=name")
-        .arg("--format=csv,noheader")
-        .output();
-    
-    match output {
-        Ok(output) => {
-            let gpu_name = String::from_utf8_lossy(&output.stdout);
-            !gpu_name.trim().is_empty()
-        }
-        Err(_) => false,
-    }
-}
-
-#[cfg(target_os = "linux")]
-fn detect_qsv_linux() -> bool {
-    use std::path::Path;
-    
-    // Check for Intel Media SDK
-    Path::new("/opt/intel/mediasdk/lib64/libmfx.so.1").exists() ||
-    Path::new("/usr/lib/x86_64-linux-gnu/libmfx.so.1").exists()
-}
-
-#[cfg(target_os = "linux")]
-fn detect_vce_linux() -> bool {
-    use std::fs;
-    
-    // Check for AMD GPU
-    if let Ok(entries) = fs::read_dir("/sys/class/drm") {
-        for entry in entries.flatten() {
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-            if name_str.starts_with("card") && name_str.contains("AMD") {
-                return true;
-            }
-        }
-    }
-    false
-}
-
 #[cfg(target_os = "macos")]
 fn detect_videotoolbox() -> bool {
     // VideoToolbox is available on all modern macOS
@@ -286,7 +548,7 @@ impl HardwareEncoder for NvencEncoder {
         }
         
         // TODO: Implement actual NVENC encoding
-        Ok(vec![0; 1000])
+        Err(RemoteCError::NotImplemented("NVENC encoding".to_string()))
     }
     
     fn name(&self) -> &str {
@@ -294,18 +556,18 @@ impl HardwareEncoder for NvencEncoder {
     }
     
     fn is_available(&self) -> bool {
-        detect_hardware_acceleration().nvidia_nvenc
+        // Never selected until encode_frame produces a real bitstream
+        false
     }
 }
 
 /// Select best available hardware encoder
 pub fn select_hardware_encoder() -> Option<Box<dyn HardwareEncoder>> {
-    let _caps = detect_hardware_acceleration();
-    
     #[cfg(feature = "nvenc")]
     {
-        if caps.nvidia_nvenc {
-            return Some(Box::new(NvencEncoder::new()));
+        let encoder = NvencEncoder::new();
+        if encoder.is_available() {
+            return Some(Box::new(encoder));
         }
     }
     
@@ -336,6 +598,124 @@ mod tests {
         assert!(true);
     }
     
+    /// Temporary sysfs/dev tree, removed on drop
+    struct SysfsFixture {
+        root: PathBuf,
+    }
+    
+    impl SysfsFixture {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir()
+                .join(format!("remotec-sysfs-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("sys/bus/pci/devices")).unwrap();
+            fs::create_dir_all(root.join("sys/class/drm")).unwrap();
+            fs::create_dir_all(root.join("dev/dri")).unwrap();
+            Self { root }
+        }
+        
+        fn add_pci_device(&self, slot: &str, class: &str, vendor: &str, device: &str, driver: Option<&str>) {
+            let dir = self.root.join("sys/bus/pci/devices").join(slot);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("class"), format!("{}\n", class)).unwrap();
+            fs::write(dir.join("vendor"), format!("{}\n", vendor)).unwrap();
+            fs::write(dir.join("device"), format!("{}\n", device)).unwrap();
+            let mut uevent = format!("PCI_SLOT_NAME={}\n", slot);
+            if let Some(driver) = driver {
+                uevent = format!("DRIVER={}\n{}", driver, uevent);
+            }
+            fs::write(dir.join("uevent"), uevent).unwrap();
+        }
+        
+        fn add_render_node(&self, node: &str, slot: &str) {
+            let dir = self.root.join("sys/class/drm").join(node).join("device");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("uevent"), format!("PCI_SLOT_NAME={}\n", slot)).unwrap();
+            fs::write(self.root.join("dev/dri").join(node), "").unwrap();
+        }
+        
+        fn probe(&self) -> LinuxGpuProbe {
+            LinuxGpuProbe::with_roots(self.root.join("sys"), self.root.join("dev"))
+        }
+    }
+    
+    impl Drop for SysfsFixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+    
+    #[test]
+    fn test_probe_nvidia_with_proprietary_driver() {
+        let fixture = SysfsFixture::new("nvidia");
+        fixture.add_pci_device("0000:00:1f.3", "0x040300", "0x8086", "0xa348", Some("snd_hda_intel"));
+        fixture.add_pci_device("0000:01:00.0", "0x030000", "0x10de", "0x2484", Some("nvidia"));
+        
+        let gpus = fixture.probe().probe_gpus();
+        assert_eq!(gpus.len(), 1);
+        assert_eq!(gpus[0].vendor, GpuVendor::Nvidia);
+        assert_eq!(gpus[0].device_id, 0x2484);
+        
+        let caps = fixture.probe().capabilities();
+        assert!(caps.nvidia_nvenc);
+        assert!(!caps.intel_qsv && !caps.amd_vce);
+        assert!(caps.can_encode(VideoCodec::H265));
+        assert!(!caps.can_encode(VideoCodec::VP9));
+        assert!(caps.can_decode(VideoCodec::VP9));
+        assert_eq!(caps.encoder_count, 1);
+        assert_eq!(caps.gpu_name, "NVIDIA GPU [10de:2484]");
+    }
+    
+    #[test]
+    fn test_probe_nouveau_has_no_encoder() {
+        let fixture = SysfsFixture::new("nouveau");
+        fixture.add_pci_device("0000:01:00.0", "0x030000", "0x10de", "0x1c82", Some("nouveau"));
+        fixture.add_render_node("renderD128", "0000:01:00.0");
+        
+        let caps = fixture.probe().capabilities();
+        assert!(!caps.nvidia_nvenc);
+        assert!(caps.codecs.is_empty());
+        assert_eq!(caps.gpus.len(), 1);
+    }
+    
+    #[test]
+    fn test_probe_hybrid_intel_amd() {
+        let fixture = SysfsFixture::new("hybrid");
+        fixture.add_pci_device("0000:00:02.0", "0x030000", "0x8086", "0x9a49", Some("i915"));
+        fixture.add_pci_device("0000:03:00.0", "0x038000", "0x1002", "0x73ff", Some("amdgpu"));
+        fixture.add_render_node("renderD128", "0000:00:02.0");
+        fixture.add_render_node("renderD129", "0000:03:00.0");
+        
+        let gpus = fixture.probe().probe_gpus();
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].render_node, Some(fixture.root.join("dev/dri/renderD128")));
+        assert_eq!(gpus[1].render_node, Some(fixture.root.join("dev/dri/renderD129")));
+        
+        let caps = fixture.probe().capabilities();
+        assert!(caps.intel_qsv);
+        assert!(caps.amd_vce);
+        assert_eq!(caps.encoder_count, 2);
+        assert!(caps.can_encode(VideoCodec::VP9));
+        assert!(caps.can_decode(VideoCodec::VP8));
+    }
+    
+    #[test]
+    fn test_probe_intel_without_render_node() {
+        let fixture = SysfsFixture::new("no-render");
+        fixture.add_pci_device("0000:00:02.0", "0x030000", "0x8086", "0x9a49", Some("i915"));
+        
+        let caps = fixture.probe().capabilities();
+        assert!(!caps.intel_qsv);
+        assert_eq!(caps.gpus[0].render_node, None);
+    }
+    
+    #[test]
+    fn test_probe_missing_sysfs() {
+        let probe = LinuxGpuProbe::with_roots("/nonexistent/sys", "/nonexistent/dev");
+        assert!(probe.probe_gpus().is_empty());
+        assert_eq!(probe.capabilities().encoder_count, 0);
+    }
+    
     #[test]
     fn test_encoder_selection() {
         let encoder = select_hardware_encoder();
@@ -0,0 +1,242 @@
+//! Lossless frame codec exposed through the video encoder traits
+//!
+//! Wraps [`crate::encoding::FrameEncoder`] so the lossless compression path
+//! can serve as the last fallback of the encoder registry. Every frame is
+//! intra-coded and carries a small header with its dimensions and
+//! compression format.
+
+use super::{
+    scale::{self, Scaler}, DecodedPicture, DecoderOutput, DecoderStats, EncodedFrame, EncoderConfig, EncoderStats, PixelFormat, VideoCodec,
+    VideoDecoder, VideoEncoder,
+};
+use crate::encoding::{self, CompressionFormat, FrameDecoder, FrameEncoder, FrameEncodingConfig, FrameMetadata};
+use crate::{RemoteCError, Result};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Size of the frame header: format, width, height, original size
+const HEADER_LEN: usize = 13;
+
+/// Serialize an encoded lossless frame into a video frame payload
+fn pack(frame: &encoding::EncodedFrame) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LEN + frame.data.len());
+    data.push(frame.metadata.format as u8);
+    data.extend_from_slice(&frame.metadata.width.to_le_bytes());
+    data.extend_from_slice(&frame.metadata.height.to_le_bytes());
+    data.extend_from_slice(&(frame.metadata.original_size as u32).to_le_bytes());
+    data.extend_from_slice(&frame.data);
+    data
+}
+
+/// Parse a video frame payload produced by [`pack`]
+fn unpack(data: &[u8]) -> Result<encoding::EncodedFrame> {
+    if data.len() < HEADER_LEN {
+        return Err(RemoteCError::DecodingError("Lossless frame header truncated".to_string()));
+    }
+
+    let format = match data[0] {
+        0 => CompressionFormat::None,
+        1 => CompressionFormat::Zlib,
+        2 => CompressionFormat::Lz4,
+        3 => CompressionFormat::Zstd,
+        other => {
+            return Err(RemoteCError::DecodingError(format!(
+                "Unknown lossless compression format {}",
+                other
+            )))
+        }
+    };
+    let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+    let payload = data[HEADER_LEN..].to_vec();
+
+    Ok(encoding::EncodedFrame {
+        metadata: FrameMetadata {
+            width: read_u32(1),
+            height: read_u32(5),
+            format,
+            original_size: read_u32(9) as usize,
+            compressed_size: payload.len(),
+            compression_ratio: 0.0,
+            timestamp: 0,
+            encoding_duration_us: 0,
+        },
+        data: payload,
+    })
+}
+
+/// Video encoder backed by the lossless frame codec
+pub struct LosslessFrameEncoder {
+    config: Option<EncoderConfig>,
+    encoder: FrameEncoder,
+    stats: Mutex<EncoderStats>,
+    frame_counter: u64,
+    /// Resampler from the input to the encoded resolution
+    scaler: Option<Scaler>,
+}
+
+impl LosslessFrameEncoder {
+    /// Create a lossless encoder with the given compression settings
+    pub fn new(encoding: FrameEncodingConfig) -> Result<Self> {
+        Ok(Self {
+            config: None,
+            encoder: FrameEncoder::new(encoding)?,
+            stats: Mutex::new(EncoderStats::default()),
+            frame_counter: 0,
+            scaler: None,
+        })
+    }
+}
+
+impl VideoEncoder for LosslessFrameEncoder {
+    fn configure(&mut self, config: EncoderConfig) -> Result<()> {
+        if config.codec != VideoCodec::Lossless {
+            return Err(RemoteCError::EncodingError("Invalid codec for LosslessFrameEncoder".to_string()));
+        }
+        self.scaler = None;
+        self.config = Some(config);
+        Ok(())
+    }
+
+    fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame> {
+        let config = self.config.as_ref()
+            .ok_or_else(|| RemoteCError::EncodingError("Encoder not configured".to_string()))?;
+        let (width, height) = (config.width, config.height);
+        let frame = scale::prepare_encoder_input(config, &mut self.scaler, frame)?;
+
+        let start = Instant::now();
+        let encoded = self.encoder.encode_frame(&frame, width, height)?;
+        let data = pack(&encoded);
+
+        self.frame_counter += 1;
+        let mut stats = self.stats.lock().unwrap();
+        stats.frames_encoded += 1;
+        stats.keyframes_encoded += 1;
+        stats.avg_encode_time =
+            (stats.avg_encode_time * (stats.frames_encoded - 1) as f64 + start.elapsed().as_micros() as f64)
+            / stats.frames_encoded as f64;
+
+        Ok(EncodedFrame {
+            data,
+            timestamp,
+            is_keyframe: true,
+            sequence: self.frame_counter,
+            layer_id: 0,
+            temporal_layer: 0,
+            width,
+            height,
+        })
+    }
+
+    fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+        Ok(Vec::new())
+    }
+
+    fn get_stats(&self) -> EncoderStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.frame_counter = 0;
+        *self.stats.lock().unwrap() = EncoderStats::default();
+        Ok(())
+    }
+}
+
+/// Video decoder for frames produced by [`LosslessFrameEncoder`]
+pub struct LosslessFrameDecoder {
+    decoder: FrameDecoder,
+    stats: Mutex<DecoderStats>,
+    /// Resampler to the requested output size
+    scaler: Option<Scaler>,
+}
+
+impl Default for LosslessFrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LosslessFrameDecoder {
+    /// Create a lossless decoder
+    pub fn new() -> Self {
+        Self {
+            decoder: FrameDecoder::new(),
+            stats: Mutex::new(DecoderStats::default()),
+            scaler: None,
+        }
+    }
+}
+
+impl VideoDecoder for LosslessFrameDecoder {
+    fn configure(&mut self, codec: VideoCodec) -> Result<()> {
+        if codec != VideoCodec::Lossless {
+            return Err(RemoteCError::DecodingError("Invalid codec for LosslessFrameDecoder".to_string()));
+        }
+        Ok(())
+    }
+
+    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<DecodedPicture> {
+        let result = unpack(&frame.data).and_then(|encoded| self.decoder.decode_frame(&encoded));
+
+        let mut stats = self.stats.lock().unwrap();
+        match result {
+            Ok(decoded) => {
+                stats.frames_decoded += 1;
+                stats.avg_decode_time =
+                    (stats.avg_decode_time * (stats.frames_decoded - 1) as f64 + decoded.decoding_duration_us as f64)
+                    / stats.frames_decoded as f64;
+                DecodedPicture::new(decoded.width, decoded.height, PixelFormat::Bgra, decoded.data)
+            }
+            Err(e) => {
+                stats.frames_errors += 1;
+                Err(e)
+            }
+        }
+    }
+
+    fn decode_frame_to(&mut self, frame: &EncodedFrame, output: &DecoderOutput) -> Result<DecodedPicture> {
+        self.decode_frame(frame)?.into_output_with(output, &mut self.scaler)
+    }
+
+    fn get_stats(&self) -> DecoderStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        *self.stats.lock().unwrap() = DecoderStats::default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let mut encoder = LosslessFrameEncoder::new(FrameEncodingConfig::default()).unwrap();
+        encoder.configure(EncoderConfig {
+            codec: VideoCodec::Lossless,
+            width: 16,
+            height: 8,
+            ..Default::default()
+        }).unwrap();
+
+        assert!(LosslessFrameEncoder::new(FrameEncodingConfig::default())
+            .unwrap()
+            .configure(EncoderConfig::default())
+            .is_err());
+
+        let frame: Vec<u8> = (0..16 * 8 * 4).map(|i| (i % 251) as u8).collect();
+        let encoded = encoder.encode_frame(&frame, 42).unwrap();
+        assert!(encoded.is_keyframe);
+        assert_eq!(encoded.timestamp, 42);
+
+        let mut decoder = LosslessFrameDecoder::new();
+        assert_eq!(decoder.decode_frame(&encoded).unwrap().data, frame);
+
+        let truncated = EncodedFrame { data: encoded.data[..5].to_vec(), ..encoded };
+        assert!(decoder.decode_frame(&truncated).is_err());
+        assert_eq!(decoder.get_stats().frames_errors, 1);
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Provides high-performance video encoding using H.264/H.265 codecs.
 
-use crate::Result;
+use crate::capture::CaptureRegion;
+use crate::{RemoteCError, Result};
 
 /// Supported video codecs
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +16,8 @@ pub enum VideoCodec {
     VP8,
     /// VP9 codec - better quality than VP8
     VP9,
+    /// Lossless frame codec - fallback when no video codec can be encoded
+    Lossless,
 }
 
 /// Video encoder configuration
@@ -36,6 +39,16 @@ pub struct EncoderConfig {
     pub quality: u8,
     /// Enable hardware acceleration if available
     pub hardware_acceleration: bool,
+    /// Resolution of the input frames when it differs from width/height;
+    /// frames are scaled to width/height before encoding
+    pub source_size: Option<(u32, u32)>,
+    /// Filter used when scaling input frames
+    pub scale_filter: ScaleFilter,
+    /// Temporal layering of the encoded stream
+    pub scalability_mode: ScalabilityMode,
+    /// Required codec profile (e.g. "baseline", "high"); `None` accepts
+    /// whatever the encoder produces
+    pub profile: Option<&'static str>,
 }
 
 impl Default for EncoderConfig {
@@ -49,10 +62,185 @@ impl Default for EncoderConfig {
             keyframe_interval: 60, // 2 seconds at 30fps
             quality: 75,
             hardware_acceleration: true,
+            source_size: None,
+            scale_filter: ScaleFilter::default(),
+            scalability_mode: ScalabilityMode::default(),
+            profile: None,
         }
     }
 }
 
+impl EncoderConfig {
+    /// Mapping between input frame and encoded resolution
+    pub fn scale_mapping(&self) -> ScaleMapping {
+        let (source_width, source_height) = self.source_size.unwrap_or((self.width, self.height));
+        ScaleMapping::new(source_width, source_height, self.width, self.height)
+    }
+}
+
+/// Origin of a region-of-interest hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoiSource {
+    /// Area around the mouse cursor
+    Cursor,
+    /// Window that currently has input focus
+    FocusedWindow,
+    /// Tiles that changed recently
+    ChangedTiles,
+    /// Explicitly requested by the application
+    Manual,
+}
+
+/// Region of interest hint for a single frame
+///
+/// Coordinates are in encoded frame pixels. A positive `quality_offset`
+/// asks the encoder to spend more bits on the region (i.e. lower QP), a
+/// negative one allows it to spend fewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionOfInterest {
+    /// X coordinate of the top-left corner
+    pub x: u32,
+    /// Y coordinate of the top-left corner
+    pub y: u32,
+    /// Width of the region
+    pub width: u32,
+    /// Height of the region
+    pub height: u32,
+    /// Quality offset in QP steps (-51..=51, positive is better quality)
+    pub quality_offset: i8,
+    /// Where the hint came from
+    pub source: RoiSource,
+}
+
+impl RegionOfInterest {
+    /// Maximum magnitude of a quality offset (H.264/H.265 QP range)
+    pub const MAX_QUALITY_OFFSET: i8 = 51;
+
+    /// Create a region of interest
+    pub fn new(x: u32, y: u32, width: u32, height: u32, quality_offset: i8, source: RoiSource) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            quality_offset: quality_offset.clamp(-Self::MAX_QUALITY_OFFSET, Self::MAX_QUALITY_OFFSET),
+            source,
+        }
+    }
+
+    /// Square region centred on the cursor, clipped to the frame
+    pub fn around_cursor(
+        cursor_x: i32,
+        cursor_y: i32,
+        radius: u32,
+        frame_width: u32,
+        frame_height: u32,
+        quality_offset: i8,
+    ) -> Option<Self> {
+        let radius = i32::try_from(radius).unwrap_or(i32::MAX);
+        let region = CaptureRegion {
+            x: cursor_x.saturating_sub(radius),
+            y: cursor_y.saturating_sub(radius),
+            width: radius.unsigned_abs().saturating_mul(2),
+            height: radius.unsigned_abs().saturating_mul(2),
+        };
+        Self::from_capture_region(&region, frame_width, frame_height, quality_offset, RoiSource::Cursor)
+    }
+
+    /// Convert a capture-space region (focused window, changed tile, ...),
+    /// clipping it to the frame. Returns `None` if nothing is left.
+    pub fn from_capture_region(
+        region: &CaptureRegion,
+        frame_width: u32,
+        frame_height: u32,
+        quality_offset: i8,
+        source: RoiSource,
+    ) -> Option<Self> {
+        let x1 = region.x.max(0) as i64;
+        let y1 = region.y.max(0) as i64;
+        let x2 = (i64::from(region.x) + i64::from(region.width)).min(i64::from(frame_width));
+        let y2 = (i64::from(region.y) + i64::from(region.height)).min(i64::from(frame_height));
+
+        if x2 <= x1 || y2 <= y1 {
+            return None;
+        }
+
+        Some(Self::new(
+            x1 as u32,
+            y1 as u32,
+            (x2 - x1) as u32,
+            (y2 - y1) as u32,
+            quality_offset,
+            source,
+        ))
+    }
+}
+
+/// Per-block quality offset map built from region-of-interest hints
+///
+/// Codecs with QP-map or ROI support (NVENC, QSV, x264 `quant_offsets`, ...)
+/// consume this directly. Offsets are stored as QP deltas, so ROI with a
+/// positive quality offset produce negative entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QpOffsetMap {
+    /// Block size in pixels (16 for H.264 macroblocks, 64 for HEVC CTUs)
+    pub block_size: u32,
+    /// Number of block columns
+    pub columns: u32,
+    /// Number of block rows
+    pub rows: u32,
+    /// Row-major QP delta per block
+    pub deltas: Vec<i8>,
+}
+
+impl QpOffsetMap {
+    /// Build a QP delta map for a frame
+    ///
+    /// When regions overlap, the highest quality offset wins for a block.
+    pub fn build(width: u32, height: u32, block_size: u32, regions: &[RegionOfInterest]) -> Self {
+        let block_size = block_size.max(1);
+        let columns = width.div_ceil(block_size);
+        let rows = height.div_ceil(block_size);
+        let mut best: Vec<Option<i8>> = vec![None; (columns * rows) as usize];
+
+        for roi in regions {
+            if roi.width == 0 || roi.height == 0 || roi.x >= width || roi.y >= height {
+                continue;
+            }
+            let col_start = roi.x / block_size;
+            let row_start = roi.y / block_size;
+            let col_end = roi.x.saturating_add(roi.width).min(width).div_ceil(block_size);
+            let row_end = roi.y.saturating_add(roi.height).min(height).div_ceil(block_size);
+
+            for row in row_start..row_end {
+                for col in col_start..col_end {
+                    let entry = &mut best[(row * columns + col) as usize];
+                    *entry = Some(entry.map_or(roi.quality_offset, |q| q.max(roi.quality_offset)));
+                }
+            }
+        }
+
+        Self {
+            block_size,
+            columns,
+            rows,
+            deltas: best.into_iter().map(|q| q.map_or(0, |q| -q)).collect(),
+        }
+    }
+
+    /// QP delta for the block containing pixel (x, y)
+    pub fn delta_at(&self, x: u32, y: u32) -> i8 {
+        let col = (x / self.block_size).min(self.columns.saturating_sub(1));
+        let row = (y / self.block_size).min(self.rows.saturating_sub(1));
+        self.deltas.get((row * self.columns + col) as usize).copied().unwrap_or(0)
+    }
+
+    /// Whether any block has a non-zero delta
+    pub fn is_empty(&self) -> bool {
+        self.deltas.iter().all(|&d| d == 0)
+    }
+}
+
 /// Encoded video frame
 #[derive(Debug)]
 pub struct EncodedFrame {
@@ -64,6 +252,14 @@ pub struct EncodedFrame {
     pub is_keyframe: bool,
     /// Frame sequence number
     pub sequence: u64,
+    /// Simulcast layer the frame belongs to (0 without simulcast)
+    pub layer_id: u8,
+    /// Temporal layer the frame belongs to (0 without temporal scalability)
+    pub temporal_layer: u8,
+    /// Encoded picture width
+    pub width: u32,
+    /// Encoded picture height
+    pub height: u32,
 }
 
 /// Video encoder trait
@@ -71,6 +267,16 @@ pub trait VideoEncoder: Send + Sync {
     /// Configure the encoder
     fn configure(&mut self, config: EncoderConfig) -> Result<()>;
     
+    /// Change the target bitrate of a configured encoder
+    ///
+    /// `config` must match the current configuration apart from its
+    /// bitrate. The default reconfigures the encoder, which may restart the
+    /// stream with a keyframe; encoders that can retune rate control in
+    /// place override this.
+    fn update_bitrate(&mut self, config: EncoderConfig) -> Result<()> {
+        self.configure(config)
+    }
+    
     /// Encode a raw frame (RGBA format)
     fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame>;
     
@@ -82,6 +288,48 @@ pub trait VideoEncoder: Send + Sync {
     
     /// Reset the encoder
     fn reset(&mut self) -> Result<()>;
+    
+    /// Whether the encoder can produce the given temporal layering
+    fn supports_scalability_mode(&self, mode: ScalabilityMode) -> bool {
+        mode == ScalabilityMode::L1T1
+    }
+    
+    /// Whether the encoder spends bits according to region-of-interest hints
+    fn supports_roi(&self) -> bool {
+        false
+    }
+    
+    /// Set region-of-interest hints for subsequent frames, until replaced
+    ///
+    /// Encoders without ROI support ignore the hints.
+    fn set_roi(&mut self, _regions: &[RegionOfInterest]) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Hints last set with [`set_roi`](Self::set_roi)
+    fn roi(&self) -> &[RegionOfInterest] {
+        &[]
+    }
+    
+    /// Encode a raw frame with region-of-interest hints for this frame only
+    ///
+    /// The hints set with [`set_roi`](Self::set_roi) are restored
+    /// afterwards, so later `encode_frame` calls are unaffected.
+    fn encode_frame_with_roi(
+        &mut self,
+        frame: &[u8],
+        timestamp: u64,
+        regions: &[RegionOfInterest],
+    ) -> Result<EncodedFrame> {
+        if !self.supports_roi() {
+            return self.encode_frame(frame, timestamp);
+        }
+        let previous = self.roi().to_vec();
+        self.set_roi(regions)?;
+        let encoded = self.encode_frame(frame, timestamp);
+        self.set_roi(&previous)?;
+        encoded
+    }
 }
 
 /// Encoder statistics
@@ -105,7 +353,12 @@ pub trait VideoDecoder: Send + Sync {
     fn configure(&mut self, codec: VideoCodec) -> Result<()>;
     
     /// Decode an encoded frame
-    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<Vec<u8>>;
+    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<DecodedPicture>;
+    
+    /// Decode a frame directly at the requested output size and format
+    fn decode_frame_to(&mut self, frame: &EncodedFrame, output: &DecoderOutput) -> Result<DecodedPicture> {
+        self.decode_frame(frame)?.into_output(output)
+    }
     
     /// Get decoder statistics
     fn get_stats(&self) -> DecoderStats;
@@ -134,20 +387,52 @@ mod h265;
 mod vp8;
 mod vp9;
 pub mod hardware;
+pub mod lossless;
+pub mod multistream;
+pub mod picture;
+pub mod quality;
+pub mod rate_control;
+pub mod refinement;
+pub mod registry;
+pub mod scale;
+pub mod simulcast;
+pub mod svc;
 
 pub use h264::H264Encoder;
 pub use h265::H265Encoder;
 pub use vp8::VP8Encoder;
 pub use vp9::VP9Encoder;
+pub use multistream::{MonitorStreamFrame, MultiStreamEncoder};
+pub use picture::{DecodedPicture, DecoderOutput, PixelFormat};
+pub use rate_control::{RateControlledEncoder, RateController};
+pub use registry::{BackendKind, EncoderBackend, EncoderRegistry, SelectedEncoder};
+pub use scale::{ScaleFilter, ScaleMapping, Scaler};
+pub use simulcast::{SimulcastConfig, SimulcastEncoder};
+pub use svc::{ScalabilityMode, TemporalLayerFilter};
 
 /// Create a video encoder for the specified codec
+///
+/// Uses the default [`EncoderConfig`] for backend selection; callers are
+/// expected to `configure` the encoder for their actual stream. Fails
+/// rather than falling back to another codec.
 pub fn create_encoder(codec: VideoCodec) -> Result<Box<dyn VideoEncoder>> {
-    match codec {
-        VideoCodec::H264 => Ok(Box::new(H264Encoder::new()?)),
-        VideoCodec::H265 => Ok(Box::new(H265Encoder::new()?)),
-        VideoCodec::VP8 => Ok(Box::new(VP8Encoder::new()?)),
-        VideoCodec::VP9 => Ok(Box::new(VP9Encoder::new()?)),
+    let selected = create_configured_encoder(&EncoderConfig {
+        codec,
+        ..Default::default()
+    })?;
+    if selected.codec != codec {
+        return Err(RemoteCError::EncodingError(format!("No {:?} encoder available", codec)));
     }
+    Ok(selected.encoder)
+}
+
+/// Create and configure the best available encoder for a configuration
+///
+/// Backends are tried in priority order: hardware (only when
+/// `hardware_acceleration` is set), software, then the lossless frame codec.
+/// [`SelectedEncoder::codec`] tells which codec the stream is actually in.
+pub fn create_configured_encoder(config: &EncoderConfig) -> Result<SelectedEncoder> {
+    EncoderRegistry::with_defaults().create_encoder(config)
 }
 
 /// Create a video decoder for the specified codec
@@ -157,5 +442,6 @@ pub fn create_decoder(codec: VideoCodec) -> Result<Box<dyn VideoDecoder>> {
         VideoCodec::H265 => Ok(Box::new(h265::H265Decoder::new()?)),
         VideoCodec::VP8 => Ok(Box::new(vp8::VP8Decoder::new()?)),
         VideoCodec::VP9 => Ok(Box::new(vp9::VP9Decoder::new()?)),
+        VideoCodec::Lossless => Ok(Box::new(lossless::LosslessFrameDecoder::new())),
     }
 }
\ No newline at end of file
@@ -0,0 +1,537 @@
+//! Per-monitor multi-stream encoding
+//!
+//! Instead of stitching every monitor into one large frame,
+//! [`MultiStreamEncoder`] runs an independent [`VideoEncoder`] per monitor at
+//! that monitor's own resolution. All streams share a single bitrate budget
+//! which is shifted towards monitors with recent activity. Viewers subscribe
+//! to the monitors they display; monitors nobody watches are not encoded.
+
+use super::{create_encoder, EncodedFrame, EncoderConfig, EncoderStats, VideoEncoder};
+use crate::capture::Monitor;
+use crate::{RemoteCError, Result};
+use std::collections::{BTreeSet, HashMap};
+
+/// Sample every Nth pixel when estimating activity
+const ACTIVITY_SAMPLE_STEP: usize = 16;
+
+/// Identifier of a viewer subscribing to monitor streams
+pub type ViewerId = u64;
+
+/// Multi-stream encoder configuration
+#[derive(Debug, Clone)]
+pub struct MultiStreamConfig {
+    /// Template for each stream; `bitrate` is the shared budget and the
+    /// resolution is taken from each monitor
+    pub encoder: EncoderConfig,
+    /// Bitrate every watched stream receives regardless of activity
+    pub min_stream_bitrate: u32,
+    /// Share weight of an idle monitor relative to a fully active one
+    pub idle_weight: f64,
+    /// Smoothing factor of the activity moving average (0.0 - 1.0)
+    pub activity_smoothing: f64,
+    /// Relative allocation change needed before an encoder is reconfigured
+    pub reallocation_threshold: f64,
+    /// Minimum time between two reallocations, in microseconds of frame
+    /// timestamps; budget cuts apply immediately
+    pub min_reallocation_interval: u64,
+}
+
+impl Default for MultiStreamConfig {
+    fn default() -> Self {
+        Self {
+            encoder: EncoderConfig::default(),
+            min_stream_bitrate: 250_000,
+            idle_weight: 0.05,
+            activity_smoothing: 0.3,
+            reallocation_threshold: 0.1,
+            min_reallocation_interval: 500_000,
+        }
+    }
+}
+
+/// Encoded frame tagged with the monitor it belongs to
+#[derive(Debug)]
+pub struct MonitorStreamFrame {
+    /// Index of the source monitor
+    pub monitor_index: usize,
+    /// Encoded frame
+    pub frame: EncodedFrame,
+}
+
+/// Per-stream statistics
+#[derive(Debug, Clone)]
+pub struct MonitorStreamStats {
+    /// Index of the source monitor
+    pub monitor_index: usize,
+    /// Stream width
+    pub width: u32,
+    /// Stream height
+    pub height: u32,
+    /// Currently allocated bitrate
+    pub bitrate: u32,
+    /// Smoothed fraction of pixels changing per frame
+    pub activity: f64,
+    /// Number of viewers subscribed to the stream
+    pub subscribers: usize,
+    /// Number of times the encoder was reconfigured for a new bitrate
+    pub reconfigurations: u64,
+    /// Statistics of the underlying encoder
+    pub encoder: EncoderStats,
+}
+
+struct MonitorStream {
+    monitor_index: usize,
+    width: u32,
+    height: u32,
+    encoder: Box<dyn VideoEncoder>,
+    bitrate: u32,
+    activity: f64,
+    previous: Option<Vec<u8>>,
+    reconfigurations: u64,
+}
+
+/// Encoder producing one video stream per monitor
+pub struct MultiStreamEncoder {
+    config: MultiStreamConfig,
+    streams: Vec<MonitorStream>,
+    subscriptions: HashMap<ViewerId, BTreeSet<usize>>,
+    /// Latest frame timestamp seen
+    clock: u64,
+    /// Timestamp of the last reallocation
+    last_reallocation: Option<u64>,
+}
+
+impl MultiStreamEncoder {
+    /// Create one encoder of the configured codec per monitor
+    pub fn new(config: MultiStreamConfig, monitors: &[Monitor]) -> Result<Self> {
+        let codec = config.encoder.codec;
+        Self::with_factory(config, monitors, |_| create_encoder(codec))
+    }
+
+    /// Create per-monitor encoders with a custom factory
+    pub fn with_factory<F>(config: MultiStreamConfig, monitors: &[Monitor], mut factory: F) -> Result<Self>
+    where
+        F: FnMut(&Monitor) -> Result<Box<dyn VideoEncoder>>,
+    {
+        if monitors.is_empty() {
+            return Err(RemoteCError::EncodingError("No monitors to encode".to_string()));
+        }
+
+        let share = config.encoder.bitrate / monitors.len() as u32;
+        let mut streams = Vec::with_capacity(monitors.len());
+        for monitor in monitors {
+            let mut stream = MonitorStream {
+                monitor_index: monitor.index,
+                width: monitor.bounds.width,
+                height: monitor.bounds.height,
+                encoder: factory(monitor)?,
+                bitrate: 0,
+                activity: 0.0,
+                previous: None,
+                reconfigurations: 0,
+            };
+            Self::configure_stream(&config.encoder, &mut stream, share)?;
+            streams.push(stream);
+        }
+
+        Ok(Self {
+            config,
+            streams,
+            subscriptions: HashMap::new(),
+            clock: 0,
+            last_reallocation: None,
+        })
+    }
+
+    /// Subscribe a viewer to a set of monitors, replacing its previous set
+    pub fn subscribe(&mut self, viewer: ViewerId, monitors: &[usize]) -> Result<()> {
+        if let Some(&unknown) = monitors.iter().find(|&&m| self.position(m).is_none()) {
+            return Err(RemoteCError::EncodingError(format!("Unknown monitor index {}", unknown)));
+        }
+
+        let previous = self.subscriptions.remove(&viewer).unwrap_or_default();
+        let requested: BTreeSet<usize> = monitors.iter().copied().collect();
+
+        // Newly joined streams restart so the viewer begins with a keyframe
+        for &monitor in requested.difference(&previous) {
+            if let Some(pos) = self.position(monitor) {
+                self.streams[pos].encoder.reset()?;
+            }
+        }
+
+        if !requested.is_empty() {
+            self.subscriptions.insert(viewer, requested);
+        }
+        self.rebalance()
+    }
+
+    /// Remove all subscriptions of a viewer
+    pub fn unsubscribe(&mut self, viewer: ViewerId) -> Result<()> {
+        if self.subscriptions.remove(&viewer).is_some() {
+            self.rebalance()?;
+        }
+        Ok(())
+    }
+
+    /// Viewers subscribed to a monitor
+    pub fn subscribers(&self, monitor_index: usize) -> Vec<ViewerId> {
+        let mut viewers: Vec<ViewerId> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, monitors)| monitors.contains(&monitor_index))
+            .map(|(&viewer, _)| viewer)
+            .collect();
+        viewers.sort_unstable();
+        viewers
+    }
+
+    /// Change the shared bitrate budget
+    pub fn set_total_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        self.config.encoder.bitrate = bitrate;
+        self.rebalance()
+    }
+
+    /// Encode a captured BGRA frame of one monitor
+    ///
+    /// Returns `None` when no viewer is subscribed to the monitor.
+    pub fn encode_monitor(
+        &mut self,
+        monitor_index: usize,
+        frame: &[u8],
+        timestamp: u64,
+    ) -> Result<Option<MonitorStreamFrame>> {
+        let pos = self
+            .position(monitor_index)
+            .ok_or_else(|| RemoteCError::EncodingError(format!("Unknown monitor index {}", monitor_index)))?;
+
+        if self.subscribers(monitor_index).is_empty() {
+            // Forget the reference so activity restarts when someone subscribes
+            self.streams[pos].previous = None;
+            return Ok(None);
+        }
+
+        self.clock = self.clock.max(timestamp);
+        let alpha = self.config.activity_smoothing;
+        let stream = &mut self.streams[pos];
+        let sample = change_ratio(stream.previous.as_deref(), frame);
+        stream.activity = stream.activity * (1.0 - alpha) + sample * alpha;
+        match &mut stream.previous {
+            Some(previous) if previous.len() == frame.len() => previous.copy_from_slice(frame),
+            previous => *previous = Some(frame.to_vec()),
+        }
+
+        self.rebalance()?;
+
+        let frame = self.streams[pos].encoder.encode_frame(frame, timestamp)?;
+        Ok(Some(MonitorStreamFrame { monitor_index, frame }))
+    }
+
+    /// Current bitrate allocation as (monitor index, bitrate)
+    pub fn allocations(&self) -> Vec<(usize, u32)> {
+        self.streams.iter().map(|s| (s.monitor_index, s.bitrate)).collect()
+    }
+
+    /// Per-stream statistics
+    pub fn stats(&self) -> Vec<MonitorStreamStats> {
+        self.streams
+            .iter()
+            .map(|s| MonitorStreamStats {
+                monitor_index: s.monitor_index,
+                width: s.width,
+                height: s.height,
+                bitrate: s.bitrate,
+                activity: s.activity,
+                subscribers: self.subscribers(s.monitor_index).len(),
+                reconfigurations: s.reconfigurations,
+                encoder: s.encoder.get_stats(),
+            })
+            .collect()
+    }
+
+    /// Flush all encoders
+    pub fn flush(&mut self) -> Result<Vec<MonitorStreamFrame>> {
+        let mut frames = Vec::new();
+        for stream in &mut self.streams {
+            let monitor_index = stream.monitor_index;
+            frames.extend(
+                stream
+                    .encoder
+                    .flush()?
+                    .into_iter()
+                    .map(|frame| MonitorStreamFrame { monitor_index, frame }),
+            );
+        }
+        Ok(frames)
+    }
+
+    fn position(&self, monitor_index: usize) -> Option<usize> {
+        self.streams.iter().position(|s| s.monitor_index == monitor_index)
+    }
+
+    /// Split the budget across watched streams by activity
+    fn rebalance(&mut self) -> Result<()> {
+        let watched: Vec<usize> = (0..self.streams.len())
+            .filter(|&pos| !self.subscribers(self.streams[pos].monitor_index).is_empty())
+            .collect();
+        if watched.is_empty() {
+            return Ok(());
+        }
+
+        let total = f64::from(self.config.encoder.bitrate);
+        let floor = f64::from(self.config.min_stream_bitrate).min(total / watched.len() as f64);
+        let remainder = total - floor * watched.len() as f64;
+        let weights: Vec<f64> = watched
+            .iter()
+            .map(|&pos| self.config.idle_weight + self.streams[pos].activity)
+            .collect();
+        let weight_sum: f64 = weights.iter().sum();
+        let targets: Vec<u32> = weights
+            .iter()
+            .map(|weight| {
+                let share = if weight_sum > 0.0 {
+                    remainder * weight / weight_sum
+                } else {
+                    remainder / watched.len() as f64
+                };
+                (floor + share) as u32
+            })
+            .collect();
+
+        // Small or frequent changes are skipped to avoid retuning encoders on
+        // every frame, but once any stream moves the whole allocation is
+        // applied so the streams never add up to more than the budget
+        let threshold = self.config.reallocation_threshold;
+        let allocated: u64 = watched.iter().map(|&pos| u64::from(self.streams[pos].bitrate)).sum();
+        let over_budget = allocated > u64::from(self.config.encoder.bitrate);
+        let significant = watched.iter().zip(&targets).any(|(&pos, &target)| {
+            let current = f64::from(self.streams[pos].bitrate.max(1));
+            (f64::from(target) - current).abs() / current > threshold
+        });
+        let recent = self
+            .last_reallocation
+            .is_some_and(|last| self.clock.saturating_sub(last) < self.config.min_reallocation_interval);
+        if !over_budget && (!significant || recent) {
+            return Ok(());
+        }
+
+        for (&pos, target) in watched.iter().zip(targets) {
+            let stream = &mut self.streams[pos];
+            if stream.bitrate != target {
+                stream.encoder.update_bitrate(Self::stream_config(&self.config.encoder, stream, target))?;
+                stream.bitrate = target;
+                stream.reconfigurations += 1;
+            }
+        }
+        self.last_reallocation = Some(self.clock);
+
+        Ok(())
+    }
+
+    fn stream_config(template: &EncoderConfig, stream: &MonitorStream, bitrate: u32) -> EncoderConfig {
+        EncoderConfig {
+            bitrate,
+            width: stream.width,
+            height: stream.height,
+            ..template.clone()
+        }
+    }
+
+    fn configure_stream(template: &EncoderConfig, stream: &mut MonitorStream, bitrate: u32) -> Result<()> {
+        stream.encoder.configure(Self::stream_config(template, stream, bitrate))?;
+        stream.bitrate = bitrate;
+        stream.reconfigurations += 1;
+        Ok(())
+    }
+}
+
+/// Fraction of sampled pixels that differ between two frames
+fn change_ratio(previous: Option<&[u8]>, frame: &[u8]) -> f64 {
+    let Some(previous) = previous.filter(|p| p.len() == frame.len()) else {
+        return 1.0;
+    };
+
+    let step = ACTIVITY_SAMPLE_STEP * 4;
+    let (mut sampled, mut changed) = (0usize, 0usize);
+    for offset in (0..frame.len().saturating_sub(3)).step_by(step) {
+        sampled += 1;
+        if frame[offset..offset + 4] != previous[offset..offset + 4] {
+            changed += 1;
+        }
+    }
+
+    if sampled == 0 {
+        0.0
+    } else {
+        changed as f64 / sampled as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::MonitorBounds;
+    use crate::video::VideoCodec;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn encoder() -> MultiStreamEncoder {
+        let config = MultiStreamConfig {
+            encoder: EncoderConfig {
+                codec: VideoCodec::VP8,
+                bitrate: 4_000_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        MultiStreamEncoder::new(config, &[Monitor::test(0, MonitorBounds::new(0, 0, 64, 32)), Monitor::test(1, MonitorBounds::new(1920, 0, 32, 32))]).unwrap()
+    }
+
+    fn frame(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; (width * height * 4) as usize]
+    }
+
+    #[test]
+    fn test_only_subscribed_monitors_are_encoded() {
+        let mut enc = encoder();
+        assert!(enc.encode_monitor(0, &frame(64, 32, 0), 0).unwrap().is_none());
+
+        enc.subscribe(7, &[1]).unwrap();
+        assert!(enc.encode_monitor(0, &frame(64, 32, 0), 0).unwrap().is_none());
+
+        let out = enc.encode_monitor(1, &frame(32, 32, 0), 0).unwrap().unwrap();
+        assert_eq!(out.monitor_index, 1);
+        assert!(out.frame.is_keyframe);
+        assert_eq!(enc.subscribers(1), vec![7]);
+
+        enc.unsubscribe(7).unwrap();
+        assert!(enc.subscribers(1).is_empty());
+        assert!(enc.subscribe(7, &[5]).is_err());
+    }
+
+    #[test]
+    fn test_budget_follows_activity() {
+        let mut enc = encoder();
+        enc.subscribe(1, &[0, 1]).unwrap();
+
+        for i in 0..20u8 {
+            let ts = u64::from(i) * 33_333;
+            enc.encode_monitor(0, &frame(64, 32, i), ts).unwrap();
+            enc.encode_monitor(1, &frame(32, 32, 0), ts).unwrap();
+        }
+
+        let allocations = enc.allocations();
+        assert!(allocations[0].1 > allocations[1].1 * 4);
+        assert!(allocations.iter().map(|&(_, b)| u64::from(b)).sum::<u64>() <= 4_000_000);
+        assert!(allocations[1].1 >= 250_000);
+    }
+
+    #[test]
+    fn test_small_changes_do_not_reconfigure() {
+        let mut enc = encoder();
+        enc.subscribe(1, &[0, 1]).unwrap();
+
+        let reconfigurations = |enc: &MultiStreamEncoder| -> u64 {
+            enc.stats().iter().map(|s| s.reconfigurations).sum()
+        };
+
+        // Let both streams settle on a steady activity level
+        for i in 0..30u64 {
+            enc.encode_monitor(0, &frame(64, 32, 0), i * 33_333).unwrap();
+            enc.encode_monitor(1, &frame(32, 32, 0), i * 33_333).unwrap();
+        }
+        let settled = reconfigurations(&enc);
+
+        for i in 30..60u64 {
+            enc.encode_monitor(0, &frame(64, 32, 0), i * 33_333).unwrap();
+            enc.encode_monitor(1, &frame(32, 32, 0), i * 33_333).unwrap();
+        }
+        assert_eq!(reconfigurations(&enc), settled);
+    }
+
+    #[test]
+    fn test_allocations_never_exceed_budget() {
+        let mut enc = encoder();
+        enc.subscribe(1, &[0, 1]).unwrap();
+        let watched_total = |enc: &MultiStreamEncoder| -> u64 {
+            enc.allocations().iter().map(|&(_, bitrate)| u64::from(bitrate)).sum()
+        };
+
+        // Activity moves from one monitor to the other
+        for i in 0..40u8 {
+            let ts = u64::from(i) * 33_333;
+            let (first, second) = if i < 20 { (i, 0) } else { (0, i) };
+            enc.encode_monitor(0, &frame(64, 32, first), ts).unwrap();
+            enc.encode_monitor(1, &frame(32, 32, second), ts).unwrap();
+            assert!(watched_total(&enc) <= 4_000_000, "frame {}: {:?}", i, enc.allocations());
+        }
+
+        // A budget cut below the reallocation threshold still applies
+        enc.set_total_bitrate(3_800_000).unwrap();
+        assert!(watched_total(&enc) <= 3_800_000, "{:?}", enc.allocations());
+    }
+
+    /// VP8 encoder counting full reconfigurations
+    struct CountingEncoder {
+        inner: Box<dyn VideoEncoder>,
+        configures: Arc<AtomicUsize>,
+    }
+
+    impl VideoEncoder for CountingEncoder {
+        fn configure(&mut self, config: EncoderConfig) -> Result<()> {
+            self.configures.fetch_add(1, Ordering::SeqCst);
+            self.inner.configure(config)
+        }
+
+        fn update_bitrate(&mut self, _config: EncoderConfig) -> Result<()> {
+            Ok(())
+        }
+
+        fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame> {
+            self.inner.encode_frame(frame, timestamp)
+        }
+
+        fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+            self.inner.flush()
+        }
+
+        fn get_stats(&self) -> EncoderStats {
+            self.inner.get_stats()
+        }
+
+        fn reset(&mut self) -> Result<()> {
+            self.inner.reset()
+        }
+    }
+
+    #[test]
+    fn test_reallocation_is_rate_limited_and_keeps_encoders() {
+        let configures = Arc::new(AtomicUsize::new(0));
+        let config = MultiStreamConfig {
+            encoder: EncoderConfig { codec: VideoCodec::VP8, bitrate: 4_000_000, ..Default::default() },
+            ..Default::default()
+        };
+        let mut enc = MultiStreamEncoder::with_factory(config, &[Monitor::test(0, MonitorBounds::new(0, 0, 64, 32)), Monitor::test(1, MonitorBounds::new(1920, 0, 32, 32))], |_| {
+            Ok(Box::new(CountingEncoder { inner: create_encoder(VideoCodec::VP8)?, configures: configures.clone() }))
+        })
+        .unwrap();
+        enc.subscribe(1, &[0, 1]).unwrap();
+
+        // Activity flips between the monitors every few frames for 2 seconds
+        let mut changes = Vec::new();
+        for i in 0..60u8 {
+            let ts = u64::from(i) * 33_333;
+            let before = enc.allocations();
+            let (first, second) = if (i / 5) % 2 == 0 { (i, 0) } else { (0, i) };
+            enc.encode_monitor(0, &frame(64, 32, first), ts).unwrap();
+            enc.encode_monitor(1, &frame(32, 32, second), ts).unwrap();
+            if enc.allocations() != before {
+                changes.push(ts);
+            }
+        }
+
+        assert!(changes.len() >= 2, "{:?}", changes);
+        assert!(changes.windows(2).all(|pair| pair[1] - pair[0] >= 500_000), "{:?}", changes);
+        // Bitrate changes never recreate the encoders
+        assert_eq!(configures.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,312 @@
+//! Decoded pictures and viewer output conversion
+
+use super::scale::{self, ScaleFilter, ScaleMapping, Scaler};
+use crate::{RemoteCError, Result};
+
+/// Pixel layout of a decoded picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelFormat {
+    /// Packed 8-bit blue, green, red, alpha
+    #[default]
+    Bgra,
+    /// Packed 8-bit red, green, blue, alpha
+    Rgba,
+    /// Planar YUV 4:2:0 (Y plane, then U, then V)
+    I420,
+}
+
+impl PixelFormat {
+    /// Size in bytes of a tightly packed picture
+    pub fn frame_size(self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        match self {
+            PixelFormat::Bgra | PixelFormat::Rgba => w * h * 4,
+            PixelFormat::I420 => w * h + 2 * w.div_ceil(2) * h.div_ceil(2),
+        }
+    }
+
+    /// Row stride in bytes of the first plane of a tightly packed picture
+    pub fn default_stride(self, width: u32) -> usize {
+        match self {
+            PixelFormat::Bgra | PixelFormat::Rgba => width as usize * 4,
+            PixelFormat::I420 => width as usize,
+        }
+    }
+}
+
+/// Requested decoder output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecoderOutput {
+    /// Output size, e.g. the viewer window; `None` keeps the decoded size
+    pub size: Option<(u32, u32)>,
+    /// Output pixel format
+    pub format: PixelFormat,
+    /// Filter used when the size differs from the decoded size
+    pub filter: ScaleFilter,
+}
+
+/// Decoded video picture
+#[derive(Debug, Clone)]
+pub struct DecodedPicture {
+    /// Picture width in pixels
+    pub width: u32,
+    /// Picture height in pixels
+    pub height: u32,
+    /// Row stride of the first plane in bytes; I420 chroma planes use
+    /// half of it, rounded up
+    pub stride: usize,
+    /// Pixel format
+    pub format: PixelFormat,
+    /// Pixel data
+    pub data: Vec<u8>,
+}
+
+impl DecodedPicture {
+    /// Create a tightly packed picture, validating the data size
+    pub fn new(width: u32, height: u32, format: PixelFormat, data: Vec<u8>) -> Result<Self> {
+        let expected = format.frame_size(width, height);
+        if data.len() != expected {
+            return Err(RemoteCError::DecodingError(format!(
+                "Invalid picture size: expected {}, got {}",
+                expected,
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            stride: format.default_stride(width),
+            format,
+            data,
+        })
+    }
+
+    /// Black picture of the given size
+    pub fn black(width: u32, height: u32, format: PixelFormat) -> Self {
+        let data = match format {
+            PixelFormat::Bgra | PixelFormat::Rgba => {
+                [0, 0, 0, 255].repeat(width as usize * height as usize)
+            }
+            PixelFormat::I420 => {
+                let luma = width as usize * height as usize;
+                let mut data = vec![16u8; luma];
+                data.resize(format.frame_size(width, height), 128);
+                data
+            }
+        };
+
+        Self {
+            width,
+            height,
+            stride: format.default_stride(width),
+            format,
+            data,
+        }
+    }
+
+    /// Mapping from this picture to the requested output size
+    pub fn output_mapping(&self, output: &DecoderOutput) -> ScaleMapping {
+        let (width, height) = output.size.unwrap_or((self.width, self.height));
+        ScaleMapping::new(self.width, self.height, width, height)
+    }
+
+    /// Convert to the requested output size and format
+    pub fn into_output(self, output: &DecoderOutput) -> Result<Self> {
+        self.into_output_with(output, &mut None)
+    }
+
+    /// Convert like [`into_output`](Self::into_output), reusing the scaler
+    /// kept in `scaler` while the sizes stay the same
+    pub fn into_output_with(self, output: &DecoderOutput, scaler: &mut Option<Scaler>) -> Result<Self> {
+        let mapping = self.output_mapping(output);
+        let picture = self.into_packed()?;
+
+        let picture = match (picture.format, output.format) {
+            (PixelFormat::I420, PixelFormat::I420) => {
+                if mapping.is_identity() {
+                    return Ok(picture);
+                }
+                let data = scale::cached_scaler(scaler, mapping, output.filter)?.scale_i420(&picture.data)?;
+                return Self::new(mapping.target_width, mapping.target_height, PixelFormat::I420, data);
+            }
+            (_, PixelFormat::I420) => {
+                return Err(RemoteCError::DecodingError(
+                    "Conversion from packed RGB to I420 is not supported".to_string(),
+                ));
+            }
+            (PixelFormat::I420, _) => {
+                // Scale the planes first: cheaper when downscaling to a window
+                let scaled = if mapping.is_identity() {
+                    picture.data
+                } else {
+                    scale::cached_scaler(scaler, mapping, output.filter)?.scale_i420(&picture.data)?
+                };
+                let bgra = i420_to_bgra(&scaled, mapping.target_width, mapping.target_height);
+                Self::new(mapping.target_width, mapping.target_height, PixelFormat::Bgra, bgra)?
+            }
+            _ => {
+                if mapping.is_identity() {
+                    picture
+                } else {
+                    let data = scale::cached_scaler(scaler, mapping, output.filter)?.scale_bgra(&picture.data)?;
+                    Self::new(mapping.target_width, mapping.target_height, picture.format, data)?
+                }
+            }
+        };
+
+        Ok(picture.with_packed_format(output.format))
+    }
+
+    /// Remove row padding, from every plane for I420
+    fn into_packed(self) -> Result<Self> {
+        let row = self.format.default_stride(self.width);
+        if self.stride == row {
+            return Ok(self);
+        }
+        if self.stride < row {
+            return Err(RemoteCError::DecodingError(format!(
+                "Picture stride {} is smaller than its rows ({} bytes)",
+                self.stride, row
+            )));
+        }
+
+        // (stride, row length, rows) of each plane
+        let height = self.height as usize;
+        let planes = match self.format {
+            PixelFormat::Bgra | PixelFormat::Rgba => vec![(self.stride, row, height)],
+            PixelFormat::I420 => {
+                let chroma = (self.stride.div_ceil(2), row.div_ceil(2), height.div_ceil(2));
+                vec![(self.stride, row, height), chroma, chroma]
+            }
+        };
+        let padded: usize = planes.iter().map(|&(stride, _, rows)| stride * rows).sum();
+        if self.data.len() < padded {
+            return Err(RemoteCError::DecodingError(format!(
+                "Invalid picture size: expected {}, got {}",
+                padded,
+                self.data.len()
+            )));
+        }
+
+        let mut data = Vec::with_capacity(self.format.frame_size(self.width, self.height));
+        let mut offset = 0;
+        for (stride, row, rows) in planes {
+            for y in 0..rows {
+                data.extend_from_slice(&self.data[offset + y * stride..offset + y * stride + row]);
+            }
+            offset += stride * rows;
+        }
+        Ok(Self { stride: row, data, ..self })
+    }
+
+    /// Swap red and blue between the packed formats if needed
+    fn with_packed_format(mut self, format: PixelFormat) -> Self {
+        if self.format != format && format != PixelFormat::I420 {
+            for px in self.data.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+            self.format = format;
+        }
+        self
+    }
+}
+
+/// Convert I420 to BGRA (BT.601 limited range)
+fn i420_to_bgra(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let cw = w.div_ceil(2);
+    let (y_plane, chroma) = data.split_at(w * h);
+    let (u_plane, v_plane) = chroma.split_at(cw * h.div_ceil(2));
+
+    let mut out = Vec::with_capacity(w * h * 4);
+    for y in 0..h {
+        for x in 0..w {
+            let c = (y / 2) * cw + x / 2;
+            let luma = (i32::from(y_plane[y * w + x]) - 16) * 298;
+            let u = i32::from(u_plane[c]) - 128;
+            let v = i32::from(v_plane[c]) - 128;
+
+            let r = (luma + 409 * v + 128) >> 8;
+            let g = (luma - 100 * u - 208 * v + 128) >> 8;
+            let b = (luma + 516 * u + 128) >> 8;
+            out.extend_from_slice(&[b.clamp(0, 255) as u8, g.clamp(0, 255) as u8, r.clamp(0, 255) as u8, 255]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_black_i420_converts_to_black_bgra() {
+        let picture = DecodedPicture::black(6, 4, PixelFormat::I420);
+        assert_eq!(picture.data.len(), 6 * 4 + 2 * 3 * 2);
+
+        let bgra = picture.into_output(&DecoderOutput::default()).unwrap();
+        assert_eq!((bgra.width, bgra.height, bgra.stride), (6, 4, 24));
+        assert!(bgra.data.chunks(4).all(|px| px == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_output_scaled_and_swizzled() {
+        let data = [10u8, 20, 30, 255].repeat(8 * 8);
+        let picture = DecodedPicture::new(8, 8, PixelFormat::Bgra, data).unwrap();
+
+        let output = DecoderOutput {
+            size: Some((4, 2)),
+            format: PixelFormat::Rgba,
+            filter: ScaleFilter::Bilinear,
+        };
+        let rgba = picture.into_output(&output).unwrap();
+        assert_eq!((rgba.width, rgba.height, rgba.format), (4, 2, PixelFormat::Rgba));
+        assert!(rgba.data.chunks(4).all(|px| px == [30, 20, 10, 255]));
+    }
+
+    #[test]
+    fn test_padded_rows_are_removed() {
+        // 2x2 BGRA with 4 bytes of padding per row
+        let data = vec![1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 3, 3, 3, 3, 4, 4, 4, 4, 0, 0, 0, 0];
+        let picture = DecodedPicture {
+            width: 2,
+            height: 2,
+            stride: 12,
+            format: PixelFormat::Bgra,
+            data,
+        };
+
+        let packed = picture.into_output(&DecoderOutput::default()).unwrap();
+        assert_eq!(packed.stride, 8);
+        assert_eq!(packed.data, vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+        assert!(DecodedPicture::new(2, 2, PixelFormat::Bgra, vec![0; 3]).is_err());
+    }
+
+    #[test]
+    fn test_padded_i420_planes_are_repacked() {
+        // 4x2 I420 with a 6 byte luma stride and 3 byte chroma stride
+        let data = vec![
+            16, 17, 18, 19, 0, 0, //
+            20, 21, 22, 23, 0, 0, //
+            100, 101, 0, //
+            150, 151, 0,
+        ];
+        let picture = DecodedPicture { width: 4, height: 2, stride: 6, format: PixelFormat::I420, data };
+        let output = DecoderOutput { format: PixelFormat::I420, ..Default::default() };
+
+        let packed = picture.clone().into_output(&output).unwrap();
+        assert_eq!(packed.stride, 4);
+        assert_eq!(packed.data, vec![16, 17, 18, 19, 20, 21, 22, 23, 100, 101, 150, 151]);
+
+        // Scaling reuses the cached scaler for following pictures
+        let mut scaler = None;
+        let output = DecoderOutput { size: Some((2, 2)), ..output };
+        let scaled = picture.clone().into_output_with(&output, &mut scaler).unwrap();
+        assert_eq!((scaled.width, scaled.height, scaled.data.len()), (2, 2, 6));
+        assert_eq!(scaler.as_ref().map(Scaler::mapping), Some(ScaleMapping::new(4, 2, 2, 2)));
+
+        let truncated = DecodedPicture { data: vec![0; 10], ..picture };
+        assert!(truncated.into_output(&output).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Objective video quality metrics
+//!
+//! Compares a source BGRA frame against the decoded frame that a viewer
+//! would see. Provides PSNR, SSIM and an edge-preservation score that tracks
+//! how legible text and thin UI lines remain after encoding.
+
+use crate::{RemoteCError, Result};
+use std::collections::VecDeque;
+
+/// SSIM window size in pixels
+const SSIM_WINDOW: usize = 8;
+/// Step between SSIM windows
+const SSIM_STEP: usize = 4;
+/// SSIM stabilisation constants for 8-bit samples
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+/// Gradient magnitude below which a pixel is not considered an edge
+const EDGE_THRESHOLD: f64 = 32.0;
+
+/// Quality of a decoded frame relative to its source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityReport {
+    /// Peak signal-to-noise ratio over the colour channels in dB
+    /// (infinite for identical frames)
+    pub psnr: f64,
+    /// Mean structural similarity of the luma plane (0.0 - 1.0)
+    pub ssim: f64,
+    /// Fraction of source edge strength preserved (0.0 - 1.0)
+    pub edge_preservation: f64,
+}
+
+/// Minimum acceptable quality for adaptive decisions
+#[derive(Debug, Clone, Copy)]
+pub struct QualityThresholds {
+    /// Minimum PSNR in dB
+    pub min_psnr: f64,
+    /// Minimum SSIM
+    pub min_ssim: f64,
+    /// Minimum edge preservation
+    pub min_edge_preservation: f64,
+}
+
+impl Default for QualityThresholds {
+    fn default() -> Self {
+        Self {
+            min_psnr: 35.0,
+            min_ssim: 0.95,
+            min_edge_preservation: 0.85,
+        }
+    }
+}
+
+impl QualityReport {
+    /// Check whether this report satisfies the given thresholds
+    pub fn meets(&self, thresholds: &QualityThresholds) -> bool {
+        self.psnr >= thresholds.min_psnr
+            && self.ssim >= thresholds.min_ssim
+            && self.edge_preservation >= thresholds.min_edge_preservation
+    }
+}
+
+/// Compute all quality metrics between a source and a decoded BGRA frame
+pub fn measure(reference: &[u8], decoded: &[u8], width: u32, height: u32) -> Result<QualityReport> {
+    validate(reference, decoded, width, height)?;
+
+    let ref_luma = luma_plane(reference);
+    let dec_luma = luma_plane(decoded);
+    let (width, height) = (width as usize, height as usize);
+
+    Ok(QualityReport {
+        psnr: psnr_unchecked(reference, decoded),
+        ssim: ssim_plane(&ref_luma, &dec_luma, width, height),
+        edge_preservation: edge_preservation_plane(&ref_luma, &dec_luma, width, height),
+    })
+}
+
+/// Peak signal-to-noise ratio over the B, G and R channels in dB
+pub fn psnr(reference: &[u8], decoded: &[u8], width: u32, height: u32) -> Result<f64> {
+    validate(reference, decoded, width, height)?;
+    Ok(psnr_unchecked(reference, decoded))
+}
+
+/// Mean structural similarity index of the luma plane
+pub fn ssim(reference: &[u8], decoded: &[u8], width: u32, height: u32) -> Result<f64> {
+    validate(reference, decoded, width, height)?;
+    Ok(ssim_plane(
+        &luma_plane(reference),
+        &luma_plane(decoded),
+        width as usize,
+        height as usize,
+    ))
+}
+
+/// Edge-preservation score of the luma plane
+///
+/// Compares Sobel gradient magnitudes at pixels that are edges in either
+/// frame. Blurred or smeared text scores low even when PSNR is acceptable.
+pub fn edge_preservation(reference: &[u8], decoded: &[u8], width: u32, height: u32) -> Result<f64> {
+    validate(reference, decoded, width, height)?;
+    Ok(edge_preservation_plane(
+        &luma_plane(reference),
+        &luma_plane(decoded),
+        width as usize,
+        height as usize,
+    ))
+}
+
+fn validate(reference: &[u8], decoded: &[u8], width: u32, height: u32) -> Result<()> {
+    let expected = width as usize * height as usize * 4;
+    if width == 0 || height == 0 {
+        return Err(RemoteCError::Other(format!("Invalid frame dimensions: {}x{}", width, height)));
+    }
+    if reference.len() != expected || decoded.len() != expected {
+        return Err(RemoteCError::Other(format!(
+            "Invalid frame size: expected {}, got {} and {}",
+            expected,
+            reference.len(),
+            decoded.len()
+        )));
+    }
+    Ok(())
+}
+
+fn psnr_unchecked(reference: &[u8], decoded: &[u8]) -> f64 {
+    let mut squared_error = 0u64;
+    for (a, b) in reference.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+        for channel in 0..3 {
+            let diff = i64::from(a[channel]) - i64::from(b[channel]);
+            squared_error += (diff * diff) as u64;
+        }
+    }
+
+    if squared_error == 0 {
+        return f64::INFINITY;
+    }
+
+    let samples = (reference.len() / 4 * 3) as f64;
+    let mse = squared_error as f64 / samples;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/// Convert BGRA to BT.601 luma
+fn luma_plane(bgra: &[u8]) -> Vec<f64> {
+    bgra.chunks_exact(4)
+        .map(|px| 0.114 * f64::from(px[0]) + 0.587 * f64::from(px[1]) + 0.299 * f64::from(px[2]))
+        .collect()
+}
+
+fn ssim_plane(reference: &[f64], decoded: &[f64], width: usize, height: usize) -> f64 {
+    // Frames smaller than one window are compared as a single window
+    let window_w = SSIM_WINDOW.min(width);
+    let window_h = SSIM_WINDOW.min(height);
+
+    let mut total = 0.0;
+    let mut windows = 0usize;
+
+    let mut y = 0;
+    while y + window_h <= height {
+        let mut x = 0;
+        while x + window_w <= width {
+            total += ssim_window(reference, decoded, width, x, y, window_w, window_h);
+            windows += 1;
+            x += SSIM_STEP;
+        }
+        y += SSIM_STEP;
+    }
+
+    total / windows as f64
+}
+
+fn ssim_window(
+    reference: &[f64],
+    decoded: &[f64],
+    stride: usize,
+    x0: usize,
+    y0: usize,
+    window_w: usize,
+    window_h: usize,
+) -> f64 {
+    let n = (window_w * window_h) as f64;
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+
+    for y in y0..y0 + window_h {
+        for x in x0..x0 + window_w {
+            let a = reference[y * stride + x];
+            let b = decoded[y * stride + x];
+            sum_a += a;
+            sum_b += b;
+            sum_aa += a * a;
+            sum_bb += b * b;
+            sum_ab += a * b;
+        }
+    }
+
+    let mean_a = sum_a / n;
+    let mean_b = sum_b / n;
+    let var_a = (sum_aa / n - mean_a * mean_a).max(0.0);
+    let var_b = (sum_bb / n - mean_b * mean_b).max(0.0);
+    let covariance = sum_ab / n - mean_a * mean_b;
+
+    ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+        / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2))
+}
+
+/// Sobel gradient magnitude, zero on the one-pixel border
+fn gradient_plane(luma: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let mut gradient = vec![0.0; luma.len()];
+    if width < 3 || height < 3 {
+        return gradient;
+    }
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let p = |dx: usize, dy: usize| luma[(y + dy - 1) * width + (x + dx - 1)];
+            let gx = p(2, 0) + 2.0 * p(2, 1) + p(2, 2) - p(0, 0) - 2.0 * p(0, 1) - p(0, 2);
+            let gy = p(0, 2) + 2.0 * p(1, 2) + p(2, 2) - p(0, 0) - 2.0 * p(1, 0) - p(2, 0);
+            gradient[y * width + x] = (gx * gx + gy * gy).sqrt();
+        }
+    }
+
+    gradient
+}
+
+fn edge_preservation_plane(reference: &[f64], decoded: &[f64], width: usize, height: usize) -> f64 {
+    let ref_gradient = gradient_plane(reference, width, height);
+    let dec_gradient = gradient_plane(decoded, width, height);
+
+    let mut preserved = 0.0;
+    let mut total = 0.0;
+    for (&a, &b) in ref_gradient.iter().zip(&dec_gradient) {
+        let strongest = a.max(b);
+        if strongest >= EDGE_THRESHOLD {
+            preserved += a.min(b);
+            total += strongest;
+        }
+    }
+
+    if total == 0.0 {
+        // No edges in either frame
+        1.0
+    } else {
+        preserved / total
+    }
+}
+
+/// Periodically samples quality on a live stream
+///
+/// Measuring every frame is too expensive for the host, so the sampler only
+/// measures every `interval` frames and keeps a short history for smoothing.
+#[derive(Debug)]
+pub struct QualitySampler {
+    interval: u32,
+    frame_counter: u32,
+    history: VecDeque<QualityReport>,
+    history_len: usize,
+}
+
+impl QualitySampler {
+    /// Create a sampler that measures every `interval` frames
+    pub fn new(interval: u32, history_len: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            frame_counter: 0,
+            history: VecDeque::with_capacity(history_len.max(1)),
+            history_len: history_len.max(1),
+        }
+    }
+
+    /// Advance the frame counter and report whether this frame should be measured
+    pub fn should_sample(&mut self) -> bool {
+        let sample = self.frame_counter % self.interval == 0;
+        self.frame_counter = self.frame_counter.wrapping_add(1);
+        sample
+    }
+
+    /// Measure the frame if it is due for sampling
+    pub fn sample(
+        &mut self,
+        reference: &[u8],
+        decoded: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<Option<QualityReport>> {
+        if !self.should_sample() {
+            return Ok(None);
+        }
+
+        let report = measure(reference, decoded, width, height)?;
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back(report);
+        Ok(Some(report))
+    }
+
+    /// Average of recent samples (PSNR averaged over finite values only)
+    pub fn average(&self) -> Option<QualityReport> {
+        if self.history.is_empty() {
+            return None;
+        }
+
+        let n = self.history.len() as f64;
+        let finite: Vec<f64> = self.history.iter().map(|r| r.psnr).filter(|p| p.is_finite()).collect();
+        let psnr = if finite.is_empty() {
+            f64::INFINITY
+        } else {
+            finite.iter().sum::<f64>() / finite.len() as f64
+        };
+
+        Some(QualityReport {
+            psnr,
+            ssim: self.history.iter().map(|r| r.ssim).sum::<f64>() / n,
+            edge_preservation: self.history.iter().map(|r| r.edge_preservation).sum::<f64>() / n,
+        })
+    }
+
+    /// Whether recent quality satisfies the thresholds (`None` until sampled)
+    pub fn meets(&self, thresholds: &QualityThresholds) -> Option<bool> {
+        self.average().map(|report| report.meets(thresholds))
+    }
+
+    /// Clear history and restart the sampling interval
+    pub fn reset(&mut self) {
+        self.frame_counter = 0;
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black frame with white vertical bars, similar to rendered text stems
+    fn text_like_frame(width: u32, height: u32) -> Vec<u8> {
+        let mut frame = vec![0u8; (width * height * 4) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let idx = ((y * width + x) * 4) as usize;
+                let value = if x % 8 < 2 { 255 } else { 0 };
+                frame[idx..idx + 3].fill(value);
+                frame[idx + 3] = 255;
+            }
+        }
+        frame
+    }
+
+    /// Horizontal 3-tap box blur
+    fn blur(frame: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let mut out = frame.to_vec();
+        for y in 0..height as usize {
+            for x in 1..width as usize - 1 {
+                for c in 0..3 {
+                    let at = |x: usize| u32::from(frame[(y * width as usize + x) * 4 + c]);
+                    out[(y * width as usize + x) * 4 + c] = ((at(x - 1) + at(x) + at(x + 1)) / 3) as u8;
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_identical_frames() {
+        let frame = text_like_frame(64, 32);
+        let report = measure(&frame, &frame, 64, 32).unwrap();
+
+        assert!(report.psnr.is_infinite());
+        assert!((report.ssim - 1.0).abs() < 1e-9);
+        assert!((report.edge_preservation - 1.0).abs() < 1e-9);
+        assert!(report.meets(&QualityThresholds::default()));
+    }
+
+    #[test]
+    fn test_psnr_of_uniform_error() {
+        let reference = vec![100u8; 16 * 16 * 4];
+        let decoded = vec![110u8; 16 * 16 * 4];
+
+        // MSE = 100 -> 10 * log10(65025 / 100)
+        let value = psnr(&reference, &decoded, 16, 16).unwrap();
+        assert!((value - 28.1308).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_blur_hurts_edges_more_than_brightness_shift() {
+        let reference = text_like_frame(64, 32);
+        let blurred = blur(&reference, 64, 32);
+        let shifted: Vec<u8> = reference.iter().map(|&v| v.saturating_sub(10)).collect();
+
+        let blurred_report = measure(&reference, &blurred, 64, 32).unwrap();
+        let shifted_report = measure(&reference, &shifted, 64, 32).unwrap();
+
+        assert!(blurred_report.edge_preservation < 0.8);
+        assert!(shifted_report.edge_preservation > 0.9);
+        assert!(blurred_report.ssim < shifted_report.ssim);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let frame = vec![0u8; 16 * 16 * 4];
+        assert!(measure(&frame, &frame[..100], 16, 16).is_err());
+        assert!(ssim(&frame, &frame, 0, 16).is_err());
+    }
+
+    #[test]
+    fn test_sampler_interval_and_average() {
+        let frame = text_like_frame(32, 32);
+        let mut sampler = QualitySampler::new(3, 4);
+
+        let sampled = (0..9)
+            .filter(|_| sampler.sample(&frame, &frame, 32, 32).unwrap().is_some())
+            .count();
+
+        assert_eq!(sampled, 3);
+        assert_eq!(sampler.meets(&QualityThresholds::default()), Some(true));
+
+        sampler.reset();
+        assert!(sampler.average().is_none());
+    }
+}
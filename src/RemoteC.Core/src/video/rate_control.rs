@@ -0,0 +1,401 @@
+//! Frame-rate and bitrate control for video encoders
+//!
+//! [`RateControlledEncoder`] sits in front of any [`VideoEncoder`] and decides
+//! which frames actually reach it. Frames arriving faster than the target
+//! framerate are skipped, and frames arriving while the leaky-bucket bit
+//! budget is exhausted are dropped.
+
+use super::{EncodedFrame, EncoderConfig, EncoderStats, RegionOfInterest, VideoEncoder};
+use crate::Result;
+use std::collections::VecDeque;
+
+/// Window over which the real output bitrate is measured (microseconds)
+const BITRATE_WINDOW_US: u64 = 1_000_000;
+
+/// Outcome of the rate controller for a single frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDecision {
+    /// Frame should be encoded
+    Encode,
+    /// Frame arrived faster than the target framerate
+    Skip,
+    /// Bit budget is exhausted
+    Drop,
+}
+
+/// Rate controller configuration
+#[derive(Debug, Clone)]
+pub struct RateControlConfig {
+    /// Target bitrate in bits per second
+    pub target_bitrate: u32,
+    /// Target frame rate
+    pub target_framerate: u32,
+    /// Size of the bit bucket, expressed in milliseconds at the target bitrate
+    pub buffer_window_ms: u32,
+    /// Fraction of the frame interval tolerated as capture jitter (0.0 - 1.0)
+    pub frame_interval_tolerance: f64,
+}
+
+impl Default for RateControlConfig {
+    fn default() -> Self {
+        Self::from_encoder_config(&EncoderConfig::default())
+    }
+}
+
+impl RateControlConfig {
+    /// Derive rate control targets from an encoder configuration
+    pub fn from_encoder_config(config: &EncoderConfig) -> Self {
+        Self {
+            target_bitrate: config.bitrate,
+            target_framerate: config.framerate,
+            buffer_window_ms: 500,
+            frame_interval_tolerance: 0.1,
+        }
+    }
+}
+
+/// Rate controller statistics
+#[derive(Debug, Default, Clone)]
+pub struct RateControlStats {
+    /// Frames offered to the controller
+    pub frames_submitted: u64,
+    /// Frames passed through to the encoder
+    pub frames_encoded: u64,
+    /// Frames dropped because the bit budget was exhausted
+    pub frames_dropped: u64,
+    /// Frames skipped to enforce the target framerate
+    pub frames_skipped: u64,
+    /// Measured output bitrate (bits per second)
+    pub output_bitrate: u32,
+    /// Current bucket fill level in bits
+    pub bucket_level: u64,
+}
+
+/// Leaky-bucket rate controller
+///
+/// Timestamps are in microseconds, matching [`EncodedFrame::timestamp`].
+#[derive(Debug)]
+pub struct RateController {
+    config: RateControlConfig,
+    /// Bits currently in the bucket
+    bucket_bits: f64,
+    /// Timestamp of the last bucket leak
+    last_leak: Option<u64>,
+    /// Earliest timestamp at which the next frame is due
+    next_due: Option<u64>,
+    /// Timestamp of the first encoded frame in the current session
+    first_encoded: Option<u64>,
+    /// Recently encoded frames as (timestamp, bytes)
+    window: VecDeque<(u64, usize)>,
+    stats: RateControlStats,
+}
+
+impl RateController {
+    /// Create a new rate controller
+    pub fn new(config: RateControlConfig) -> Self {
+        Self {
+            config,
+            bucket_bits: 0.0,
+            last_leak: None,
+            next_due: None,
+            first_encoded: None,
+            window: VecDeque::new(),
+            stats: RateControlStats::default(),
+        }
+    }
+
+    /// Get current configuration
+    pub fn config(&self) -> &RateControlConfig {
+        &self.config
+    }
+
+    /// Update bitrate and framerate targets without resetting state
+    pub fn set_targets(&mut self, bitrate: u32, framerate: u32) {
+        self.config.target_bitrate = bitrate;
+        self.config.target_framerate = framerate;
+    }
+
+    /// Decide whether the frame captured at `timestamp` should be encoded
+    pub fn decide(&mut self, timestamp: u64) -> FrameDecision {
+        self.stats.frames_submitted += 1;
+        self.leak(timestamp);
+
+        let interval = self.frame_interval_us();
+        if let (Some(due), Some(interval)) = (self.next_due, interval) {
+            let tolerance = (interval as f64 * self.config.frame_interval_tolerance) as u64;
+            if timestamp + tolerance < due {
+                self.stats.frames_skipped += 1;
+                return FrameDecision::Skip;
+            }
+        }
+
+        if self.bucket_bits > self.capacity_bits() {
+            self.stats.frames_dropped += 1;
+            return FrameDecision::Drop;
+        }
+
+        if let Some(interval) = interval {
+            // Re-anchor the schedule if we fell more than a frame behind
+            let anchor = match self.next_due {
+                Some(due) if timestamp.saturating_sub(due) <= interval => due,
+                _ => timestamp,
+            };
+            self.next_due = Some(anchor + interval);
+        }
+
+        FrameDecision::Encode
+    }
+
+    /// Account for a frame the encoder produced
+    pub fn on_frame_encoded(&mut self, timestamp: u64, bytes: usize) {
+        self.stats.frames_encoded += 1;
+        self.bucket_bits += (bytes * 8) as f64;
+        self.first_encoded.get_or_insert(timestamp);
+
+        self.window.push_back((timestamp, bytes));
+        while let Some(&(ts, _)) = self.window.front() {
+            if timestamp.saturating_sub(ts) >= BITRATE_WINDOW_US {
+                self.window.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.stats.output_bitrate = self.measure_bitrate(timestamp);
+    }
+
+    /// Get rate controller statistics
+    pub fn stats(&self) -> RateControlStats {
+        let mut stats = self.stats.clone();
+        stats.bucket_level = self.bucket_bits as u64;
+        stats
+    }
+
+    /// Measured output bitrate over the last second (bits per second)
+    pub fn output_bitrate(&self) -> u32 {
+        self.stats.output_bitrate
+    }
+
+    /// Reset all state and statistics
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    fn frame_interval_us(&self) -> Option<u64> {
+        if self.config.target_framerate == 0 {
+            None
+        } else {
+            Some(1_000_000 / u64::from(self.config.target_framerate))
+        }
+    }
+
+    fn capacity_bits(&self) -> f64 {
+        f64::from(self.config.target_bitrate) * f64::from(self.config.buffer_window_ms) / 1000.0
+    }
+
+    fn leak(&mut self, timestamp: u64) {
+        if let Some(last) = self.last_leak {
+            let elapsed = timestamp.saturating_sub(last) as f64 / 1_000_000.0;
+            self.bucket_bits = (self.bucket_bits - f64::from(self.config.target_bitrate) * elapsed).max(0.0);
+        }
+        self.last_leak = Some(self.last_leak.map_or(timestamp, |last| last.max(timestamp)));
+    }
+
+    fn measure_bitrate(&self, now: u64) -> u32 {
+        let bits: usize = self.window.iter().map(|&(_, bytes)| bytes * 8).sum();
+
+        // Until a full window has elapsed, measure over the time actually covered
+        let interval = self.frame_interval_us().unwrap_or(0);
+        let covered = now
+            .saturating_sub(self.first_encoded.unwrap_or(now))
+            .saturating_add(interval)
+            .clamp(1, BITRATE_WINDOW_US);
+
+        (bits as u64 * 1_000_000 / covered).min(u64::from(u32::MAX)) as u32
+    }
+}
+
+/// Video encoder wrapper enforcing framerate and bitrate targets
+pub struct RateControlledEncoder {
+    encoder: Box<dyn VideoEncoder>,
+    controller: RateController,
+}
+
+impl RateControlledEncoder {
+    /// Wrap an encoder; targets are taken from the config passed to [`configure`](Self::configure)
+    pub fn new(encoder: Box<dyn VideoEncoder>) -> Self {
+        Self {
+            encoder,
+            controller: RateController::new(RateControlConfig::default()),
+        }
+    }
+
+    /// Wrap an encoder with explicit rate control settings
+    pub fn with_config(encoder: Box<dyn VideoEncoder>, config: RateControlConfig) -> Self {
+        Self {
+            encoder,
+            controller: RateController::new(config),
+        }
+    }
+
+    /// Configure the wrapped encoder and update rate control targets
+    pub fn configure(&mut self, config: EncoderConfig) -> Result<()> {
+        self.controller.set_targets(config.bitrate, config.framerate);
+        self.controller.reset();
+        self.encoder.configure(config)
+    }
+
+    /// Encode a frame, or return `None` if the rate controller discarded it
+    pub fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<Option<EncodedFrame>> {
+        self.encode_with(timestamp, |encoder| encoder.encode_frame(frame, timestamp))
+    }
+
+    /// Encode a frame with region-of-interest hints, or return `None` if discarded
+    pub fn encode_frame_with_roi(
+        &mut self,
+        frame: &[u8],
+        timestamp: u64,
+        regions: &[RegionOfInterest],
+    ) -> Result<Option<EncodedFrame>> {
+        self.encode_with(timestamp, |encoder| encoder.encode_frame_with_roi(frame, timestamp, regions))
+    }
+
+    fn encode_with<F>(&mut self, timestamp: u64, encode: F) -> Result<Option<EncodedFrame>>
+    where
+        F: FnOnce(&mut dyn VideoEncoder) -> Result<EncodedFrame>,
+    {
+        match self.controller.decide(timestamp) {
+            FrameDecision::Encode => {
+                let encoded = encode(self.encoder.as_mut())?;
+                self.controller.on_frame_encoded(timestamp, encoded.data.len());
+                Ok(Some(encoded))
+            }
+            FrameDecision::Skip | FrameDecision::Drop => Ok(None),
+        }
+    }
+
+    /// Flush any pending frames
+    pub fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+        let frames = self.encoder.flush()?;
+        for frame in &frames {
+            self.controller.on_frame_encoded(frame.timestamp, frame.data.len());
+        }
+        Ok(frames)
+    }
+
+    /// Encoder statistics including dropped frames and measured bitrate
+    pub fn get_stats(&self) -> EncoderStats {
+        let rate_stats = self.controller.stats();
+        let mut stats = self.encoder.get_stats();
+        stats.frames_dropped += rate_stats.frames_dropped + rate_stats.frames_skipped;
+        stats.current_bitrate = rate_stats.output_bitrate;
+        stats
+    }
+
+    /// Rate controller statistics
+    pub fn rate_stats(&self) -> RateControlStats {
+        self.controller.stats()
+    }
+
+    /// Access the rate controller
+    pub fn controller_mut(&mut self) -> &mut RateController {
+        &mut self.controller
+    }
+
+    /// Access the wrapped encoder
+    pub fn encoder_mut(&mut self) -> &mut dyn VideoEncoder {
+        self.encoder.as_mut()
+    }
+
+    /// Reset the encoder and rate controller
+    pub fn reset(&mut self) -> Result<()> {
+        self.controller.reset();
+        self.encoder.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::{VideoCodec, VP8Encoder};
+
+    fn controller(bitrate: u32, framerate: u32) -> RateController {
+        RateController::new(RateControlConfig {
+            target_bitrate: bitrate,
+            target_framerate: framerate,
+            buffer_window_ms: 500,
+            frame_interval_tolerance: 0.1,
+        })
+    }
+
+    #[test]
+    fn test_skips_frames_above_target_framerate() {
+        let mut rc = controller(100_000_000, 30);
+
+        // One second of 60fps input
+        let encoded = (0..60u64)
+            .filter(|i| rc.decide(i * 16_667) == FrameDecision::Encode)
+            .count();
+
+        assert_eq!(encoded, 30);
+        assert_eq!(rc.stats().frames_skipped, 30);
+        assert_eq!(rc.stats().frames_dropped, 0);
+    }
+
+    #[test]
+    fn test_drops_frames_when_over_budget() {
+        let mut rc = controller(1_000_000, 30);
+
+        // A 100 KB frame is 800 kbit, more than the 500 kbit bucket
+        assert_eq!(rc.decide(0), FrameDecision::Encode);
+        rc.on_frame_encoded(0, 100_000);
+
+        assert_eq!(rc.decide(33_333), FrameDecision::Drop);
+        assert_eq!(rc.decide(66_666), FrameDecision::Drop);
+
+        // After ~0.3s the bucket has leaked below capacity
+        assert_eq!(rc.decide(400_000), FrameDecision::Encode);
+        assert_eq!(rc.stats().frames_dropped, 2);
+    }
+
+    #[test]
+    fn test_measures_output_bitrate() {
+        let mut rc = controller(10_000_000, 10);
+
+        for i in 0..20u64 {
+            let ts = i * 100_000;
+            if rc.decide(ts) == FrameDecision::Encode {
+                rc.on_frame_encoded(ts, 10_000);
+            }
+        }
+
+        // 10 frames per second of 80 kbit each
+        assert_eq!(rc.output_bitrate(), 800_000);
+    }
+
+    #[test]
+    fn test_rate_controlled_encoder_stats() {
+        let mut encoder = RateControlledEncoder::new(Box::new(VP8Encoder::new().unwrap()));
+        encoder.configure(EncoderConfig {
+            codec: VideoCodec::VP8,
+            width: 64,
+            height: 64,
+            framerate: 30,
+            ..Default::default()
+        }).unwrap();
+
+        let frame = vec![0u8; 64 * 64 * 4];
+        let mut produced = 0;
+        for i in 0..60u64 {
+            if encoder.encode_frame(&frame, i * 16_667).unwrap().is_some() {
+                produced += 1;
+            }
+        }
+
+        let stats = encoder.get_stats();
+        assert_eq!(produced, 30);
+        assert_eq!(stats.frames_encoded, 30);
+        assert_eq!(stats.frames_dropped, 30);
+        assert!(stats.current_bitrate > 0);
+    }
+}
@@ -0,0 +1,503 @@
+//! Progressive lossless refinement for static screens
+//!
+//! Lossy video codecs leave a still screen slightly blurry. Once the screen
+//! has been static for a number of frames, the host sends the tiles that
+//! changed since the last refinement losslessly through the frame codec in
+//! [`crate::encoding`], layered on top of the video stream. The viewer
+//! composites those tiles over the decoded video until they change again.
+
+use crate::capture::CaptureRegion;
+use crate::encoding::{
+    self, CompressionFormat, FrameDecoder, FrameEncoder, FrameEncodingConfig,
+};
+use crate::{RemoteCError, Result};
+
+/// Refinement configuration
+#[derive(Debug, Clone)]
+pub struct RefinementConfig {
+    /// Number of unchanged frames before refinement starts
+    pub static_frames: u32,
+    /// Tile size in pixels
+    pub tile_size: u32,
+    /// Maximum tiles sent per frame (0 for unlimited)
+    pub max_tiles_per_update: usize,
+    /// Lossless codec settings
+    pub encoding: FrameEncodingConfig,
+}
+
+impl Default for RefinementConfig {
+    fn default() -> Self {
+        Self {
+            static_frames: 15, // 0.5 seconds at 30fps
+            tile_size: 64,
+            max_tiles_per_update: 0,
+            encoding: FrameEncodingConfig {
+                compression_format: CompressionFormat::Zlib,
+                quality: 90,
+                max_threads: 1,
+            },
+        }
+    }
+}
+
+/// A losslessly encoded tile
+#[derive(Debug, Clone)]
+pub struct RefinementTile {
+    /// Tile position in frame pixels
+    pub region: CaptureRegion,
+    /// Lossless tile data
+    pub frame: encoding::EncodedFrame,
+}
+
+/// A batch of refinement tiles applying on top of a video frame
+#[derive(Debug, Clone)]
+pub struct RefinementUpdate {
+    /// Sequence number of the video frame the tiles refine
+    pub base_sequence: u64,
+    /// Refined tiles
+    pub tiles: Vec<RefinementTile>,
+}
+
+/// Result of feeding a frame to the refinement controller
+#[derive(Debug, Clone)]
+pub enum RefinementEvent {
+    /// Nothing to send
+    None,
+    /// Previously refined tiles changed; the viewer must drop them
+    Invalidate(Vec<CaptureRegion>),
+    /// Lossless tiles to composite over the video
+    Refine(RefinementUpdate),
+}
+
+/// Host-side refinement controller
+pub struct RefinementController {
+    config: RefinementConfig,
+    encoder: FrameEncoder,
+    previous: Option<Vec<u8>>,
+    width: u32,
+    height: u32,
+    static_count: u32,
+    /// Tiles changed since they were last refined
+    pending: Vec<bool>,
+    /// Tiles the viewer currently shows refined
+    refined: Vec<bool>,
+}
+
+impl RefinementController {
+    /// Create a new refinement controller
+    pub fn new(config: RefinementConfig) -> Result<Self> {
+        if config.tile_size == 0 {
+            return Err(RemoteCError::EncodingError("Tile size must be non-zero".to_string()));
+        }
+        let encoder = FrameEncoder::new(config.encoding.clone())?;
+
+        Ok(Self {
+            config,
+            encoder,
+            previous: None,
+            width: 0,
+            height: 0,
+            static_count: 0,
+            pending: Vec::new(),
+            refined: Vec::new(),
+        })
+    }
+
+    /// Feed a captured BGRA frame after it has been sent as video frame `sequence`
+    pub fn process_frame(
+        &mut self,
+        frame: &[u8],
+        width: u32,
+        height: u32,
+        sequence: u64,
+    ) -> Result<RefinementEvent> {
+        let expected_size = width as usize * height as usize * 4;
+        if frame.len() != expected_size {
+            return Err(RemoteCError::EncodingError(format!(
+                "Invalid frame size: expected {}, got {}",
+                expected_size,
+                frame.len()
+            )));
+        }
+
+        if self.previous.is_none() || width != self.width || height != self.height {
+            // New stream or resolution change: everything needs refinement,
+            // and tiles refined at the old size must go wherever they lie
+            let had_refinement = self.refined.iter().any(|&r| r);
+            let invalidated = CaptureRegion {
+                x: 0,
+                y: 0,
+                width: width.max(self.width),
+                height: height.max(self.height),
+            };
+            self.width = width;
+            self.height = height;
+            let tiles = self.tile_count();
+            self.pending = vec![true; tiles];
+            self.refined = vec![false; tiles];
+            self.static_count = 0;
+            self.remember(frame);
+
+            return Ok(if had_refinement {
+                RefinementEvent::Invalidate(vec![invalidated])
+            } else {
+                RefinementEvent::None
+            });
+        }
+
+        let changed = self.changed_tiles(frame);
+        self.remember(frame);
+
+        if !changed.is_empty() {
+            self.static_count = 0;
+            let mut invalidated = Vec::new();
+            for &tile in &changed {
+                self.pending[tile] = true;
+                if std::mem::replace(&mut self.refined[tile], false) {
+                    invalidated.push(self.tile_region(tile));
+                }
+            }
+
+            return Ok(if invalidated.is_empty() {
+                RefinementEvent::None
+            } else {
+                RefinementEvent::Invalidate(invalidated)
+            });
+        }
+
+        self.static_count = self.static_count.saturating_add(1);
+        if self.static_count < self.config.static_frames {
+            return Ok(RefinementEvent::None);
+        }
+
+        self.refine(frame, sequence)
+    }
+
+    /// Keep `frame` as the reference for change detection, reusing the
+    /// buffer when the size is unchanged
+    fn remember(&mut self, frame: &[u8]) {
+        match &mut self.previous {
+            Some(previous) if previous.len() == frame.len() => previous.copy_from_slice(frame),
+            previous => *previous = Some(frame.to_vec()),
+        }
+    }
+
+    /// Whether any tile is still waiting for refinement
+    pub fn has_pending(&self) -> bool {
+        self.pending.iter().any(|&p| p)
+    }
+
+    /// Forget all state, e.g. after the viewer reconnects
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.static_count = 0;
+        self.pending.clear();
+        self.refined.clear();
+    }
+
+    fn refine(&mut self, frame: &[u8], sequence: u64) -> Result<RefinementEvent> {
+        let limit = match self.config.max_tiles_per_update {
+            0 => usize::MAX,
+            n => n,
+        };
+        let selected: Vec<usize> = (0..self.pending.len())
+            .filter(|&tile| self.pending[tile])
+            .take(limit)
+            .collect();
+
+        if selected.is_empty() {
+            return Ok(RefinementEvent::None);
+        }
+
+        let mut tiles = Vec::with_capacity(selected.len());
+        for tile in selected {
+            let region = self.tile_region(tile);
+            let pixels = extract_region(frame, self.width, &region);
+            let encoded = self.encoder.encode_frame(&pixels, region.width, region.height)?;
+            tiles.push(RefinementTile { region, frame: encoded });
+            self.pending[tile] = false;
+            self.refined[tile] = true;
+        }
+
+        Ok(RefinementEvent::Refine(RefinementUpdate {
+            base_sequence: sequence,
+            tiles,
+        }))
+    }
+
+    fn columns(&self) -> usize {
+        self.width.div_ceil(self.config.tile_size) as usize
+    }
+
+    fn tile_count(&self) -> usize {
+        self.columns() * self.height.div_ceil(self.config.tile_size) as usize
+    }
+
+    fn tile_region(&self, tile: usize) -> CaptureRegion {
+        let size = self.config.tile_size;
+        let columns = self.columns();
+        let x = (tile % columns) as u32 * size;
+        let y = (tile / columns) as u32 * size;
+
+        CaptureRegion {
+            x: x as i32,
+            y: y as i32,
+            width: size.min(self.width - x),
+            height: size.min(self.height - y),
+        }
+    }
+
+    fn changed_tiles(&self, frame: &[u8]) -> Vec<usize> {
+        let Some(previous) = self.previous.as_ref() else {
+            return (0..self.tile_count()).collect();
+        };
+        let stride = self.width as usize * 4;
+
+        (0..self.tile_count())
+            .filter(|&tile| {
+                let region = self.tile_region(tile);
+                let start = region.x as usize * 4;
+                let end = start + region.width as usize * 4;
+                (region.y as usize..(region.y as usize + region.height as usize)).any(|row| {
+                    let offset = row * stride;
+                    frame[offset + start..offset + end] != previous[offset + start..offset + end]
+                })
+            })
+            .collect()
+    }
+}
+
+/// Viewer-side compositor for refinement tiles
+pub struct RefinementCompositor {
+    decoder: FrameDecoder,
+    /// Decoded tiles as (region, BGRA pixels)
+    tiles: Vec<(CaptureRegion, Vec<u8>)>,
+}
+
+impl Default for RefinementCompositor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RefinementCompositor {
+    /// Create an empty compositor
+    pub fn new() -> Self {
+        Self {
+            decoder: FrameDecoder::new(),
+            tiles: Vec::new(),
+        }
+    }
+
+    /// Decode and store the tiles of a refinement update
+    pub fn apply(&mut self, update: &RefinementUpdate) -> Result<()> {
+        for tile in &update.tiles {
+            let decoded = self.decoder.decode_frame(&tile.frame)?;
+            self.tiles.retain(|(region, _)| !regions_overlap(region, &tile.region));
+            self.tiles.push((tile.region, decoded.data));
+        }
+        Ok(())
+    }
+
+    /// Drop refined tiles overlapping the given regions
+    pub fn invalidate(&mut self, regions: &[CaptureRegion]) {
+        self.tiles
+            .retain(|(tile, _)| !regions.iter().any(|region| regions_overlap(tile, region)));
+    }
+
+    /// Handle an event from the host
+    pub fn handle_event(&mut self, event: &RefinementEvent) -> Result<()> {
+        match event {
+            RefinementEvent::None => Ok(()),
+            RefinementEvent::Invalidate(regions) => {
+                self.invalidate(regions);
+                Ok(())
+            }
+            RefinementEvent::Refine(update) => self.apply(update),
+        }
+    }
+
+    /// Number of tiles currently held
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Drop all refined tiles
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Overwrite refined areas of a decoded BGRA video frame
+    pub fn composite(&self, frame: &mut [u8], width: u32, height: u32) {
+        let stride = width as usize * 4;
+        for (region, pixels) in &self.tiles {
+            if region.x < 0
+                || region.y < 0
+                || region.x as u32 + region.width > width
+                || region.y as u32 + region.height > height
+            {
+                continue;
+            }
+
+            let row_len = region.width as usize * 4;
+            for row in 0..region.height as usize {
+                let dst = (region.y as usize + row) * stride + region.x as usize * 4;
+                let src = row * row_len;
+                frame[dst..dst + row_len].copy_from_slice(&pixels[src..src + row_len]);
+            }
+        }
+    }
+}
+
+fn regions_overlap(a: &CaptureRegion, b: &CaptureRegion) -> bool {
+    a.x < b.x + b.width as i32
+        && a.x + a.width as i32 > b.x
+        && a.y < b.y + b.height as i32
+        && a.y + a.height as i32 > b.y
+}
+
+/// Copy a region out of a BGRA frame
+fn extract_region(frame: &[u8], width: u32, region: &CaptureRegion) -> Vec<u8> {
+    let stride = width as usize * 4;
+    let row_len = region.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * region.height as usize);
+
+    for row in 0..region.height as usize {
+        let start = (region.y as usize + row) * stride + region.x as usize * 4;
+        pixels.extend_from_slice(&frame[start..start + row_len]);
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 100;
+    const HEIGHT: u32 = 70;
+
+    fn test_frame(seed: u8) -> Vec<u8> {
+        (0..WIDTH * HEIGHT * 4)
+            .map(|i| (i as u8).wrapping_mul(7).wrapping_add(seed))
+            .collect()
+    }
+
+    fn controller(static_frames: u32) -> RefinementController {
+        RefinementController::new(RefinementConfig {
+            static_frames,
+            tile_size: 32,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_refines_after_static_period() {
+        let mut ctl = controller(3);
+        let frame = test_frame(0);
+
+        assert!(matches!(ctl.process_frame(&frame, WIDTH, HEIGHT, 1).unwrap(), RefinementEvent::None));
+        assert!(matches!(ctl.process_frame(&frame, WIDTH, HEIGHT, 2).unwrap(), RefinementEvent::None));
+        assert!(matches!(ctl.process_frame(&frame, WIDTH, HEIGHT, 3).unwrap(), RefinementEvent::None));
+
+        let RefinementEvent::Refine(update) = ctl.process_frame(&frame, WIDTH, HEIGHT, 4).unwrap() else {
+            panic!("expected refinement");
+        };
+        // 100x70 with 32px tiles -> 4x3 tiles, edge tiles clipped
+        assert_eq!(update.base_sequence, 4);
+        assert_eq!(update.tiles.len(), 12);
+        assert_eq!(update.tiles[11].region.width, 4);
+        assert_eq!(update.tiles[11].region.height, 6);
+        assert!(!ctl.has_pending());
+
+        // The viewer reconstructs the source exactly
+        let mut compositor = RefinementCompositor::new();
+        compositor.apply(&update).unwrap();
+        let mut video = vec![0u8; frame.len()];
+        compositor.composite(&mut video, WIDTH, HEIGHT);
+        assert_eq!(video, frame);
+
+        // Nothing more to send while static
+        assert!(matches!(ctl.process_frame(&frame, WIDTH, HEIGHT, 5).unwrap(), RefinementEvent::None));
+    }
+
+    #[test]
+    fn test_motion_invalidates_only_changed_tiles() {
+        let mut ctl = controller(1);
+        let frame = test_frame(0);
+        ctl.process_frame(&frame, WIDTH, HEIGHT, 1).unwrap();
+
+        let mut compositor = RefinementCompositor::new();
+        compositor.handle_event(&ctl.process_frame(&frame, WIDTH, HEIGHT, 2).unwrap()).unwrap();
+        assert_eq!(compositor.tile_count(), 12);
+
+        // Change one pixel in the second tile
+        let mut moved = frame.clone();
+        moved[(10 * WIDTH as usize + 40) * 4] ^= 0xff;
+
+        let event = ctl.process_frame(&moved, WIDTH, HEIGHT, 3).unwrap();
+        let RefinementEvent::Invalidate(regions) = &event else {
+            panic!("expected invalidation");
+        };
+        assert_eq!(regions.len(), 1);
+        assert_eq!((regions[0].x, regions[0].y), (32, 0));
+        compositor.handle_event(&event).unwrap();
+        assert_eq!(compositor.tile_count(), 11);
+
+        // Once static again, only the changed tile is resent
+        let RefinementEvent::Refine(update) = ctl.process_frame(&moved, WIDTH, HEIGHT, 4).unwrap() else {
+            panic!("expected refinement");
+        };
+        assert_eq!(update.tiles.len(), 1);
+        compositor.apply(&update).unwrap();
+
+        let mut video = vec![0u8; moved.len()];
+        compositor.composite(&mut video, WIDTH, HEIGHT);
+        assert_eq!(video, moved);
+    }
+
+    #[test]
+    fn test_tile_limit_spreads_refinement() {
+        let mut ctl = RefinementController::new(RefinementConfig {
+            static_frames: 1,
+            tile_size: 32,
+            max_tiles_per_update: 5,
+            ..Default::default()
+        })
+        .unwrap();
+        let frame = test_frame(1);
+        ctl.process_frame(&frame, WIDTH, HEIGHT, 1).unwrap();
+
+        let counts: Vec<usize> = (2..6)
+            .map(|seq| match ctl.process_frame(&frame, WIDTH, HEIGHT, seq).unwrap() {
+                RefinementEvent::Refine(update) => update.tiles.len(),
+                _ => 0,
+            })
+            .collect();
+
+        assert_eq!(counts, vec![5, 5, 2, 0]);
+    }
+
+    #[test]
+    fn test_resolution_change_invalidates_everything() {
+        let mut ctl = controller(1);
+        let frame = test_frame(0);
+        ctl.process_frame(&frame, WIDTH, HEIGHT, 1).unwrap();
+
+        let mut compositor = RefinementCompositor::new();
+        compositor.handle_event(&ctl.process_frame(&frame, WIDTH, HEIGHT, 2).unwrap()).unwrap();
+        assert_eq!(compositor.tile_count(), 12);
+
+        // Shrinking drops tiles refined at the old size, inside or outside the new one
+        let small = vec![0u8; 32 * 32 * 4];
+        let event = ctl.process_frame(&small, 32, 32, 3).unwrap();
+        let RefinementEvent::Invalidate(regions) = &event else {
+            panic!("expected invalidation");
+        };
+        assert_eq!((regions[0].width, regions[0].height), (WIDTH, HEIGHT));
+        compositor.handle_event(&event).unwrap();
+        assert_eq!(compositor.tile_count(), 0);
+        assert!(ctl.process_frame(&small, 16, 16, 4).is_err());
+    }
+}
@@ -0,0 +1,581 @@
+//! Encoder backend registry
+//!
+//! Every way of producing a [`VideoEncoder`] is described by an
+//! [`EncoderBackend`]: which codecs and profiles it handles, how large a
+//! frame it accepts and whether it is usable on this machine. The registry
+//! tries backends in priority order (hardware, software, then the lossless
+//! frame codec) and falls back to the next one when a backend is missing or
+//! fails to initialize. Falling back to the lossless codec changes the
+//! stream format, which [`SelectedEncoder::codec`] reports.
+
+use super::hardware::{self, HardwareEncoder};
+use super::lossless::LosslessFrameEncoder;
+use super::{
+    scale::{self, Scaler}, svc, EncodedFrame, EncoderConfig, EncoderStats, H264Encoder, H265Encoder, VP8Encoder,
+    VP9Encoder, VideoCodec, VideoEncoder,
+};
+use crate::encoding::{CompressionFormat, FrameEncodingConfig};
+use crate::{RemoteCError, Result};
+use std::sync::{Arc, Mutex};
+
+/// Kind of encoder backend, in order of preference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BackendKind {
+    /// GPU or fixed-function encoder
+    Hardware,
+    /// CPU video codec
+    Software,
+    /// Lossless frame codec, works everywhere
+    Lossless,
+}
+
+/// What an encoder backend can handle
+#[derive(Debug, Clone)]
+pub struct BackendCapabilities {
+    /// Supported codecs
+    pub codecs: Vec<VideoCodec>,
+    /// Supported codec profiles (e.g. "baseline", "main")
+    pub profiles: Vec<&'static str>,
+    /// Maximum frame width
+    pub max_width: u32,
+    /// Maximum frame height
+    pub max_height: u32,
+}
+
+impl BackendCapabilities {
+    /// Whether a configuration fits within these capabilities
+    pub fn supports(&self, config: &EncoderConfig) -> bool {
+        self.codecs.contains(&config.codec)
+            && config.profile.map_or(true, |profile| self.profiles.contains(&profile))
+            && config.width <= self.max_width
+            && config.height <= self.max_height
+    }
+}
+
+/// A source of video encoders
+pub trait EncoderBackend: Send + Sync {
+    /// Backend name used in logs
+    fn name(&self) -> &str;
+
+    /// Backend kind, which determines its priority
+    fn kind(&self) -> BackendKind;
+
+    /// Codecs, profiles and limits of the backend
+    fn capabilities(&self) -> BackendCapabilities;
+
+    /// Probe whether the backend is usable on this machine
+    fn is_available(&self) -> bool;
+
+    /// Create an unconfigured encoder
+    fn create(&self) -> Result<Box<dyn VideoEncoder>>;
+}
+
+/// Encoder chosen by the registry
+pub struct SelectedEncoder {
+    /// Configured encoder
+    pub encoder: Box<dyn VideoEncoder>,
+    /// Name of the backend that produced it
+    pub backend: String,
+    /// Kind of the backend that produced it
+    pub kind: BackendKind,
+    /// Codec the encoder produces; [`VideoCodec::Lossless`] when the
+    /// registry fell back from the requested one
+    pub codec: VideoCodec,
+}
+
+/// Ordered collection of encoder backends
+#[derive(Clone)]
+pub struct EncoderRegistry {
+    backends: Vec<Arc<dyn EncoderBackend>>,
+}
+
+impl Default for EncoderRegistry {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+impl EncoderRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self { backends: Vec::new() }
+    }
+
+    /// Registry with all backends compiled into this build
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+
+        #[cfg(feature = "nvenc")]
+        registry.register(Arc::new(HardwareBackend::new(
+            "nvenc",
+            vec![VideoCodec::H264, VideoCodec::H265],
+            || Box::new(hardware::NvencEncoder::new()),
+        )));
+
+        for codec in [VideoCodec::H264, VideoCodec::H265, VideoCodec::VP8, VideoCodec::VP9] {
+            registry.register(Arc::new(SoftwareBackend::new(codec)));
+        }
+        registry.register(Arc::new(LosslessBackend));
+
+        registry
+    }
+
+    /// Add a backend; among backends of the same kind, earlier ones win
+    pub fn register(&mut self, backend: Arc<dyn EncoderBackend>) {
+        self.backends.push(backend);
+    }
+
+    /// Registered backends in priority order
+    pub fn backends(&self) -> Vec<Arc<dyn EncoderBackend>> {
+        let mut backends = self.backends.clone();
+        backends.sort_by_key(|b| b.kind());
+        backends
+    }
+
+    /// Backends eligible for a configuration, in priority order
+    pub fn candidates(&self, config: &EncoderConfig) -> Vec<Arc<dyn EncoderBackend>> {
+        self.backends()
+            .into_iter()
+            .filter(|b| config.hardware_acceleration || b.kind() != BackendKind::Hardware)
+            .filter(|b| b.capabilities().supports(config))
+            .collect()
+    }
+
+    /// Create and configure an encoder, falling back through the backends
+    ///
+    /// When no backend handles the requested codec, the lossless frame
+    /// codec is used instead.
+    pub fn create_encoder(&self, config: &EncoderConfig) -> Result<SelectedEncoder> {
+        if let Some(selected) = self.try_backends(config) {
+            return Ok(selected);
+        }
+
+        if config.codec != VideoCodec::Lossless {
+            let lossless = EncoderConfig { codec: VideoCodec::Lossless, profile: None, ..config.clone() };
+            if let Some(selected) = self.try_backends(&lossless) {
+                log::warn!("No {:?} encoder available, falling back to lossless frames", config.codec);
+                return Ok(selected);
+            }
+        }
+
+        Err(RemoteCError::EncodingError(format!(
+            "No encoder backend available for {:?} at {}x{}",
+            config.codec, config.width, config.height
+        )))
+    }
+
+    fn try_backends(&self, config: &EncoderConfig) -> Option<SelectedEncoder> {
+        for backend in self.candidates(config) {
+            if !backend.is_available() {
+                log::debug!("Encoder backend {} unavailable", backend.name());
+                continue;
+            }
+
+            let result = backend.create().and_then(|mut encoder| {
+                encoder.configure(config.clone())?;
+                Ok(encoder)
+            });
+
+            match result {
+                Ok(encoder) => {
+                    log::info!(
+                        "Selected {:?} encoder backend {} for {:?} {}x{}",
+                        backend.kind(), backend.name(), config.codec, config.width, config.height
+                    );
+                    return Some(SelectedEncoder {
+                        encoder,
+                        backend: backend.name().to_string(),
+                        kind: backend.kind(),
+                        codec: config.codec,
+                    });
+                }
+                Err(e) => {
+                    log::warn!("Encoder backend {} failed, trying next: {}", backend.name(), e);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Built-in CPU encoders
+struct SoftwareBackend {
+    codec: VideoCodec,
+}
+
+impl SoftwareBackend {
+    fn new(codec: VideoCodec) -> Self {
+        Self { codec }
+    }
+}
+
+impl EncoderBackend for SoftwareBackend {
+    fn name(&self) -> &str {
+        match self.codec {
+            VideoCodec::H264 => "software-h264",
+            VideoCodec::H265 => "software-h265",
+            VideoCodec::VP8 => "software-vp8",
+            VideoCodec::VP9 => "software-vp9",
+            VideoCodec::Lossless => "software-lossless",
+        }
+    }
+
+    fn kind(&self) -> BackendKind {
+        BackendKind::Software
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        let (profiles, max_width, max_height) = match self.codec {
+            // OpenH264 only produces constrained baseline, up to level 5.2
+            VideoCodec::H264 => (vec!["baseline"], 4096, 2304),
+            VideoCodec::H265 => (vec!["main"], 8192, 4320),
+            VideoCodec::VP8 => (vec!["0"], 16384, 16384),
+            VideoCodec::VP9 => (vec!["0"], 16384, 16384),
+            VideoCodec::Lossless => (vec!["lossless"], 8192, 8192),
+        };
+
+        BackendCapabilities {
+            codecs: vec![self.codec],
+            profiles,
+            max_width,
+            max_height,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn create(&self) -> Result<Box<dyn VideoEncoder>> {
+        Ok(match self.codec {
+            VideoCodec::H264 => Box::new(H264Encoder::new()?),
+            VideoCodec::H265 => Box::new(H265Encoder::new()?),
+            VideoCodec::VP8 => Box::new(VP8Encoder::new()?),
+            VideoCodec::VP9 => Box::new(VP9Encoder::new()?),
+            VideoCodec::Lossless => lossless_encoder()?,
+        })
+    }
+}
+
+/// Lossless encoder used by the registry
+fn lossless_encoder() -> Result<Box<dyn VideoEncoder>> {
+    Ok(Box::new(LosslessFrameEncoder::new(FrameEncodingConfig {
+        compression_format: CompressionFormat::Zstd,
+        ..Default::default()
+    })?))
+}
+
+/// Lossless frame codec, the fallback for every codec request
+struct LosslessBackend;
+
+impl EncoderBackend for LosslessBackend {
+    fn name(&self) -> &str {
+        "lossless"
+    }
+
+    fn kind(&self) -> BackendKind {
+        BackendKind::Lossless
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            codecs: vec![VideoCodec::Lossless],
+            profiles: vec!["lossless"],
+            max_width: 8192,
+            max_height: 8192,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn create(&self) -> Result<Box<dyn VideoEncoder>> {
+        lossless_encoder()
+    }
+}
+
+/// Backend wrapping a [`HardwareEncoder`] implementation
+#[cfg_attr(not(feature = "nvenc"), allow(dead_code))]
+struct HardwareBackend {
+    name: &'static str,
+    codecs: Vec<VideoCodec>,
+    factory: fn() -> Box<dyn HardwareEncoder>,
+}
+
+#[cfg_attr(not(feature = "nvenc"), allow(dead_code))]
+impl HardwareBackend {
+    fn new(name: &'static str, codecs: Vec<VideoCodec>, factory: fn() -> Box<dyn HardwareEncoder>) -> Self {
+        Self { name, codecs, factory }
+    }
+}
+
+impl EncoderBackend for HardwareBackend {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn kind(&self) -> BackendKind {
+        BackendKind::Hardware
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        let caps = hardware::detect_hardware_acceleration();
+        BackendCapabilities {
+            codecs: self.codecs.iter().copied().filter(|&c| caps.can_encode(c)).collect(),
+            profiles: vec!["main", "high"],
+            max_width: 4096,
+            max_height: 4096,
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        (self.factory)().is_available()
+    }
+
+    fn create(&self) -> Result<Box<dyn VideoEncoder>> {
+        Ok(Box::new(HardwareVideoEncoder::new((self.factory)())))
+    }
+}
+
+/// Adapts a [`HardwareEncoder`] to the [`VideoEncoder`] trait
+struct HardwareVideoEncoder {
+    inner: Box<dyn HardwareEncoder>,
+    config: Option<EncoderConfig>,
+    stats: Mutex<EncoderStats>,
+    frame_counter: u64,
+    /// Resampler from the input to the encoded resolution
+    scaler: Option<Scaler>,
+}
+
+impl HardwareVideoEncoder {
+    fn new(inner: Box<dyn HardwareEncoder>) -> Self {
+        Self {
+            inner,
+            config: None,
+            stats: Mutex::new(EncoderStats::default()),
+            frame_counter: 0,
+            scaler: None,
+        }
+    }
+}
+
+impl VideoEncoder for HardwareVideoEncoder {
+    fn configure(&mut self, config: EncoderConfig) -> Result<()> {
+        self.inner.initialize(config.width, config.height, config.bitrate)?;
+        self.scaler = None;
+        self.config = Some(config);
+        Ok(())
+    }
+
+    fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame> {
+        let config = self.config.as_ref()
+            .ok_or_else(|| RemoteCError::EncodingError("Encoder not configured".to_string()))?;
+        let frame = scale::prepare_encoder_input(config, &mut self.scaler, frame)?;
+        let data = self.inner.encode_frame(&frame, timestamp)?;
+
+        self.frame_counter += 1;
+        let is_keyframe = self.frame_counter % u64::from(config.keyframe_interval.max(1)) == 1;
+        let temporal_layer = svc::temporal_layer(config.scalability_mode, self.frame_counter, config.keyframe_interval);
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.frames_encoded += 1;
+        if is_keyframe {
+            stats.keyframes_encoded += 1;
+        }
+        stats.current_bitrate = config.bitrate;
+
+        Ok(EncodedFrame {
+            data,
+            timestamp,
+            is_keyframe,
+            sequence: self.frame_counter,
+            layer_id: 0,
+            temporal_layer,
+            width: config.width,
+            height: config.height,
+        })
+    }
+
+    fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+        Ok(Vec::new())
+    }
+
+    fn get_stats(&self) -> EncoderStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.frame_counter = 0;
+        *self.stats.lock().unwrap() = EncoderStats::default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct MockBackend {
+        name: &'static str,
+        kind: BackendKind,
+        codecs: Vec<VideoCodec>,
+        available: bool,
+        fail_create: bool,
+        created: AtomicUsize,
+    }
+
+    impl MockBackend {
+        fn new(name: &'static str, kind: BackendKind, codecs: Vec<VideoCodec>) -> Self {
+            Self {
+                name,
+                kind,
+                codecs,
+                available: true,
+                fail_create: false,
+                created: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl EncoderBackend for MockBackend {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn kind(&self) -> BackendKind {
+            self.kind
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities {
+                codecs: self.codecs.clone(),
+                profiles: vec!["main"],
+                max_width: 3840,
+                max_height: 2160,
+            }
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        fn create(&self) -> Result<Box<dyn VideoEncoder>> {
+            self.created.fetch_add(1, Ordering::SeqCst);
+            if self.fail_create {
+                return Err(RemoteCError::EncodingError("mock failure".to_string()));
+            }
+            Ok(Box::new(VP8Encoder::new()?))
+        }
+    }
+
+    fn config(hardware_acceleration: bool) -> EncoderConfig {
+        EncoderConfig {
+            codec: VideoCodec::VP8,
+            width: 64,
+            height: 64,
+            hardware_acceleration,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hardware_preferred_when_enabled() {
+        let mut registry = EncoderRegistry::new();
+        registry.register(Arc::new(MockBackend::new("sw", BackendKind::Software, vec![VideoCodec::VP8])));
+        registry.register(Arc::new(MockBackend::new("hw", BackendKind::Hardware, vec![VideoCodec::VP8])));
+
+        assert_eq!(registry.create_encoder(&config(true)).unwrap().backend, "hw");
+        assert_eq!(registry.create_encoder(&config(false)).unwrap().backend, "sw");
+    }
+
+    #[test]
+    fn test_falls_back_past_unavailable_and_failing_backends() {
+        let mut unavailable = MockBackend::new("hw-missing", BackendKind::Hardware, vec![VideoCodec::VP8]);
+        unavailable.available = false;
+        let mut failing = MockBackend::new("hw-broken", BackendKind::Hardware, vec![VideoCodec::VP8]);
+        failing.fail_create = true;
+        let unavailable = Arc::new(unavailable);
+        let failing = Arc::new(failing);
+
+        let mut registry = EncoderRegistry::new();
+        registry.register(unavailable.clone());
+        registry.register(failing.clone());
+        registry.register(Arc::new(MockBackend::new("sw", BackendKind::Software, vec![VideoCodec::VP8])));
+
+        let selected = registry.create_encoder(&config(true)).unwrap();
+        assert_eq!(selected.backend, "sw");
+        assert_eq!(selected.kind, BackendKind::Software);
+        assert_eq!(unavailable.created.load(Ordering::SeqCst), 0);
+        assert_eq!(failing.created.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_capabilities_filter_candidates() {
+        let mut registry = EncoderRegistry::new();
+        registry.register(Arc::new(MockBackend::new("h264-only", BackendKind::Hardware, vec![VideoCodec::H264])));
+        registry.register(Arc::new(MockBackend::new("sw", BackendKind::Software, vec![VideoCodec::VP8])));
+
+        let names = |cfg: &EncoderConfig| -> Vec<String> {
+            registry.candidates(cfg).iter().map(|b| b.name().to_string()).collect()
+        };
+        assert_eq!(names(&config(true)), vec!["sw"]);
+
+        let too_large = EncoderConfig { width: 7680, height: 4320, ..config(true) };
+        assert!(names(&too_large).is_empty());
+        assert!(registry.create_encoder(&too_large).is_err());
+
+        assert_eq!(names(&EncoderConfig { profile: Some("main"), ..config(true) }), vec!["sw"]);
+        assert!(names(&EncoderConfig { profile: Some("high"), ..config(true) }).is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_profile_falls_back_to_lossless() {
+        let h264_high = EncoderConfig { codec: VideoCodec::H264, profile: Some("high"), ..config(false) };
+        let selected = EncoderRegistry::with_defaults().create_encoder(&h264_high).unwrap();
+        assert_eq!(selected.codec, VideoCodec::Lossless);
+
+        let h264_baseline = EncoderConfig { profile: Some("baseline"), ..h264_high };
+        let selected = EncoderRegistry::with_defaults().create_encoder(&h264_baseline).unwrap();
+        assert_eq!(selected.backend, "software-h264");
+    }
+
+    #[test]
+    fn test_lossless_is_last_resort() {
+        let mut registry = EncoderRegistry::new();
+        registry.register(Arc::new(LosslessBackend));
+        let mut broken = MockBackend::new("sw", BackendKind::Software, vec![VideoCodec::VP8]);
+        broken.fail_create = true;
+        registry.register(Arc::new(broken));
+
+        let mut selected = registry.create_encoder(&config(true)).unwrap();
+        assert_eq!(selected.kind, BackendKind::Lossless);
+        // The stream is reported as lossless, not as the requested VP8
+        assert_eq!(selected.codec, VideoCodec::Lossless);
+        let frame = selected.encoder.encode_frame(&vec![0u8; 64 * 64 * 4], 0).unwrap();
+        assert!(frame.is_keyframe);
+
+        let mut decoder = crate::video::create_decoder(selected.codec).unwrap();
+        assert_eq!(decoder.decode_frame(&frame).unwrap().data, vec![0u8; 64 * 64 * 4]);
+    }
+
+    #[test]
+    fn test_default_registry_order() {
+        let kinds: Vec<BackendKind> = EncoderRegistry::with_defaults().backends().iter().map(|b| b.kind()).collect();
+        assert!(kinds.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(kinds.last(), Some(&BackendKind::Lossless));
+
+        let selected = EncoderRegistry::with_defaults().create_encoder(&config(true)).unwrap();
+        assert_eq!(selected.backend, "software-vp8");
+        assert_eq!(selected.codec, VideoCodec::VP8);
+    }
+
+    #[cfg(feature = "nvenc")]
+    #[test]
+    fn test_nvenc_placeholder_never_selected() {
+        let config = EncoderConfig { codec: VideoCodec::H264, ..config(true) };
+        let selected = EncoderRegistry::with_defaults().create_encoder(&config).unwrap();
+        assert_eq!(selected.backend, "software-h264");
+    }
+}
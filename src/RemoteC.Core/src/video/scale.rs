@@ -0,0 +1,503 @@
+//! Frame resampling between capture and encode
+//!
+//! Captured frames are often larger than what the link can carry, e.g. a 4K
+//! desktop streamed as 1080p. [`Scaler`] resamples BGRA and I420 frames with
+//! a separable filter. Coefficients are computed once per size pair, and rows
+//! are processed in parallel with tight inner loops the compiler vectorizes.
+//!
+//! [`ScaleMapping`] describes the relation between source and encoded
+//! resolution so viewer input can be mapped back to source pixels.
+
+use super::EncoderConfig;
+use crate::{RemoteCError, Result};
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+/// Resampling filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleFilter {
+    /// Linear interpolation, fastest
+    Bilinear,
+    /// Lanczos windowed sinc with 3 lobes, sharpest
+    Lanczos3,
+    /// Area averaging, best for large downscales of text
+    #[default]
+    Area,
+}
+
+impl ScaleFilter {
+    /// Kernel radius in source pixels at 1:1 scale
+    fn support(self) -> f64 {
+        match self {
+            ScaleFilter::Bilinear => 1.0,
+            ScaleFilter::Lanczos3 => 3.0,
+            ScaleFilter::Area => 0.5,
+        }
+    }
+
+    fn kernel(self, x: f64) -> f64 {
+        match self {
+            ScaleFilter::Bilinear => (1.0 - x.abs()).max(0.0),
+            ScaleFilter::Lanczos3 => {
+                if x.abs() >= 3.0 {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / 3.0)
+                }
+            }
+            // Area weights are computed from pixel coverage instead
+            ScaleFilter::Area => {
+                if x.abs() <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Mapping between a source resolution and an encoded resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleMapping {
+    /// Source (captured) width
+    pub source_width: u32,
+    /// Source (captured) height
+    pub source_height: u32,
+    /// Target (encoded) width
+    pub target_width: u32,
+    /// Target (encoded) height
+    pub target_height: u32,
+}
+
+impl ScaleMapping {
+    /// Create a mapping between two resolutions
+    pub fn new(source_width: u32, source_height: u32, target_width: u32, target_height: u32) -> Self {
+        Self {
+            source_width,
+            source_height,
+            target_width,
+            target_height,
+        }
+    }
+
+    /// Mapping that leaves coordinates unchanged
+    pub fn identity(width: u32, height: u32) -> Self {
+        Self::new(width, height, width, height)
+    }
+
+    /// Whether source and target resolution are the same
+    pub fn is_identity(&self) -> bool {
+        self.source_width == self.target_width && self.source_height == self.target_height
+    }
+
+    /// Map a target pixel to the source pixel it was sampled around
+    pub fn to_source(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            map_coordinate(x, self.target_width, self.source_width),
+            map_coordinate(y, self.target_height, self.source_height),
+        )
+    }
+
+    /// Map a source pixel to the target pixel covering it
+    pub fn to_target(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            map_coordinate(x, self.source_width, self.target_width),
+            map_coordinate(y, self.source_height, self.target_height),
+        )
+    }
+}
+
+/// Map a pixel centre between two axis lengths, clamped to the output range
+fn map_coordinate(value: i32, from: u32, to: u32) -> i32 {
+    if from == 0 || to == 0 {
+        return 0;
+    }
+    let mapped = ((f64::from(value) + 0.5) * f64::from(to) / f64::from(from)).floor();
+    (mapped as i64).clamp(0, i64::from(to) - 1) as i32
+}
+
+/// Filter taps for one output sample
+#[derive(Debug, Clone)]
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+fn compute_taps(source: u32, target: u32, filter: ScaleFilter) -> Vec<Taps> {
+    let ratio = f64::from(source) / f64::from(target);
+    // Widen the kernel when downscaling so every source pixel contributes
+    let filter_scale = ratio.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..target)
+        .map(|i| {
+            let center = (f64::from(i) + 0.5) * ratio;
+            let start = ((center - support).floor().max(0.0)) as usize;
+            let end = ((center + support).ceil() as usize).min(source as usize);
+
+            let mut weights: Vec<f64> = (start..end)
+                .map(|s| match filter {
+                    ScaleFilter::Area => {
+                        // Overlap of source pixel [s, s + 1] with the footprint
+                        let lo = (center - support).max(s as f64);
+                        let hi = (center + support).min(s as f64 + 1.0);
+                        (hi - lo).max(0.0)
+                    }
+                    _ => filter.kernel((s as f64 + 0.5 - center) / filter_scale),
+                })
+                .collect();
+
+            let sum: f64 = weights.iter().sum();
+            if sum.abs() > f64::EPSILON {
+                for w in &mut weights {
+                    *w /= sum;
+                }
+            } else {
+                // Degenerate footprint: take the nearest pixel
+                weights.fill(0.0);
+                let nearest = (center.floor() as usize).clamp(start, end.saturating_sub(1));
+                weights[nearest - start] = 1.0;
+            }
+
+            Taps {
+                start,
+                weights: weights.into_iter().map(|w| w as f32).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Taps for both axes of one plane
+#[derive(Debug, Clone)]
+struct PlaneTaps {
+    horizontal: Vec<Taps>,
+    vertical: Vec<Taps>,
+}
+
+impl PlaneTaps {
+    fn new(sw: u32, sh: u32, tw: u32, th: u32, filter: ScaleFilter) -> Self {
+        Self {
+            horizontal: compute_taps(sw, tw, filter),
+            vertical: compute_taps(sh, th, filter),
+        }
+    }
+}
+
+/// Reusable resampler for a fixed source and target resolution
+#[derive(Debug, Clone)]
+pub struct Scaler {
+    mapping: ScaleMapping,
+    filter: ScaleFilter,
+    luma: PlaneTaps,
+    chroma: PlaneTaps,
+}
+
+impl Scaler {
+    /// Create a scaler from `source` to `target` resolution
+    pub fn new(mapping: ScaleMapping, filter: ScaleFilter) -> Result<Self> {
+        if mapping.source_width == 0
+            || mapping.source_height == 0
+            || mapping.target_width == 0
+            || mapping.target_height == 0
+        {
+            return Err(RemoteCError::EncodingError(format!(
+                "Invalid scale dimensions: {}x{} -> {}x{}",
+                mapping.source_width, mapping.source_height, mapping.target_width, mapping.target_height
+            )));
+        }
+
+        let (sw, sh, tw, th) = (
+            mapping.source_width,
+            mapping.source_height,
+            mapping.target_width,
+            mapping.target_height,
+        );
+
+        Ok(Self {
+            mapping,
+            filter,
+            luma: PlaneTaps::new(sw, sh, tw, th, filter),
+            chroma: PlaneTaps::new(sw.div_ceil(2), sh.div_ceil(2), tw.div_ceil(2), th.div_ceil(2), filter),
+        })
+    }
+
+    /// Coordinate mapping of this scaler
+    pub fn mapping(&self) -> ScaleMapping {
+        self.mapping
+    }
+
+    /// Filter used by this scaler
+    pub fn filter(&self) -> ScaleFilter {
+        self.filter
+    }
+
+    /// Scale a packed 4-byte-per-pixel frame (BGRA or RGBA)
+    pub fn scale_bgra(&self, src: &[u8]) -> Result<Vec<u8>> {
+        let m = self.mapping;
+        check_len(src, m.source_width as usize * m.source_height as usize * 4)?;
+        if m.is_identity() {
+            return Ok(src.to_vec());
+        }
+
+        Ok(resample::<4>(
+            src,
+            m.source_width as usize,
+            m.source_height as usize,
+            &self.luma,
+        ))
+    }
+
+    /// Scale a planar I420 frame
+    pub fn scale_i420(&self, src: &[u8]) -> Result<Vec<u8>> {
+        let m = self.mapping;
+        let (sw, sh) = (m.source_width as usize, m.source_height as usize);
+        let (scw, sch) = (sw.div_ceil(2), sh.div_ceil(2));
+        check_len(src, sw * sh + 2 * scw * sch)?;
+        if m.is_identity() {
+            return Ok(src.to_vec());
+        }
+
+        let (y, chroma) = src.split_at(sw * sh);
+        let (u, v) = chroma.split_at(scw * sch);
+
+        let mut out = resample::<1>(y, sw, sh, &self.luma);
+        out.extend(resample::<1>(u, scw, sch, &self.chroma));
+        out.extend(resample::<1>(v, scw, sch, &self.chroma));
+        Ok(out)
+    }
+}
+
+fn check_len(src: &[u8], expected: usize) -> Result<()> {
+    if src.len() != expected {
+        return Err(RemoteCError::EncodingError(format!(
+            "Invalid frame size: expected {}, got {}",
+            expected,
+            src.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Two-pass separable resampling of an interleaved plane with `C` channels
+fn resample<const C: usize>(src: &[u8], sw: usize, sh: usize, taps: &PlaneTaps) -> Vec<u8> {
+    let tw = taps.horizontal.len();
+    let th = taps.vertical.len();
+
+    // Horizontal pass: sh rows of tw pixels
+    let mut horizontal = vec![0u8; tw * sh * C];
+    horizontal
+        .par_chunks_mut(tw * C)
+        .zip(src.par_chunks(sw * C))
+        .for_each(|(out, row)| {
+            for (x, tap) in taps.horizontal.iter().enumerate() {
+                let mut acc = [0f32; C];
+                for (i, &w) in tap.weights.iter().enumerate() {
+                    let px = &row[(tap.start + i) * C..(tap.start + i + 1) * C];
+                    for c in 0..C {
+                        acc[c] += f32::from(px[c]) * w;
+                    }
+                }
+                for c in 0..C {
+                    out[x * C + c] = clamp_u8(acc[c]);
+                }
+            }
+        });
+
+    // Vertical pass: th rows of tw pixels
+    let row_len = tw * C;
+    let mut out = vec![0u8; row_len * th];
+    out.par_chunks_mut(row_len).enumerate().for_each(|(y, dst)| {
+        let tap = &taps.vertical[y];
+        let mut acc = vec![0f32; row_len];
+        for (i, &w) in tap.weights.iter().enumerate() {
+            let start = (tap.start + i) * row_len;
+            let row = &horizontal[start..start + row_len];
+            for (a, &p) in acc.iter_mut().zip(row) {
+                *a += f32::from(p) * w;
+            }
+        }
+        for (d, a) in dst.iter_mut().zip(acc) {
+            *d = clamp_u8(a);
+        }
+    });
+
+    out
+}
+
+#[inline]
+fn clamp_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Scale a BGRA frame
+pub fn scale_bgra(
+    src: &[u8],
+    source: (u32, u32),
+    target: (u32, u32),
+    filter: ScaleFilter,
+) -> Result<Vec<u8>> {
+    Scaler::new(ScaleMapping::new(source.0, source.1, target.0, target.1), filter)?.scale_bgra(src)
+}
+
+/// Scale an I420 frame
+pub fn scale_i420(
+    src: &[u8],
+    source: (u32, u32),
+    target: (u32, u32),
+    filter: ScaleFilter,
+) -> Result<Vec<u8>> {
+    Scaler::new(ScaleMapping::new(source.0, source.1, target.0, target.1), filter)?.scale_i420(src)
+}
+
+/// Validate an encoder input frame and scale it to the encoded resolution
+///
+/// `scaler` keeps the resampler between frames; it is only rebuilt when the
+/// configured mapping or filter changes.
+pub(crate) fn prepare_encoder_input<'a>(
+    config: &EncoderConfig,
+    scaler: &mut Option<Scaler>,
+    frame: &'a [u8],
+) -> Result<Cow<'a, [u8]>> {
+    let mapping = config.scale_mapping();
+    check_len(
+        frame,
+        mapping.source_width as usize * mapping.source_height as usize * 4,
+    )?;
+
+    if mapping.is_identity() {
+        return Ok(Cow::Borrowed(frame));
+    }
+
+    Ok(Cow::Owned(cached_scaler(scaler, mapping, config.scale_filter)?.scale_bgra(frame)?))
+}
+
+/// Scaler in `slot`, replaced if it was built for another mapping or filter
+pub(crate) fn cached_scaler(slot: &mut Option<Scaler>, mapping: ScaleMapping, filter: ScaleFilter) -> Result<&Scaler> {
+    Ok(match slot.take() {
+        Some(cached) if cached.mapping == mapping && cached.filter == filter => slot.insert(cached),
+        _ => slot.insert(Scaler::new(mapping, filter)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [ScaleFilter; 3] = [ScaleFilter::Bilinear, ScaleFilter::Lanczos3, ScaleFilter::Area];
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let mut frame = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                frame.extend_from_slice(&[(x * 255 / width) as u8, (y * 255 / height) as u8, 128, 255]);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_uniform_frame_stays_uniform() {
+        let src = vec![77u8; 40 * 30 * 4];
+        for filter in FILTERS {
+            for target in [(20, 15), (13, 7), (80, 60)] {
+                let out = scale_bgra(&src, (40, 30), target, filter).unwrap();
+                assert_eq!(out.len(), (target.0 * target.1 * 4) as usize);
+                assert!(out.iter().all(|&p| p == 77), "{:?} {:?}", filter, target);
+            }
+        }
+    }
+
+    #[test]
+    fn test_area_downscale_averages_blocks() {
+        // 4x2 checkerboard of 0/200 in a single channel
+        let mut src = Vec::new();
+        for y in 0..2 {
+            for x in 0..4 {
+                let v = if (x + y) % 2 == 0 { 0 } else { 200 };
+                src.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+
+        let out = scale_bgra(&src, (4, 2), (2, 1), ScaleFilter::Area).unwrap();
+        assert_eq!(out, vec![100, 100, 100, 255, 100, 100, 100, 255]);
+    }
+
+    #[test]
+    fn test_downscale_preserves_gradient() {
+        let src = gradient(64, 64);
+        for filter in FILTERS {
+            let out = scale_bgra(&src, (64, 64), (16, 16), filter).unwrap();
+            // Blue channel increases left to right, green top to bottom
+            let px = |x: usize, y: usize| &out[(y * 16 + x) * 4..(y * 16 + x) * 4 + 4];
+            assert!(px(15, 0)[0] > px(0, 0)[0] + 200);
+            assert!(px(0, 15)[1] > px(0, 0)[1] + 200);
+            assert_eq!(px(8, 8)[2], 128);
+        }
+    }
+
+    #[test]
+    fn test_scale_i420_plane_sizes() {
+        let (w, h) = (33u32, 17u32);
+        let size = (w * h + 2 * w.div_ceil(2) * h.div_ceil(2)) as usize;
+        let src = vec![90u8; size];
+
+        let out = scale_i420(&src, (w, h), (16, 9), ScaleFilter::Lanczos3).unwrap();
+        assert_eq!(out.len(), 16 * 9 + 2 * 8 * 5);
+        assert!(out.iter().all(|&p| p == 90));
+        assert!(scale_i420(&src[1..], (w, h), (16, 9), ScaleFilter::Area).is_err());
+    }
+
+    #[test]
+    fn test_mapping_round_trip() {
+        let mapping = ScaleMapping::new(3840, 2160, 1920, 1080);
+        assert!(!mapping.is_identity());
+        assert_eq!(mapping.to_source(0, 0), (1, 1));
+        assert_eq!(mapping.to_source(960, 540), (1921, 1081));
+        assert_eq!(mapping.to_source(5000, -10), (3839, 0));
+        assert_eq!(mapping.to_target(1921, 1081), (960, 540));
+
+        let identity = ScaleMapping::identity(800, 600);
+        assert_eq!(identity.to_source(123, 456), (123, 456));
+    }
+
+    #[test]
+    fn test_encoder_input_is_scaled() {
+        let config = EncoderConfig {
+            width: 32,
+            height: 16,
+            source_size: Some((64, 32)),
+            ..Default::default()
+        };
+        let src = gradient(64, 32);
+        let mut scaler = None;
+
+        let prepared = prepare_encoder_input(&config, &mut scaler, &src).unwrap();
+        assert_eq!(prepared.len(), 32 * 16 * 4);
+        assert!(prepare_encoder_input(&config, &mut scaler, &src[..100]).is_err());
+        assert_eq!(scaler.as_ref().map(Scaler::mapping), Some(config.scale_mapping()));
+
+        // A new source size replaces the cached scaler
+        let resized = EncoderConfig { source_size: Some((48, 24)), ..config };
+        let smaller = gradient(48, 24);
+        let prepared = prepare_encoder_input(&resized, &mut scaler, &smaller).unwrap();
+        assert_eq!(prepared.len(), 32 * 16 * 4);
+        assert_eq!(scaler.as_ref().map(Scaler::mapping), Some(resized.scale_mapping()));
+
+        let unscaled = EncoderConfig {
+            width: 64,
+            height: 32,
+            ..Default::default()
+        };
+        assert!(matches!(prepare_encoder_input(&unscaled, &mut scaler, &src).unwrap(), Cow::Borrowed(_)));
+    }
+}
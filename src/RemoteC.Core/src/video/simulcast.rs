@@ -0,0 +1,302 @@
+//! Simulcast encoding
+//!
+//! [`SimulcastEncoder`] encodes every captured frame into several
+//! independent layers of decreasing resolution and bitrate. Each
+//! [`EncodedFrame`] carries the `layer_id` it belongs to, so a forwarding
+//! server can pick a layer per viewer according to that viewer's link.
+
+use super::{create_encoder, EncodedFrame, EncoderConfig, EncoderStats, VideoEncoder};
+use crate::{RemoteCError, Result};
+
+/// Description of a single simulcast layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulcastLayer {
+    /// Layer identifier written to `EncodedFrame::layer_id`
+    pub id: u8,
+    /// Resolution divisor relative to the source (1 = full resolution)
+    pub scale_divisor: u32,
+    /// Target bitrate of the layer in bits per second
+    pub bitrate: u32,
+}
+
+/// Simulcast configuration
+#[derive(Debug, Clone)]
+pub struct SimulcastConfig {
+    /// Full-resolution encoder settings; each layer derives from it
+    pub encoder: EncoderConfig,
+    /// Layers to produce, ordered from highest to lowest quality
+    pub layers: Vec<SimulcastLayer>,
+}
+
+impl SimulcastConfig {
+    /// Full, half and quarter resolution layers
+    ///
+    /// Bitrates follow the pixel count: the half layer gets a quarter of
+    /// the full layer's bitrate and the quarter layer a sixteenth.
+    pub fn three_layers(encoder: EncoderConfig) -> Self {
+        Self::with_layer_count(encoder, 3)
+    }
+
+    /// Full and half resolution layers
+    pub fn two_layers(encoder: EncoderConfig) -> Self {
+        Self::with_layer_count(encoder, 2)
+    }
+
+    fn with_layer_count(encoder: EncoderConfig, count: u8) -> Self {
+        let layers = (0..count)
+            .map(|id| SimulcastLayer {
+                id,
+                scale_divisor: 1 << id,
+                bitrate: encoder.bitrate >> (2 * id),
+            })
+            .collect();
+
+        Self { encoder, layers }
+    }
+}
+
+impl Default for SimulcastConfig {
+    fn default() -> Self {
+        Self::three_layers(EncoderConfig::default())
+    }
+}
+
+/// Per-layer statistics
+#[derive(Debug, Clone)]
+pub struct SimulcastLayerStats {
+    /// Layer description
+    pub layer: SimulcastLayer,
+    /// Encoded width
+    pub width: u32,
+    /// Encoded height
+    pub height: u32,
+    /// Whether the layer is currently being encoded
+    pub active: bool,
+    /// Statistics of the layer encoder
+    pub encoder: EncoderStats,
+}
+
+struct LayerEncoder {
+    layer: SimulcastLayer,
+    width: u32,
+    height: u32,
+    active: bool,
+    encoder: Box<dyn VideoEncoder>,
+}
+
+/// Encoder producing several resolution layers from one capture
+pub struct SimulcastEncoder {
+    config: SimulcastConfig,
+    layers: Vec<LayerEncoder>,
+}
+
+impl SimulcastEncoder {
+    /// Create one encoder of the configured codec per layer
+    pub fn new(config: SimulcastConfig) -> Result<Self> {
+        let codec = config.encoder.codec;
+        Self::with_factory(config, |_| create_encoder(codec))
+    }
+
+    /// Create layer encoders with a custom factory
+    pub fn with_factory<F>(config: SimulcastConfig, mut factory: F) -> Result<Self>
+    where
+        F: FnMut(&SimulcastLayer) -> Result<Box<dyn VideoEncoder>>,
+    {
+        if config.layers.is_empty() {
+            return Err(RemoteCError::EncodingError("Simulcast needs at least one layer".to_string()));
+        }
+
+        let mut layers = Vec::with_capacity(config.layers.len());
+        for layer in &config.layers {
+            if layer.scale_divisor == 0 {
+                return Err(RemoteCError::EncodingError(format!(
+                    "Invalid scale divisor for simulcast layer {}",
+                    layer.id
+                )));
+            }
+            if layers.iter().any(|l: &LayerEncoder| l.layer.id == layer.id) {
+                return Err(RemoteCError::EncodingError(format!(
+                    "Duplicate simulcast layer {}",
+                    layer.id
+                )));
+            }
+
+            let (width, height) = layer_dimensions(&config.encoder, layer.scale_divisor);
+            let mut encoder = factory(layer)?;
+            encoder.configure(Self::layer_config(&config.encoder, layer, width, height))?;
+
+            layers.push(LayerEncoder {
+                layer: *layer,
+                width,
+                height,
+                active: true,
+                encoder,
+            });
+        }
+
+        Ok(Self { config, layers })
+    }
+
+    /// Configured layers
+    pub fn layers(&self) -> &[SimulcastLayer] {
+        &self.config.layers
+    }
+
+    /// Encoded resolution of a layer
+    pub fn layer_dimensions(&self, id: u8) -> Option<(u32, u32)> {
+        self.layers
+            .iter()
+            .find(|l| l.layer.id == id)
+            .map(|l| (l.width, l.height))
+    }
+
+    /// Enable or disable a layer, e.g. when no viewer receives it
+    ///
+    /// A re-enabled layer restarts with a keyframe.
+    pub fn set_layer_active(&mut self, id: u8, active: bool) -> Result<()> {
+        let layer = self
+            .layers
+            .iter_mut()
+            .find(|l| l.layer.id == id)
+            .ok_or_else(|| RemoteCError::EncodingError(format!("Unknown simulcast layer {}", id)))?;
+
+        if active && !layer.active {
+            layer.encoder.reset()?;
+        }
+        layer.active = active;
+        Ok(())
+    }
+
+    /// Highest quality layer whose bitrate fits the available bandwidth
+    ///
+    /// Falls back to the lowest layer when none fits.
+    pub fn layer_for_bitrate(&self, available_bitrate: u32) -> u8 {
+        self.config
+            .layers
+            .iter()
+            .filter(|l| l.bitrate <= available_bitrate)
+            .max_by_key(|l| l.bitrate)
+            .or_else(|| self.config.layers.iter().min_by_key(|l| l.bitrate))
+            .map_or(0, |l| l.id)
+    }
+
+    /// Encode a full-resolution frame into every active layer
+    pub fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<Vec<EncodedFrame>> {
+        let mut frames = Vec::with_capacity(self.layers.len());
+        for layer in self.layers.iter_mut().filter(|l| l.active) {
+            let mut encoded = layer.encoder.encode_frame(frame, timestamp)?;
+            encoded.layer_id = layer.layer.id;
+            frames.push(encoded);
+        }
+        Ok(frames)
+    }
+
+    /// Flush all layer encoders
+    pub fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+        let mut frames = Vec::new();
+        for layer in &mut self.layers {
+            let id = layer.layer.id;
+            frames.extend(layer.encoder.flush()?.into_iter().map(|mut frame| {
+                frame.layer_id = id;
+                frame
+            }));
+        }
+        Ok(frames)
+    }
+
+    /// Per-layer statistics
+    pub fn stats(&self) -> Vec<SimulcastLayerStats> {
+        self.layers
+            .iter()
+            .map(|l| SimulcastLayerStats {
+                layer: l.layer,
+                width: l.width,
+                height: l.height,
+                active: l.active,
+                encoder: l.encoder.get_stats(),
+            })
+            .collect()
+    }
+
+    /// Reset every layer encoder
+    pub fn reset(&mut self) -> Result<()> {
+        for layer in &mut self.layers {
+            layer.encoder.reset()?;
+        }
+        Ok(())
+    }
+
+    fn layer_config(base: &EncoderConfig, layer: &SimulcastLayer, width: u32, height: u32) -> EncoderConfig {
+        EncoderConfig {
+            bitrate: layer.bitrate,
+            width,
+            height,
+            source_size: Some(base.source_size.unwrap_or((base.width, base.height))),
+            ..base.clone()
+        }
+    }
+}
+
+/// Layer resolution, rounded down to even dimensions for chroma subsampling
+fn layer_dimensions(base: &EncoderConfig, divisor: u32) -> (u32, u32) {
+    let even = |v: u32| ((v / divisor) & !1).max(2);
+    (even(base.width), even(base.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::VideoCodec;
+
+    fn config() -> SimulcastConfig {
+        SimulcastConfig::three_layers(EncoderConfig {
+            codec: VideoCodec::VP8,
+            bitrate: 4_000_000,
+            width: 160,
+            height: 90,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_three_layer_dimensions() {
+        let enc = SimulcastEncoder::new(config()).unwrap();
+
+        assert_eq!(enc.layer_dimensions(0), Some((160, 90)));
+        assert_eq!(enc.layer_dimensions(1), Some((80, 44)));
+        assert_eq!(enc.layer_dimensions(2), Some((40, 22)));
+        assert_eq!(enc.layer_dimensions(3), None);
+
+        let bitrates: Vec<u32> = enc.layers().iter().map(|l| l.bitrate).collect();
+        assert_eq!(bitrates, vec![4_000_000, 1_000_000, 250_000]);
+    }
+
+    #[test]
+    fn test_frames_tagged_with_layer() {
+        let mut enc = SimulcastEncoder::new(config()).unwrap();
+        let frame = vec![10u8; 160 * 90 * 4];
+
+        let frames = enc.encode_frame(&frame, 0).unwrap();
+        let ids: Vec<u8> = frames.iter().map(|f| f.layer_id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert!(frames.iter().all(|f| f.is_keyframe));
+        // Smaller layers produce less data
+        assert!(frames[0].data.len() > frames[1].data.len());
+        assert!(frames[1].data.len() > frames[2].data.len());
+
+        enc.set_layer_active(0, false).unwrap();
+        let frames = enc.encode_frame(&frame, 33_333).unwrap();
+        assert_eq!(frames.iter().map(|f| f.layer_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(enc.set_layer_active(9, true).is_err());
+    }
+
+    #[test]
+    fn test_layer_for_bitrate() {
+        let enc = SimulcastEncoder::new(config()).unwrap();
+
+        assert_eq!(enc.layer_for_bitrate(10_000_000), 0);
+        assert_eq!(enc.layer_for_bitrate(2_000_000), 1);
+        assert_eq!(enc.layer_for_bitrate(300_000), 2);
+        assert_eq!(enc.layer_for_bitrate(1_000), 2);
+    }
+}
@@ -0,0 +1,223 @@
+//! Temporal scalability (SVC)
+//!
+//! With temporal layering every frame belongs to a layer T0..Tn, and frames
+//! only reference frames of the same or a lower layer. Dropping all frames
+//! above some layer therefore halves (or quarters) the framerate without
+//! breaking decoding. [`TemporalLayerFilter`] does that dropping for a relay
+//! or a congested receiver.
+
+use super::EncodedFrame;
+use std::fmt;
+use std::str::FromStr;
+
+/// Scalability mode, named as in the WebRTC SVC specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalabilityMode {
+    /// Single temporal layer
+    #[default]
+    L1T1,
+    /// Two temporal layers, pattern T0 T1
+    L1T2,
+    /// Three temporal layers, pattern T0 T2 T1 T2
+    L1T3,
+}
+
+impl ScalabilityMode {
+    /// Number of temporal layers
+    pub fn temporal_layers(self) -> u8 {
+        match self {
+            ScalabilityMode::L1T1 => 1,
+            ScalabilityMode::L1T2 => 2,
+            ScalabilityMode::L1T3 => 3,
+        }
+    }
+
+    /// Repeating temporal layer pattern
+    pub fn pattern(self) -> &'static [u8] {
+        match self {
+            ScalabilityMode::L1T1 => &[0],
+            ScalabilityMode::L1T2 => &[0, 1],
+            ScalabilityMode::L1T3 => &[0, 2, 1, 2],
+        }
+    }
+
+    /// Temporal layer of the frame at `position` within the current GOP
+    pub fn layer_for_frame(self, position: u64) -> u8 {
+        let pattern = self.pattern();
+        pattern[(position % pattern.len() as u64) as usize]
+    }
+
+    /// Fraction of the full framerate delivered up to and including `layer`
+    pub fn framerate_fraction(self, layer: u8) -> f64 {
+        let pattern = self.pattern();
+        let included = pattern.iter().filter(|&&l| l <= layer).count();
+        included as f64 / pattern.len() as f64
+    }
+}
+
+impl fmt::Display for ScalabilityMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScalabilityMode::L1T1 => "L1T1",
+            ScalabilityMode::L1T2 => "L1T2",
+            ScalabilityMode::L1T3 => "L1T3",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ScalabilityMode {
+    type Err = crate::RemoteCError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "L1T1" => Ok(ScalabilityMode::L1T1),
+            "L1T2" => Ok(ScalabilityMode::L1T2),
+            "L1T3" => Ok(ScalabilityMode::L1T3),
+            _ => Err(crate::RemoteCError::EncodingError(format!(
+                "Unsupported scalability mode: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Temporal layer for an encoder's `frame_counter`-th frame (1-based)
+///
+/// The pattern restarts at every keyframe so keyframes are always T0.
+pub(crate) fn temporal_layer(mode: ScalabilityMode, frame_counter: u64, keyframe_interval: u32) -> u8 {
+    let position = frame_counter.saturating_sub(1) % u64::from(keyframe_interval.max(1));
+    mode.layer_for_frame(position)
+}
+
+/// Selective forwarding filter for temporally layered streams
+///
+/// Lowering the target takes effect immediately. Raising it waits for the
+/// next T0 frame, the first point at which the upper layers' references are
+/// guaranteed to be present.
+#[derive(Debug, Clone)]
+pub struct TemporalLayerFilter {
+    target: u8,
+    current: u8,
+    frames_forwarded: u64,
+    frames_dropped: u64,
+}
+
+impl TemporalLayerFilter {
+    /// Create a filter forwarding layers up to `target`
+    pub fn new(target: u8) -> Self {
+        Self {
+            target,
+            current: target,
+            frames_forwarded: 0,
+            frames_dropped: 0,
+        }
+    }
+
+    /// Change the highest layer to forward
+    pub fn set_target(&mut self, target: u8) {
+        self.target = target;
+        if target < self.current {
+            self.current = target;
+        }
+    }
+
+    /// Requested highest layer
+    pub fn target(&self) -> u8 {
+        self.target
+    }
+
+    /// Highest layer currently being forwarded
+    pub fn current(&self) -> u8 {
+        self.current
+    }
+
+    /// Whether `frame` should be forwarded
+    pub fn forward(&mut self, frame: &EncodedFrame) -> bool {
+        if self.current < self.target && frame.temporal_layer == 0 {
+            self.current = self.target;
+        }
+
+        let forward = frame.temporal_layer <= self.current;
+        if forward {
+            self.frames_forwarded += 1;
+        } else {
+            self.frames_dropped += 1;
+        }
+        forward
+    }
+
+    /// Frames forwarded so far
+    pub fn frames_forwarded(&self) -> u64 {
+        self.frames_forwarded
+    }
+
+    /// Frames dropped so far
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sequence: u64, temporal_layer: u8) -> EncodedFrame {
+        EncodedFrame {
+            data: vec![0; 10],
+            timestamp: sequence * 33_333,
+            is_keyframe: sequence == 1,
+            sequence,
+            layer_id: 0,
+            temporal_layer,
+            width: 64,
+            height: 64,
+        }
+    }
+
+    #[test]
+    fn test_layer_patterns() {
+        let l1t3: Vec<u8> = (1..=8).map(|n| temporal_layer(ScalabilityMode::L1T3, n, 60)).collect();
+        assert_eq!(l1t3, vec![0, 2, 1, 2, 0, 2, 1, 2]);
+
+        let l1t2: Vec<u8> = (1..=4).map(|n| temporal_layer(ScalabilityMode::L1T2, n, 60)).collect();
+        assert_eq!(l1t2, vec![0, 1, 0, 1]);
+
+        // Pattern restarts at the keyframe
+        assert_eq!(temporal_layer(ScalabilityMode::L1T3, 6, 5), 0);
+        assert_eq!(ScalabilityMode::L1T3.framerate_fraction(1), 0.5);
+        assert_eq!("L1T2".parse::<ScalabilityMode>().unwrap(), ScalabilityMode::L1T2);
+        assert!("L3T3".parse::<ScalabilityMode>().is_err());
+    }
+
+    #[test]
+    fn test_filter_drops_upper_layers() {
+        let mut filter = TemporalLayerFilter::new(1);
+        let forwarded: Vec<u64> = (1..=8u64)
+            .map(|n| frame(n, ScalabilityMode::L1T3.layer_for_frame(n - 1)))
+            .filter(|f| filter.forward(f))
+            .map(|f| f.sequence)
+            .collect();
+
+        assert_eq!(forwarded, vec![1, 3, 5, 7]);
+        assert_eq!(filter.frames_dropped(), 4);
+    }
+
+    #[test]
+    fn test_filter_switches_up_only_at_t0() {
+        let mut filter = TemporalLayerFilter::new(0);
+        assert!(filter.forward(&frame(1, 0)));
+        assert!(!filter.forward(&frame(2, 2)));
+
+        filter.set_target(2);
+        assert!(!filter.forward(&frame(3, 1)));
+        assert!(!filter.forward(&frame(4, 2)));
+        assert!(filter.forward(&frame(5, 0)));
+        assert!(filter.forward(&frame(6, 2)));
+
+        // Switching down is immediate
+        filter.set_target(0);
+        assert!(!filter.forward(&frame(7, 1)));
+        assert_eq!(filter.current(), 0);
+    }
+}
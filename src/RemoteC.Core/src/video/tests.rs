@@ -1,4 +1,5 @@
 use super::*;
+use crate::RemoteCError;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
@@ -92,6 +93,10 @@ impl VideoEncoder for MockVideoEncoder {
             timestamp,
             is_keyframe,
             sequence: self.frame_counter,
+            layer_id: 0,
+            temporal_layer: 0,
+            width: config.width,
+            height: config.height,
         })
     }
     
@@ -147,7 +152,7 @@ impl VideoDecoder for MockVideoDecoder {
         Ok(())
     }
     
-    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<Vec<u8>> {
+    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<DecodedPicture> {
         if self.should_fail {
             self.stats.frames_errors += 1;
             return Err(RemoteCError::DecodingError("Mock decoding failure".to_string()));
@@ -171,7 +176,7 @@ impl VideoDecoder for MockVideoDecoder {
             (self.stats.avg_decode_time * (self.stats.frames_decoded - 1) as f64 + decode_time) 
             / self.stats.frames_decoded as f64;
         
-        Ok(decoded_data)
+        DecodedPicture::new((decoded_size / 4) as u32, 1, PixelFormat::Bgra, decoded_data)
     }
     
     fn get_stats(&self) -> DecoderStats {
@@ -213,6 +218,10 @@ fn test_encoder_configuration() {
         keyframe_interval: 120,
         quality: 90,
         hardware_acceleration: false,
+        source_size: None,
+        scale_filter: ScaleFilter::Lanczos3,
+        scalability_mode: ScalabilityMode::L1T1,
+        profile: None,
     };
     
     assert!(encoder.configure(config.clone()).is_ok());
@@ -364,13 +373,17 @@ fn test_decode_frame() {
         timestamp: 5000,
         is_keyframe: true,
         sequence: 1,
+        layer_id: 0,
+        temporal_layer: 0,
+        width: 320,
+        height: 240,
     };
     
     let result = decoder.decode_frame(&encoded_frame);
     assert!(result.is_ok());
     
     let decoded = result.unwrap();
-    assert_eq!(decoded.len(), 50000); // 1000 * 50
+    assert_eq!(decoded.data.len(), 50000); // 1000 * 50
 }
 
 #[test]
@@ -384,6 +397,10 @@ fn test_decoder_statistics() {
             timestamp: i * 1000,
             is_keyframe: i == 0,
             sequence: i + 1,
+            layer_id: 0,
+            temporal_layer: 0,
+            width: 320,
+            height: 240,
         };
         decoder.decode_frame(&frame).unwrap();
     }
@@ -404,6 +421,10 @@ fn test_decoder_error_handling() {
         timestamp: 0,
         is_keyframe: true,
         sequence: 1,
+        layer_id: 0,
+        temporal_layer: 0,
+        width: 320,
+        height: 240,
     };
     
     assert!(decoder.decode_frame(&frame).is_err());
@@ -487,3 +508,198 @@ fn test_performance_tracking() {
     assert!(elapsed.as_millis() >= 10);
 }
 
+#[test]
+fn test_roi_clipped_to_frame() {
+    let region = CaptureRegion { x: -50, y: 100, width: 200, height: 2000 };
+    let roi = RegionOfInterest::from_capture_region(&region, 1920, 1080, 10, RoiSource::FocusedWindow).unwrap();
+    
+    assert_eq!((roi.x, roi.y, roi.width, roi.height), (0, 100, 150, 980));
+    assert_eq!(roi.source, RoiSource::FocusedWindow);
+    
+    let outside = CaptureRegion { x: 2000, y: 0, width: 100, height: 100 };
+    assert!(RegionOfInterest::from_capture_region(&outside, 1920, 1080, 10, RoiSource::Manual).is_none());
+}
+
+#[test]
+fn test_roi_around_cursor() {
+    let roi = RegionOfInterest::around_cursor(10, 10, 32, 1920, 1080, 100).unwrap();
+    
+    assert_eq!((roi.x, roi.y, roi.width, roi.height), (0, 0, 42, 42));
+    assert_eq!(roi.quality_offset, RegionOfInterest::MAX_QUALITY_OFFSET);
+    assert_eq!(roi.source, RoiSource::Cursor);
+}
+
+#[test]
+fn test_qp_offset_map() {
+    let regions = [
+        RegionOfInterest::new(0, 0, 32, 32, 4, RoiSource::ChangedTiles),
+        RegionOfInterest::new(16, 16, 16, 16, 10, RoiSource::Cursor),
+        RegionOfInterest::new(48, 0, 16, 16, -6, RoiSource::Manual),
+    ];
+    let map = QpOffsetMap::build(64, 40, 16, &regions);
+    
+    assert_eq!((map.columns, map.rows), (4, 3));
+    assert_eq!(map.delta_at(0, 0), -4);
+    // Overlapping regions: highest quality offset wins
+    assert_eq!(map.delta_at(20, 20), -10);
+    assert_eq!(map.delta_at(50, 5), 6);
+    assert_eq!(map.delta_at(40, 39), 0);
+    assert!(!map.is_empty());
+    assert!(QpOffsetMap::build(64, 40, 16, &[]).is_empty());
+}
+
+#[test]
+fn test_qp_offset_map_oversized_region() {
+    let regions = [RegionOfInterest::new(10, 20, u32::MAX, u32::MAX, 6, RoiSource::Manual)];
+    let map = QpOffsetMap::build(64, 40, 16, &regions);
+    
+    // Clipped to the frame: every block from column 0, row 1 onwards
+    assert_eq!(map.delta_at(0, 0), 0);
+    assert_eq!(map.delta_at(0, 16), -6);
+    assert_eq!(map.delta_at(63, 39), -6);
+}
+
+#[test]
+fn test_roi_around_cursor_large_radius() {
+    let roi = RegionOfInterest::around_cursor(100, 100, u32::MAX, 1920, 1080, 5).unwrap();
+    
+    assert_eq!((roi.x, roi.y, roi.width, roi.height), (0, 0, 1920, 1080));
+}
+
+/// Encoder recording how many ROI hints were active for each frame
+struct RoiRecordingEncoder {
+    inner: MockVideoEncoder,
+    regions: Vec<RegionOfInterest>,
+    per_frame: Vec<usize>,
+}
+
+impl VideoEncoder for RoiRecordingEncoder {
+    fn configure(&mut self, config: EncoderConfig) -> Result<()> {
+        self.inner.configure(config)
+    }
+    
+    fn encode_frame(&mut self, frame: &[u8], timestamp: u64) -> Result<EncodedFrame> {
+        self.per_frame.push(self.regions.len());
+        self.inner.encode_frame(frame, timestamp)
+    }
+    
+    fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
+        self.inner.flush()
+    }
+    
+    fn get_stats(&self) -> EncoderStats {
+        self.inner.get_stats()
+    }
+    
+    fn reset(&mut self) -> Result<()> {
+        self.inner.reset()
+    }
+    
+    fn supports_roi(&self) -> bool {
+        true
+    }
+    
+    fn set_roi(&mut self, regions: &[RegionOfInterest]) -> Result<()> {
+        self.regions = regions.to_vec();
+        Ok(())
+    }
+    
+    fn roi(&self) -> &[RegionOfInterest] {
+        &self.regions
+    }
+}
+
+#[test]
+fn test_roi_hints_apply_to_one_frame() {
+    let mut encoder = RoiRecordingEncoder { inner: MockVideoEncoder::new(), regions: Vec::new(), per_frame: Vec::new() };
+    encoder.configure(EncoderConfig { width: 64, height: 64, ..Default::default() }).unwrap();
+    let frame = vec![0u8; 64 * 64 * 4];
+    let roi = [RegionOfInterest::new(0, 0, 16, 16, 8, RoiSource::Cursor)];
+    
+    encoder.encode_frame_with_roi(&frame, 0, &roi).unwrap();
+    encoder.encode_frame(&frame, 1).unwrap();
+    assert_eq!(encoder.per_frame, [1, 0]);
+    
+    // Persistent hints survive a frame with its own hints
+    let persistent = [roi[0], RegionOfInterest::new(32, 32, 16, 16, 4, RoiSource::Manual)];
+    encoder.set_roi(&persistent).unwrap();
+    encoder.encode_frame_with_roi(&frame, 2, &roi).unwrap();
+    encoder.encode_frame(&frame, 3).unwrap();
+    assert_eq!(encoder.per_frame, [1, 0, 1, 2]);
+    assert_eq!(encoder.roi(), persistent);
+    
+    // OpenH264 takes no QP map, so H.264 encodes ignore the hints
+    let mut h264 = H264Encoder::new().unwrap();
+    h264.configure(EncoderConfig { width: 64, height: 64, ..Default::default() }).unwrap();
+    assert!(!h264.supports_roi());
+    assert!(h264.encode_frame_with_roi(&frame, 0, &roi).is_ok());
+}
+
+#[test]
+fn test_encoder_without_roi_support_ignores_hints() {
+    let mut encoder = MockVideoEncoder::new();
+    encoder.configure(EncoderConfig {
+        width: 64,
+        height: 64,
+        ..Default::default()
+    }).unwrap();
+    
+    let roi = [RegionOfInterest::new(0, 0, 16, 16, 8, RoiSource::Cursor)];
+    let frame = vec![0u8; 64 * 64 * 4];
+    
+    assert!(!encoder.supports_roi());
+    assert!(encoder.encode_frame_with_roi(&frame, 0, &roi).is_ok());
+}
+
+#[test]
+fn test_encoder_temporal_layers() {
+    let mut encoder = VP9Encoder::new().unwrap();
+    encoder.configure(EncoderConfig {
+        codec: VideoCodec::VP9,
+        width: 64,
+        height: 64,
+        keyframe_interval: 6,
+        scalability_mode: ScalabilityMode::L1T3,
+        ..Default::default()
+    }).unwrap();
+    
+    let frame = vec![0u8; 64 * 64 * 4];
+    let layers: Vec<u8> = (0..8)
+        .map(|i| encoder.encode_frame(&frame, i * 33_333).unwrap().temporal_layer)
+        .collect();
+    assert_eq!(layers, vec![0, 2, 1, 2, 0, 2, 0, 2]);
+    
+    let mut h265 = create_encoder(VideoCodec::H265).unwrap();
+    assert!(!h265.supports_scalability_mode(ScalabilityMode::L1T2));
+    assert!(h265.configure(EncoderConfig {
+        codec: VideoCodec::H265,
+        scalability_mode: ScalabilityMode::L1T2,
+        ..Default::default()
+    }).is_err());
+}
+
+#[test]
+fn test_decode_to_viewer_size() {
+    let mut encoder = create_encoder(VideoCodec::VP8).unwrap();
+    encoder.configure(EncoderConfig {
+        codec: VideoCodec::VP8,
+        width: 64,
+        height: 32,
+        ..Default::default()
+    }).unwrap();
+    let frame = encoder.encode_frame(&vec![0u8; 64 * 32 * 4], 0).unwrap();
+    
+    let mut decoder = create_decoder(VideoCodec::VP8).unwrap();
+    let picture = decoder.decode_frame(&frame).unwrap();
+    assert_eq!((picture.width, picture.height, picture.format), (64, 32, PixelFormat::I420));
+    
+    let output = DecoderOutput {
+        size: Some((32, 16)),
+        format: PixelFormat::Rgba,
+        ..Default::default()
+    };
+    let picture = decoder.decode_frame_to(&frame, &output).unwrap();
+    assert_eq!((picture.width, picture.height, picture.stride), (32, 16, 128));
+    assert_eq!(picture.format, PixelFormat::Rgba);
+    assert_eq!(picture.data.len(), 32 * 16 * 4);
+}
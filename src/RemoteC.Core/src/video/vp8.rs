@@ -1,6 +1,6 @@
 //! VP8 video encoder implementation
 
-use super::{VideoEncoder, VideoDecoder, EncoderConfig, EncodedFrame, EncoderStats, DecoderStats, VideoCodec};
+use super::{scale::{self, Scaler}, svc, ScalabilityMode, VideoEncoder, VideoDecoder, EncoderConfig, EncodedFrame, EncoderStats, DecoderStats, VideoCodec, DecodedPicture, DecoderOutput, PixelFormat};
 use crate::{Result, RemoteCError};
 use std::sync::Mutex;
 
@@ -9,6 +9,8 @@ pub struct VP8Encoder {
     config: Option<EncoderConfig>,
     stats: Mutex<EncoderStats>,
     frame_counter: u64,
+    /// Resampler from the input to the encoded resolution
+    scaler: Option<Scaler>,
 }
 
 impl VP8Encoder {
@@ -17,6 +19,7 @@ impl VP8Encoder {
             config: None,
             stats: Mutex::new(EncoderStats::default()),
             frame_counter: 0,
+            scaler: None,
         })
     }
 }
@@ -28,6 +31,13 @@ impl VideoEncoder for VP8Encoder {
                 "Invalid codec for VP8Encoder".to_string()
             ));
         }
+        
+        if !self.supports_scalability_mode(config.scalability_mode) {
+            return Err(RemoteCError::EncodingError(
+                format!("Scalability mode {} not supported by VP8Encoder", config.scalability_mode)
+            ));
+        }
+        self.scaler = None;
         self.config = Some(config);
         Ok(())
     }
@@ -36,17 +46,13 @@ impl VideoEncoder for VP8Encoder {
         let config = self.config.as_ref()
             .ok_or_else(|| RemoteCError::EncodingError("Encoder not configured".to_string()))?;
         
-        // Validate frame size
-        let expected_size = (config.width * config.height * 4) as usize;
-        if frame.len() != expected_size {
-            return Err(RemoteCError::EncodingError(
-                format!("Invalid frame size: expected {}, got {}", expected_size, frame.len())
-            ));
-        }
+        // Validate frame size and scale to the encoded resolution
+        let frame = scale::prepare_encoder_input(config, &mut self.scaler, frame)?;
         
         // TODO: Implement actual VP8 encoding using libvpx
         self.frame_counter += 1;
         let is_keyframe = self.frame_counter % config.keyframe_interval as u64 == 1;
+        let temporal_layer = svc::temporal_layer(config.scalability_mode, self.frame_counter, config.keyframe_interval);
         
         let mut stats = self.stats.lock().unwrap();
         stats.frames_encoded += 1;
@@ -59,9 +65,17 @@ impl VideoEncoder for VP8Encoder {
             timestamp,
             is_keyframe,
             sequence: self.frame_counter,
+            layer_id: 0,
+            temporal_layer,
+            width: config.width,
+            height: config.height,
         })
     }
     
+    fn supports_scalability_mode(&self, _mode: ScalabilityMode) -> bool {
+        true
+    }
+    
     fn flush(&mut self) -> Result<Vec<EncodedFrame>> {
         Ok(Vec::new())
     }
@@ -80,12 +94,15 @@ impl VideoEncoder for VP8Encoder {
 /// VP8 video decoder
 pub struct VP8Decoder {
     stats: Mutex<DecoderStats>,
+    /// Resampler to the requested output size
+    scaler: Option<Scaler>,
 }
 
 impl VP8Decoder {
     pub fn new() -> Result<Self> {
         Ok(Self {
             stats: Mutex::new(DecoderStats::default()),
+            scaler: None,
         })
     }
 }
@@ -100,12 +117,16 @@ impl VideoDecoder for VP8Decoder {
         Ok(())
     }
     
-    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<Vec<u8>> {
+    fn decode_frame(&mut self, frame: &EncodedFrame) -> Result<DecodedPicture> {
         // TODO: Implement actual VP8 decoding
         let mut stats = self.stats.lock().unwrap();
         stats.frames_decoded += 1;
         
-        Ok(vec![0; frame.data.len() * 80])
+        Ok(DecodedPicture::black(frame.width, frame.height, PixelFormat::I420))
+    }
+    
+    fn decode_frame_to(&mut self, frame: &EncodedFrame, output: &DecoderOutput) -> Result<DecodedPicture> {
+        self.decode_frame(frame)?.into_output_with(output, &mut self.scaler)
     }
     
     fn get_stats(&self) -> DecoderStats {